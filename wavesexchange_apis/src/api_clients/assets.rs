@@ -1,4 +1,4 @@
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{models::ids::AssetId, ApiResult, BaseApi, HttpClient, NetworkUrls};
 use itertools::Itertools;
 
 #[derive(Clone, Debug)]
@@ -6,15 +6,23 @@ pub struct AssetsService;
 
 impl BaseApi for AssetsService {}
 
+impl NetworkUrls for AssetsService {
+    const MAINNET_URL: &'static str = "https://waves.exchange/api/v1/assets";
+    const TESTNET_URL: &'static str = "https://testnet.waves.exchange/api/v1/assets";
+}
+
 impl HttpClient<AssetsService> {
     pub async fn get(
         &self,
-        asset_ids: impl IntoIterator<Item = impl Into<String>>,
+        asset_ids: impl IntoIterator<Item = impl Into<AssetId>>,
         height: Option<u32>,
         format: dto::OutputFormat,
         include_metadata: bool,
     ) -> ApiResult<dto::AssetResponse> {
-        let ids = asset_ids.into_iter().map(Into::into).collect::<Vec<_>>();
+        let ids = asset_ids
+            .into_iter()
+            .map(|id| AssetId::into_inner(id.into()))
+            .collect::<Vec<_>>();
         if ids.is_empty() {
             return Ok(dto::AssetResponse {
                 data: vec![],
@@ -25,12 +33,11 @@ impl HttpClient<AssetsService> {
         meta.height__gte = height;
         meta.format = format.to_option();
         meta.include_metadata = include_metadata;
-        let meta = serde_qs::to_string(&meta).expect("query string");
 
         let body = dto::AssetRequest { ids };
 
         self.create_req_handler(
-            self.http_post(format!("?{meta}")).json(&body),
+            self.http_post_with_query("", &meta)?.json(&body),
             "assets::get_assets",
         )
         .execute()
@@ -76,6 +83,7 @@ impl HttpClient<AssetsService> {
             include_metadata: req.include_metadata,
             search: req.search,
             ticker: req.ticker,
+            ticker__in: req.tickers,
             ext_ticker: req.ext_ticker,
             smart: req.smart,
             label: req.label,
@@ -84,14 +92,12 @@ impl HttpClient<AssetsService> {
             limit: req.limit,
             after: req.after,
         };
-        let meta = serde_qs::to_string(&meta).expect("query string");
-
         let body = req.ids.map(|ids| dto::AssetRequest { ids });
 
         let request_builder = if let Some(body) = body {
-            self.http_post(format!("?{meta}")).json(&body)
+            self.http_post_with_query("", &meta)?.json(&body)
         } else {
-            self.http_get(format!("?{meta}"))
+            self.http_get_with_query("", &meta)?
         };
         self.create_req_handler(request_builder, "assets::get_assets")
             .execute()
@@ -99,6 +105,245 @@ impl HttpClient<AssetsService> {
     }
 }
 
+#[cfg(feature = "loaders")]
+pub use ticker_resolver::TickerResolver;
+
+#[cfg(feature = "loaders")]
+mod ticker_resolver {
+    use super::{dto, AssetsService};
+    use crate::{Error, HttpClient};
+    use std::{collections::HashMap, sync::OnceLock, time::Duration};
+    use wavesexchange_loaders::{CachedLoader, Loader, LoaderError, TimedCache};
+
+    const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(600);
+    const PAGE_SIZE: u32 = 100;
+
+    static CACHE_TTL: OnceLock<Duration> = OnceLock::new();
+
+    /// Resolves asset tickers (case-insensitive) to asset ids, `None` for unknown tickers.
+    ///
+    /// Concurrent lookups are batched, and each batch of uncached tickers
+    /// is resolved by a single search of the assets having these tickers.
+    /// Results, including unknown tickers, are cached in a process-wide cache
+    /// (see `CachedLoader`) shared by all the resolvers, see `set_cache_ttl`.
+    ///
+    /// ```no_run
+    /// # use wavesexchange_apis::{assets::TickerResolver, mainnet_client, AssetsService};
+    /// # tokio_test::block_on(async {
+    /// let resolver = TickerResolver::new(mainnet_client::<AssetsService>());
+    /// let usdn = resolver.resolve("usdn").await.unwrap();
+    /// # })
+    /// ```
+    #[derive(Clone, Debug)]
+    pub struct TickerResolver {
+        client: HttpClient<AssetsService>,
+    }
+
+    impl TickerResolver {
+        pub fn new(client: HttpClient<AssetsService>) -> Self {
+            TickerResolver { client }
+        }
+
+        /// Set the TTL of the process-wide cache of all the resolvers (10 minutes by default),
+        /// rounded up to whole seconds.
+        ///
+        /// The TTL can only be set once and before the first lookup,
+        /// otherwise the TTL in effect is returned as the error.
+        pub fn set_cache_ttl(ttl: Duration) -> Result<(), Duration> {
+            let secs = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+            CACHE_TTL
+                .set(Duration::from_secs(secs.max(1)))
+                .map_err(|_| cache_ttl())
+        }
+
+        pub async fn resolve(&self, ticker: &str) -> Result<Option<String>, LoaderError<Error>> {
+            self.load(normalize(ticker)).await
+        }
+
+        /// Asset ids by the tickers as they were passed
+        pub async fn resolve_many(
+            &self,
+            tickers: &[&str],
+        ) -> Result<HashMap<String, Option<String>>, LoaderError<Error>> {
+            let mut normalized = tickers.iter().map(|t| normalize(t)).collect::<Vec<_>>();
+            normalized.sort_unstable();
+            normalized.dedup();
+            let ids = self.load_many(normalized).await?;
+            Ok(tickers
+                .iter()
+                .map(|&ticker| (ticker.to_owned(), ids[&normalize(ticker)].clone()))
+                .collect())
+        }
+
+        /// Asset ids by the normalized tickers (the first asset wins if a ticker is not unique)
+        async fn search_tickers(
+            &self,
+            tickers: &[String],
+        ) -> Result<HashMap<String, String>, Error> {
+            let mut ids = HashMap::new();
+            let mut cursor = None;
+            loop {
+                let page = self
+                    .client
+                    .new_search()
+                    .with_tickers(tickers)
+                    .with_limit(PAGE_SIZE)
+                    .with_cursor(cursor)
+                    .search()
+                    .await?;
+                for asset in page.data {
+                    let (ticker, id) = match asset.data {
+                        dto::AssetLookup::Found(dto::AssetInfo::Full(info)) => {
+                            (info.ticker, info.id)
+                        }
+                        dto::AssetLookup::Found(dto::AssetInfo::Brief(info)) => {
+                            (info.ticker, info.id)
+                        }
+                        dto::AssetLookup::NotFound | dto::AssetLookup::Absent => continue,
+                    };
+                    if let Some(ticker) = ticker {
+                        ids.entry(normalize(&ticker)).or_insert(id);
+                    }
+                }
+                match page.cursor {
+                    Some(next) => cursor = Some(next),
+                    None => return Ok(ids),
+                }
+            }
+        }
+    }
+
+    fn normalize(ticker: &str) -> String {
+        ticker.to_uppercase()
+    }
+
+    /// The TTL of the cache, fixed by the first call
+    fn cache_ttl() -> Duration {
+        *CACHE_TTL.get_or_init(|| DEFAULT_CACHE_TTL)
+    }
+
+    #[async_trait::async_trait]
+    impl CachedLoader<String, Option<String>> for TickerResolver {
+        type Cache = TimedCache<String, Option<String>>;
+        type Error = Error;
+
+        async fn load_fn(&mut self, tickers: &[String]) -> Result<Vec<Option<String>>, Error> {
+            let ids = self.search_tickers(tickers).await?;
+            Ok(tickers
+                .iter()
+                .map(|ticker| ids.get(ticker).cloned())
+                .collect())
+        }
+
+        fn init_cache() -> Self::Cache {
+            TimedCache::with_lifespan(cache_ttl().as_secs())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_ticker_resolver() {
+            use crate::mock::{MockResponse, MockResponses};
+            use reqwest::Method;
+            use serde_json::json;
+
+            let asset = |ticker: Option<&str>, id: &str| {
+                json!({
+                    "type": "asset",
+                    "data": {"ticker": ticker, "id": id, "name": id, "smart": false},
+                    "metadata": null
+                })
+            };
+            let mocks = MockResponses::new()
+                .on(
+                    Method::GET,
+                    "/",
+                    MockResponse::json(&json!({
+                        "data": [
+                            asset(Some("USDN"), "usdn_id"),
+                            asset(Some("Usdn"), "other_usdn_id"),
+                            asset(Some("BTC"), "btc_id"),
+                            asset(None, "no_ticker_id"),
+                        ],
+                        "cursor": null
+                    })),
+                )
+                .on(
+                    Method::GET,
+                    "/",
+                    MockResponse::json(&json!({
+                        "data": [asset(Some("eth"), "eth_id")],
+                        "cursor": null
+                    })),
+                );
+            let resolver = TickerResolver::new(HttpClient::mocked(mocks.clone()));
+            let searched = |n: usize| {
+                let query = mocks.received()[n].query.clone().unwrap_or_default();
+                let mut tickers = url_params(&query, "ticker__in");
+                tickers.sort_unstable();
+                tickers
+            };
+
+            // one search for all the distinct tickers of a batch
+            let ids = resolver
+                .resolve_many(&["usdn", "BTC", "Usdn", "unknown"])
+                .await
+                .unwrap();
+            assert_eq!(mocks.received().len(), 1);
+            assert_eq!(searched(0), ["BTC", "UNKNOWN", "USDN"]);
+            assert_eq!(ids.len(), 4);
+            assert_eq!(ids["usdn"].as_deref(), Some("usdn_id"));
+            assert_eq!(ids["Usdn"].as_deref(), Some("usdn_id"));
+            assert_eq!(ids["BTC"].as_deref(), Some("btc_id"));
+            assert_eq!(ids["unknown"], None);
+
+            // cache hits, unknown tickers included
+            assert_eq!(
+                resolver.resolve("USDN").await.unwrap().as_deref(),
+                Some("usdn_id")
+            );
+            assert_eq!(
+                resolver.resolve("btc").await.unwrap().as_deref(),
+                Some("btc_id")
+            );
+            assert_eq!(resolver.resolve("UNKNOWN").await.unwrap(), None);
+            assert_eq!(mocks.received().len(), 1);
+
+            // cache miss
+            assert_eq!(
+                resolver.resolve("ETH").await.unwrap().as_deref(),
+                Some("eth_id")
+            );
+            assert_eq!(mocks.received().len(), 2);
+            assert_eq!(searched(1), ["ETH"]);
+
+            // the TTL is fixed by the first lookup
+            assert_eq!(
+                TickerResolver::set_cache_ttl(Duration::from_millis(1500)),
+                Err(DEFAULT_CACHE_TTL)
+            );
+        }
+
+        /// Values of a sequence parameter serialized by `serde_qs` (`name[0]=a&name[1]=b`)
+        fn url_params(query: &str, name: &str) -> Vec<String> {
+            query
+                .split('&')
+                .filter_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    let key = percent_encoding::percent_decode_str(key)
+                        .decode_utf8()
+                        .ok()?;
+                    key.starts_with(&format!("{name}["))
+                        .then(|| value.to_owned())
+                })
+                .collect()
+        }
+    }
+}
+
 pub mod request {
     use super::{dto, AssetsService};
     use crate::{ApiResult, HttpClient};
@@ -119,6 +364,8 @@ pub mod request {
         pub(super) ids: Option<Vec<String>>,
         /// Ticker value or `*` for any asset having ticker value. Default is None.
         pub(super) ticker: Option<String>,
+        /// Ticker values to locate. Default is None.
+        pub(super) tickers: Option<Vec<String>>,
         /// External ticker value or `*` for any asset having external ticker value. Default is None.
         pub(super) ext_ticker: Option<String>,
         /// Asset labels contain label value or `*` for assets having any label. Default is None.
@@ -147,6 +394,7 @@ pub mod request {
                 search: None,
                 ids: None,
                 ticker: None,
+                tickers: None,
                 ext_ticker: None,
                 label: None,
                 labels: None,
@@ -188,6 +436,15 @@ pub mod request {
             self
         }
 
+        /// Ticker values to locate. Default is None.
+        pub fn with_tickers(
+            mut self,
+            tickers: impl IntoIterator<Item = impl Into<String>>,
+        ) -> Self {
+            self.tickers = Some(tickers.into_iter().map(Into::into).collect());
+            self
+        }
+
         /// External ticker value or `*` for any asset having external ticker value. Default is None.
         pub fn with_ext_ticker(mut self, ext_ticker: impl Into<String>) -> Self {
             self.ext_ticker = Some(ext_ticker.into());
@@ -245,9 +502,10 @@ pub mod request {
 }
 
 pub mod dto {
-    use crate::models::dto::DataEntryValue;
+    use crate::models::{amount::scale_amount, dto::DataEntryValue};
+    use bigdecimal::BigDecimal;
     use chrono::{DateTime, Utc};
-    use serde::{Deserialize, Serialize};
+    use serde::{Deserialize, Deserializer, Serialize};
     use std::collections::HashMap;
 
     #[derive(Debug, Deserialize)]
@@ -259,11 +517,55 @@ pub mod dto {
     #[derive(Clone, Debug, Deserialize)]
     #[serde(tag = "type", rename = "asset")]
     pub struct AssetData {
-        pub data: Option<AssetInfo>,
+        #[serde(default, deserialize_with = "deserialize_asset_lookup")]
+        pub data: AssetLookup,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub metadata: Option<AssetMetadata>,
     }
 
+    /// `data` of an asset, telling a nonexistent asset (`"data": null`)
+    /// from the data missing in the response (no `data` key)
+    #[derive(Clone, Debug, Default)]
+    pub enum AssetLookup {
+        Found(AssetInfo),
+        /// The asset doesn't exist
+        NotFound,
+        /// No data in the response (e.g. requested with `OutputFormat::None`),
+        /// which says nothing about the existence of the asset
+        #[default]
+        Absent,
+    }
+
+    impl AssetLookup {
+        pub fn found(&self) -> Option<&AssetInfo> {
+            match self {
+                AssetLookup::Found(info) => Some(info),
+                AssetLookup::NotFound | AssetLookup::Absent => None,
+            }
+        }
+
+        pub fn into_found(self) -> Option<AssetInfo> {
+            match self {
+                AssetLookup::Found(info) => Some(info),
+                AssetLookup::NotFound | AssetLookup::Absent => None,
+            }
+        }
+
+        pub fn is_not_found(&self) -> bool {
+            matches!(self, AssetLookup::NotFound)
+        }
+    }
+
+    /// Only called if the key is present, otherwise it's `AssetLookup::Absent` by default
+    fn deserialize_asset_lookup<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<AssetLookup, D::Error> {
+        Ok(match Option::<AssetInfo>::deserialize(deserializer)? {
+            Some(info) => AssetLookup::Found(info),
+            None => AssetLookup::NotFound,
+        })
+    }
+
     #[derive(Clone, Debug, Deserialize)]
     #[serde(untagged)]
     pub enum AssetInfo {
@@ -282,6 +584,123 @@ pub mod dto {
     #[derive(Clone, Debug, Deserialize)]
     pub struct OracleData(pub HashMap<String, DataEntryValue>);
 
+    /// Keys of the oracle data entries, as published by the WX oracle
+    /// (with the `_<asset_id>` suffix already stripped by the assets service).
+    pub mod oracle_keys {
+        /// Address of the oracle which provided the data (string)
+        pub const ORACLE_ADDRESS: &str = "oracle_address";
+        /// Verification status (integer), see `VerificationStatus`
+        pub const STATUS: &str = "status";
+        /// Prefix of the localized description keys, e.g. `description_en` (string)
+        pub const DESCRIPTION_PREFIX: &str = "description_";
+        /// Project link (string)
+        pub const LINK: &str = "link";
+        /// Asset logo, either url or data url (string)
+        pub const LOGO: &str = "logo";
+    }
+
+    /// Asset verification status, as defined by the oracle's `status` key
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+    pub enum VerificationStatus {
+        /// -2
+        Scam,
+        /// -1
+        Suspicious,
+        /// 0
+        Unknown,
+        /// 1
+        Detailed,
+        /// 2
+        Verified,
+        /// Any other value
+        Other(i64),
+    }
+
+    impl From<i64> for VerificationStatus {
+        fn from(status: i64) -> Self {
+            match status {
+                -2 => VerificationStatus::Scam,
+                -1 => VerificationStatus::Suspicious,
+                0 => VerificationStatus::Unknown,
+                1 => VerificationStatus::Detailed,
+                2 => VerificationStatus::Verified,
+                other => VerificationStatus::Other(other),
+            }
+        }
+    }
+
+    impl OracleData {
+        /// String value of the key, `None` if the key is missing or is not a string
+        pub fn get_str(&self, key: &str) -> Option<&str> {
+            self.0.get(key)?.try_as_str().ok()
+        }
+
+        /// Integer value of the key, `None` if the key is missing or is not an integer
+        pub fn get_int(&self, key: &str) -> Option<i64> {
+            self.0.get(key)?.try_as_integer().ok()
+        }
+
+        pub fn oracle_address(&self) -> Option<&str> {
+            self.get_str(oracle_keys::ORACLE_ADDRESS)
+        }
+
+        pub fn verification_status(&self) -> Option<VerificationStatus> {
+            self.get_int(oracle_keys::STATUS)
+                .map(VerificationStatus::from)
+        }
+
+        /// Description in the given language (`en`, `ru`, etc.)
+        pub fn description(&self, lang: &str) -> Option<&str> {
+            self.get_str(&format!("{}{}", oracle_keys::DESCRIPTION_PREFIX, lang))
+        }
+
+        pub fn link(&self) -> Option<&str> {
+            self.get_str(oracle_keys::LINK)
+        }
+
+        pub fn logo(&self) -> Option<&str> {
+            self.get_str(oracle_keys::LOGO)
+        }
+    }
+
+    /// Accessors below look through all the oracles in order,
+    /// and the first one having a value of the expected type wins.
+    /// Use `AssetMetadata::oracle` to read the data of a specific oracle.
+    impl AssetMetadata {
+        /// Data provided by the oracle with the given address
+        pub fn oracle(&self, oracle_address: &str) -> Option<&OracleData> {
+            self.oracle_data
+                .iter()
+                .find(|od| od.oracle_address() == Some(oracle_address))
+        }
+
+        pub fn get_str(&self, key: &str) -> Option<&str> {
+            self.oracle_data.iter().find_map(|od| od.get_str(key))
+        }
+
+        pub fn get_int(&self, key: &str) -> Option<i64> {
+            self.oracle_data.iter().find_map(|od| od.get_int(key))
+        }
+
+        pub fn verification_status(&self) -> Option<VerificationStatus> {
+            self.oracle_data
+                .iter()
+                .find_map(OracleData::verification_status)
+        }
+
+        pub fn description(&self, lang: &str) -> Option<&str> {
+            self.oracle_data.iter().find_map(|od| od.description(lang))
+        }
+
+        pub fn link(&self) -> Option<&str> {
+            self.oracle_data.iter().find_map(OracleData::link)
+        }
+
+        pub fn logo(&self) -> Option<&str> {
+            self.oracle_data.iter().find_map(OracleData::logo)
+        }
+    }
+
     #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
     #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
     pub enum AssetLabel {
@@ -327,6 +746,18 @@ pub mod dto {
         pub smart: bool,
     }
 
+    impl FullAssetInfo {
+        /// `precision` as the number of decimals (clamped, as it's never out of range in fact)
+        pub fn decimals(&self) -> u8 {
+            self.precision.clamp(0, u8::MAX.into()) as u8
+        }
+
+        /// Total quantity as a decimal value
+        pub fn quantity_decimal(&self) -> BigDecimal {
+            scale_amount(self.quantity, self.decimals())
+        }
+    }
+
     #[derive(Clone, Debug, Deserialize)]
     pub struct BriefAssetInfo {
         pub ticker: Option<String>,
@@ -361,6 +792,7 @@ pub mod dto {
         pub include_metadata: bool,
         pub search: Option<String>,
         pub ticker: Option<String>,
+        pub ticker__in: Option<Vec<String>>,
         pub ext_ticker: Option<String>,
         pub smart: Option<bool>,
         pub label: Option<String>,
@@ -374,4 +806,99 @@ pub mod dto {
     pub(super) struct AssetRequest {
         pub ids: Vec<String>,
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_oracle_data_accessors() {
+            let metadata = r#"{
+            "oracle_data": [
+                {
+                    "oracle_address": "3PPNhHYkkEy13gRWDCaruQyhNbX2MrLDYi1",
+                    "status": "2",
+                    "description_en": "First oracle description",
+                    "link": "https://first.example"
+                },
+                {
+                    "oracle_address": "3P8ZP5RRbBNCBxYqhWsgfpTWkGBZymRtcBa",
+                    "status": 2,
+                    "description_en": "Second oracle description",
+                    "description_ru": "Описание второго оракула",
+                    "logo": "data:image/png;base64,AAAA",
+                    "link": 42
+                }
+            ],
+            "labels": ["WA_VERIFIED"],
+            "sponsor_balance": null,
+            "has_image": true
+        }"#;
+            let metadata = serde_json::from_str::<AssetMetadata>(metadata).unwrap();
+
+            // first matching oracle wins
+            assert_eq!(metadata.description("en"), Some("First oracle description"));
+            assert_eq!(metadata.link(), Some("https://first.example"));
+            // wrong type in the first oracle, so the second one is used
+            assert_eq!(
+                metadata.verification_status(),
+                Some(VerificationStatus::Verified)
+            );
+            // missing in the first oracle
+            assert_eq!(metadata.description("ru"), Some("Описание второго оракула"));
+            assert_eq!(metadata.logo(), Some("data:image/png;base64,AAAA"));
+            // missing everywhere
+            assert_eq!(metadata.description("de"), None);
+            assert_eq!(metadata.get_str("ticker"), None);
+            assert_eq!(metadata.get_int("ticker"), None);
+
+            // selected oracle
+            let second = metadata
+                .oracle("3P8ZP5RRbBNCBxYqhWsgfpTWkGBZymRtcBa")
+                .unwrap();
+            assert_eq!(second.description("en"), Some("Second oracle description"));
+            assert_eq!(second.link(), None);
+            assert_eq!(second.get_int("link"), Some(42));
+            let first = metadata
+                .oracle("3PPNhHYkkEy13gRWDCaruQyhNbX2MrLDYi1")
+                .unwrap();
+            assert_eq!(first.verification_status(), None);
+            assert_eq!(first.get_str("status"), Some("2"));
+            assert!(metadata.oracle("unknown").is_none());
+
+            assert_eq!(VerificationStatus::from(-2), VerificationStatus::Scam);
+            assert_eq!(VerificationStatus::from(5), VerificationStatus::Other(5));
+        }
+
+        #[test]
+        fn test_asset_lookup() {
+            let parse = |json: &str| serde_json::from_str::<AssetData>(json).unwrap().data;
+
+            let found = parse(
+                r#"{
+                "type": "asset",
+                "data": {"ticker": null, "id": "WAVES", "name": "Waves", "smart": false}
+            }"#,
+            );
+            match &found {
+                AssetLookup::Found(AssetInfo::Brief(info)) => assert_eq!(info.id, "WAVES"),
+                other => panic!("unexpected lookup: {other:?}"),
+            }
+            assert!(found.found().is_some());
+            assert!(!found.is_not_found());
+
+            let not_found = parse(r#"{"type": "asset", "data": null}"#);
+            assert!(matches!(not_found, AssetLookup::NotFound));
+            assert!(not_found.is_not_found());
+            assert!(not_found.into_found().is_none());
+
+            let absent = parse(r#"{"type": "asset", "metadata": null}"#);
+            assert!(matches!(absent, AssetLookup::Absent));
+            assert!(!absent.is_not_found());
+            assert!(absent.found().is_none());
+
+            // malformed data is an error rather than any of the above
+            assert!(serde_json::from_str::<AssetData>(r#"{"type": "asset", "data": 1}"#).is_err());
+        }
+    }
 }
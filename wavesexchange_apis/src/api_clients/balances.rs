@@ -1,7 +1,11 @@
 use chrono::{DateTime, Utc};
 
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{
+    models::{asset_id::normalize_waves, dto::Sort},
+    ApiResult, BaseApi, HttpClient,
+};
 use std::fmt::Debug;
+use wavesexchange_warp::pagination::List;
 
 const CHUNK_SIZE: usize = 100;
 
@@ -30,9 +34,12 @@ impl HttpClient<BalancesService> {
 
         let pairs = pairs
             .into_iter()
-            .map(|(address, asset_id)| dto::AddressAssetPair {
-                address: address.into(),
-                asset_id: asset_id.into(),
+            .map(|(address, asset_id)| {
+                let asset_id: String = asset_id.into();
+                dto::AddressAssetPair {
+                    address: address.into(),
+                    asset_id: normalize_waves(&asset_id).to_owned(),
+                }
             })
             .collect::<Vec<_>>();
 
@@ -63,10 +70,11 @@ impl HttpClient<BalancesService> {
         date_from: Option<DateTime<Utc>>,
         date_to: Option<DateTime<Utc>>,
     ) -> ApiResult<dto::BalancesAggResponse> {
+        let asset_id: String = asset_id.into();
         let mut url = format!(
             "balance_history/aggregates/{}/{}",
             address.into(),
-            asset_id.into()
+            normalize_waves(&asset_id)
         );
 
         match (date_from, date_to) {
@@ -86,9 +94,48 @@ impl HttpClient<BalancesService> {
 
         Ok(dto::BalancesAggResponse { items: resp.items })
     }
+
+    /// Balance history of a single address (of all its assets if `asset_id` is `None`),
+    /// one page of it, see `List::page_info` for the cursor of the next one.
+    ///
+    /// Entries are sorted by height ascending, and so are the pages:
+    /// the service is asked for the ascending order (its default is descending),
+    /// so the cursor of a page leads to the greater heights.
+    pub async fn address_balance_history(
+        &self,
+        address: impl AsRef<str>,
+        asset_id: Option<&str>,
+        height_from: Option<u32>,
+        height_to: Option<u32>,
+        limit: usize,
+        after: Option<impl AsRef<str>>,
+    ) -> ApiResult<List<dto::BalanceHistoryEntry>> {
+        let query = dto::BalanceHistoryQueryParams {
+            asset_id: asset_id.map(|id| normalize_waves(id).to_owned()),
+            height_from,
+            height_to,
+            limit,
+            after: after.map(|cursor| cursor.as_ref().to_owned()),
+            sort: Sort::Asc,
+        };
+        let url = format!("balance_history/{}", address.as_ref());
+
+        let mut list: List<dto::BalanceHistoryEntry> = self
+            .create_req_handler(
+                self.http_get_with_query(url, &query)?,
+                "balances::address_balance_history",
+            )
+            .execute()
+            .await?;
+
+        // in case the service ignores the requested order
+        list.items.sort_by_key(|entry| entry.height);
+        Ok(list)
+    }
 }
 
 pub mod dto {
+    use crate::models::dto::Sort;
     use bigdecimal::BigDecimal;
     use chrono::{DateTime, Utc};
     use serde::{Deserialize, Serialize};
@@ -129,4 +176,101 @@ pub mod dto {
         pub amount_end: BigDecimal,
         pub date_stamp: DateTime<Utc>,
     }
+
+    #[derive(Debug, Serialize)]
+    pub(super) struct BalanceHistoryQueryParams {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub asset_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub height_from: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub height_to: Option<u32>,
+        pub limit: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub after: Option<String>,
+        pub sort: Sort,
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+    pub struct BalanceHistoryEntry {
+        pub height: u32,
+        pub timestamp: DateTime<Utc>,
+        pub balance: i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_history_deserialization() {
+        let json = r#"{
+        "type": "list",
+        "page_info": {"has_next_page": true, "last_cursor": "MTIz"},
+        "items": [
+            {"height": 3000002, "timestamp": "2022-01-12T10:02:00Z", "balance": 500000000},
+            {"height": 3000001, "timestamp": "2022-01-12T10:01:00Z", "balance": -1}
+        ]
+    }"#;
+        let list: List<dto::BalanceHistoryEntry> = serde_json::from_str(json).unwrap();
+        assert!(list.page_info.has_next_page);
+        assert_eq!(list.page_info.last_cursor.as_deref(), Some("MTIz"));
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.items[0].height, 3000002);
+        assert_eq!(list.items[0].balance, 500000000);
+        assert_eq!(
+            list.items[0].timestamp,
+            "2022-01-12T10:02:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(list.items[1].balance, -1);
+
+        let qs = serde_qs::to_string(&dto::BalanceHistoryQueryParams {
+            asset_id: Some("WAVES".to_owned()),
+            height_from: None,
+            height_to: Some(3000002),
+            limit: 100,
+            after: None,
+            sort: Sort::Asc,
+        })
+        .unwrap();
+        assert_eq!(qs, "asset_id=WAVES&height_to=3000002&limit=100&sort=asc");
+    }
+
+    #[tokio::test]
+    async fn test_balance_history_ascending() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+        use serde_json::json;
+
+        let ts = "2022-01-12T10:00:00Z";
+        let entry = |height: u32, balance: i64| json!({"height": height, "timestamp": ts, "balance": balance});
+        let mocks = MockResponses::new().on(
+            Method::GET,
+            "/balance_history/3PAddress",
+            MockResponse::json(&json!({
+                "type": "list",
+                "page_info": {"has_next_page": false, "last_cursor": null},
+                "items": [entry(30, 3), entry(10, 1), entry(20, 2)]
+            })),
+        );
+        let client = HttpClient::<BalancesService>::mocked(mocks.clone());
+
+        let list = client
+            .address_balance_history("3PAddress", Some("WAVES"), Some(10), None, 3, Some("abc"))
+            .await
+            .unwrap();
+        let heights = list.items.iter().map(|e| e.height).collect::<Vec<_>>();
+        assert_eq!(heights, [10, 20, 30]);
+        assert_eq!(
+            list.items.iter().map(|e| e.balance).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        let received = mocks.received();
+        assert_eq!(
+            received[0].query.as_deref(),
+            Some("asset_id=WAVES&height_from=10&limit=3&after=abc&sort=asc")
+        );
+    }
 }
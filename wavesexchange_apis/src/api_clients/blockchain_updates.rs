@@ -1,53 +1,97 @@
 use crate::{ApiResult, BaseApi, Error, GrpcClient};
 use itertools::Itertools;
 use std::{
-    collections::HashMap,
-    convert::{From, Into, TryFrom, TryInto},
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    convert::{From, TryFrom},
+    time::Duration,
 };
-use waves_protobuf_schemas::{
-    tonic,
-    waves::events::{
-        blockchain_updated::{Append, Update},
-        grpc::{GetBlockUpdateRequest, GetBlockUpdateResponse},
-        state_update::BalanceUpdate,
-        BlockchainUpdated,
-    },
+use waves_protobuf_schemas::waves::events::{
+    blockchain_updated::{Append, Update},
+    grpc::{GetBlockUpdateRequest, GetBlockUpdateResponse},
+    state_update::BalanceUpdate,
+    BlockchainUpdated,
 };
 
+/// Breaking change in 0.2: these were tuple structs with a public `String` field,
+/// now they are the validated `models::ids` newtypes. Instead of `Address(s)` and `address.0`
+/// use `Address::new_unchecked(s)` (or `Address::from(s)`) and `address.as_str()`
+/// (or `address.into_inner()`), the same for `AssetId`.
+pub use crate::models::ids::{Address, AssetId};
+
 #[derive(Clone, Debug)]
 pub struct BlockchainUpdates;
 
 impl BaseApi for BlockchainUpdates {}
 
 impl GrpcClient<BlockchainUpdates> {
+    /// Fetch transactions at the given height, using the client's default timeout (if any)
     pub async fn fetch_transactions_at_height(
         &self,
         height: u32,
     ) -> ApiResult<TransactionsAtHeight> {
-        let request = tonic::Request::new(GetBlockUpdateRequest {
-            height: height as i32,
-        });
+        self.fetch_transactions_at_height_with_timeout(height, self.timeout())
+            .await
+    }
 
-        self.grpc_client
-            .clone()
-            .get_block_update(request)
+    /// Fetch transactions at the given height, overriding the client's default timeout.
+    /// Passing `None` disables the deadline for this call.
+    pub async fn fetch_transactions_at_height_with_timeout(
+        &self,
+        height: u32,
+        timeout: Option<Duration>,
+    ) -> ApiResult<TransactionsAtHeight> {
+        self.fetch_transactions_at_height_filtered_with_timeout(
+            height,
+            &BalancesFilter::default(),
+            timeout,
+        )
+        .await
+    }
+
+    /// Fetch transactions at the given height, retaining only the balance changes
+    /// matching the filter (and only the transactions having any of them).
+    pub async fn fetch_transactions_at_height_filtered(
+        &self,
+        height: u32,
+        filter: &BalancesFilter,
+    ) -> ApiResult<TransactionsAtHeight> {
+        self.fetch_transactions_at_height_filtered_with_timeout(height, filter, self.timeout())
             .await
-            .map_err(Arc::new)?
-            .into_inner()
-            .try_into()
-            .map_err(|err| match err {
-                ConvertError::NotFound => Error::ResponseParseError(format!(
-                    "Requested block update not found at height {}",
-                    height
-                )),
-                ConvertError::NoUpdate => {
-                    Error::ResponseParseError("Expected Append Update, found None".to_string())
-                }
-                ConvertError::RollbackUpdate => Error::ResponseParseError(
-                    "Expected Append Update, found Rollback Update".to_string(),
-                ),
-            })
+    }
+
+    pub async fn fetch_transactions_at_height_filtered_with_timeout(
+        &self,
+        height: u32,
+        filter: &BalancesFilter,
+        timeout: Option<Duration>,
+    ) -> ApiResult<TransactionsAtHeight> {
+        let request = self.request(
+            GetBlockUpdateRequest {
+                height: height as i32,
+            },
+            timeout,
+        );
+
+        let mut grpc_client = self.grpc_client.clone();
+        let response = self
+            .call(
+                "blockchain_updates::get_block_update",
+                grpc_client.get_block_update(request),
+                timeout,
+            )
+            .await?;
+        TransactionsAtHeight::from_response_filtered(response, filter).map_err(|err| match err {
+            ConvertError::NotFound => Error::ResponseParseError(format!(
+                "Requested block update not found at height {}",
+                height
+            )),
+            ConvertError::NoUpdate => {
+                Error::ResponseParseError("Expected Append Update, found None".to_string())
+            }
+            ConvertError::RollbackUpdate => Error::ResponseParseError(
+                "Expected Append Update, found Rollback Update".to_string(),
+            ),
+        })
     }
 }
 
@@ -65,12 +109,6 @@ pub struct TransactionsBalances {
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct TxId(pub String);
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
-pub struct Address(pub String);
-
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
-pub struct AssetId(pub String);
-
 #[derive(Clone, Default, Debug)]
 pub struct AddressBalances {
     pub balances_by_address: HashMap<Address, AssetBalances>,
@@ -87,6 +125,58 @@ pub struct AmountChange {
     pub after: i64,
 }
 
+impl AmountChange {
+    /// `after - before`, widened so that it never overflows
+    pub fn delta(&self) -> i128 {
+        i128::from(self.after) - i128::from(self.before)
+    }
+
+    pub fn is_increase(&self) -> bool {
+        self.after > self.before
+    }
+
+    pub fn is_decrease(&self) -> bool {
+        self.after < self.before
+    }
+}
+
+/// Which balance changes to retain, see `fetch_transactions_at_height_filtered`.
+///
+/// An unset set matches everything, so the default filter retains all the changes.
+#[derive(Clone, Default, Debug)]
+pub struct BalancesFilter {
+    pub addresses: Option<HashSet<Address>>,
+    pub assets: Option<HashSet<AssetId>>,
+}
+
+impl BalancesFilter {
+    pub fn with_addresses(mut self, addresses: impl IntoIterator<Item = Address>) -> Self {
+        self.addresses = Some(addresses.into_iter().collect());
+        self
+    }
+
+    pub fn with_assets(mut self, assets: impl IntoIterator<Item = AssetId>) -> Self {
+        self.assets = Some(assets.into_iter().collect());
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.addresses.is_none() && self.assets.is_none()
+    }
+
+    fn matches_address(&self, address: &Address) -> bool {
+        self.addresses
+            .as_ref()
+            .map_or(true, |addresses| addresses.contains(address))
+    }
+
+    fn matches_asset(&self, asset_id: &AssetId) -> bool {
+        self.assets
+            .as_ref()
+            .map_or(true, |assets| assets.contains(asset_id))
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum ConvertError {
     NotFound,
@@ -94,21 +184,21 @@ pub enum ConvertError {
     RollbackUpdate,
 }
 
-impl TryFrom<GetBlockUpdateResponse> for TransactionsAtHeight {
-    type Error = ConvertError;
-
-    fn try_from(res: GetBlockUpdateResponse) -> Result<TransactionsAtHeight, ConvertError> {
+impl TransactionsAtHeight {
+    pub fn from_response_filtered(
+        res: GetBlockUpdateResponse,
+        filter: &BalancesFilter,
+    ) -> Result<TransactionsAtHeight, ConvertError> {
         match res.update {
             None => Err(ConvertError::NotFound),
-            Some(update) => update.try_into(),
+            Some(update) => Self::from_update_filtered(update, filter),
         }
     }
-}
-
-impl TryFrom<BlockchainUpdated> for TransactionsAtHeight {
-    type Error = ConvertError;
 
-    fn try_from(update: BlockchainUpdated) -> Result<TransactionsAtHeight, ConvertError> {
+    pub fn from_update_filtered(
+        update: BlockchainUpdated,
+        filter: &BalancesFilter,
+    ) -> Result<TransactionsAtHeight, ConvertError> {
         let (height, update) = (update.height, update.update);
         match update {
             None => Err(ConvertError::NoUpdate),
@@ -116,7 +206,7 @@ impl TryFrom<BlockchainUpdated> for TransactionsAtHeight {
             Some(Update::Append(append)) => {
                 let txs = TransactionsAtHeight {
                     height: height as u32,
-                    transactions: append.into(),
+                    transactions: TransactionsBalances::from_append_filtered(append, filter),
                 };
                 Ok(txs)
             }
@@ -124,8 +214,26 @@ impl TryFrom<BlockchainUpdated> for TransactionsAtHeight {
     }
 }
 
-impl From<Append> for TransactionsBalances {
-    fn from(append: Append) -> TransactionsBalances {
+impl TryFrom<GetBlockUpdateResponse> for TransactionsAtHeight {
+    type Error = ConvertError;
+
+    fn try_from(res: GetBlockUpdateResponse) -> Result<TransactionsAtHeight, ConvertError> {
+        Self::from_response_filtered(res, &BalancesFilter::default())
+    }
+}
+
+impl TryFrom<BlockchainUpdated> for TransactionsAtHeight {
+    type Error = ConvertError;
+
+    fn try_from(update: BlockchainUpdated) -> Result<TransactionsAtHeight, ConvertError> {
+        Self::from_update_filtered(update, &BalancesFilter::default())
+    }
+}
+
+impl TransactionsBalances {
+    /// Transactions without any balance change matching the filter are dropped
+    /// (unless the filter is empty, then all the transactions are retained).
+    pub fn from_append_filtered(append: Append, filter: &BalancesFilter) -> TransactionsBalances {
         let ids = append
             .transaction_ids
             .into_iter()
@@ -136,35 +244,46 @@ impl From<Append> for TransactionsBalances {
             .map(|st| st.balances);
         let ids_balances = ids.zip(balances);
         let tx_by_id = ids_balances
-            .map(|(id, balances)| (id, balances.into()))
+            .map(|(id, balances)| (id, AddressBalances::from_updates_filtered(balances, filter)))
+            .filter(|(_, balances)| filter.is_empty() || !balances.balances_by_address.is_empty())
             .collect();
         TransactionsBalances { tx_by_id }
     }
 }
 
-impl From<Vec<BalanceUpdate>> for AddressBalances {
-    fn from(balance_updates: Vec<BalanceUpdate>) -> AddressBalances {
+impl From<Append> for TransactionsBalances {
+    fn from(append: Append) -> TransactionsBalances {
+        Self::from_append_filtered(append, &BalancesFilter::default())
+    }
+}
+
+impl AddressBalances {
+    pub fn from_updates_filtered(
+        balance_updates: Vec<BalanceUpdate>,
+        filter: &BalancesFilter,
+    ) -> AddressBalances {
         let res = balance_updates
             .into_iter()
             .map(|balance_update| {
-                let address = Address(bs58::encode(&balance_update.address).into_string());
+                let address =
+                    Address::new_unchecked(bs58::encode(&balance_update.address).into_string());
                 let before = balance_update.amount_before;
                 let after = balance_update.amount_after.as_ref().map(|amt| {
-                    let asset_id = if amt.asset_id.is_empty() {
-                        AssetId("WAVES".to_string())
-                    } else {
-                        AssetId(bs58::encode(&amt.asset_id).into_string())
-                    };
+                    // the empty id of WAVES is normalized
+                    let asset_id =
+                        AssetId::new_unchecked(bs58::encode(&amt.asset_id).into_string());
                     let amount = amt.amount;
                     (asset_id, amount)
                 });
                 (address, before, after)
             })
+            .filter(|(address, _, _)| filter.matches_address(address))
             .filter_map(|(address, amount_before, after)| {
                 after.map(|(asset_id, amount_after)| {
                     (address, (asset_id, amount_before, amount_after))
                 })
             })
+            .filter(|(_, (asset_id, _, _))| filter.matches_asset(asset_id))
             .into_grouping_map()
             .aggregate(|acc, _, (asset_id, amount_before, amount_after)| {
                 let mut balances: AssetBalances = acc.unwrap_or_default();
@@ -181,3 +300,142 @@ impl From<Vec<BalanceUpdate>> for AddressBalances {
         }
     }
 }
+
+impl From<Vec<BalanceUpdate>> for AddressBalances {
+    fn from(balance_updates: Vec<BalanceUpdate>) -> AddressBalances {
+        Self::from_updates_filtered(balance_updates, &BalancesFilter::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_change_delta() {
+        let change = AmountChange {
+            before: i64::MIN,
+            after: i64::MAX,
+        };
+        assert_eq!(change.delta(), u64::MAX as i128);
+        assert!(change.is_increase());
+        assert!(!change.is_decrease());
+
+        let change = AmountChange {
+            before: i64::MAX,
+            after: i64::MIN,
+        };
+        assert_eq!(change.delta(), -(u64::MAX as i128));
+        assert!(change.is_decrease());
+        assert!(!change.is_increase());
+
+        let change = AmountChange {
+            before: 100,
+            after: 100,
+        };
+        assert_eq!(change.delta(), 0);
+        assert!(!change.is_increase());
+        assert!(!change.is_decrease());
+    }
+
+    #[test]
+    fn test_filtered_balances() {
+        use waves_protobuf_schemas::waves::{events::StateUpdate, Amount};
+
+        let balance = |address: &[u8], asset_id: &[u8], before: i64, after: i64| BalanceUpdate {
+            address: address.to_vec(),
+            amount_after: Some(Amount {
+                asset_id: asset_id.to_vec(),
+                amount: after,
+            }),
+            amount_before: before,
+        };
+        let append = || Append {
+            transaction_ids: vec![b"tx1".to_vec(), b"tx2".to_vec(), b"tx3".to_vec()],
+            transaction_state_updates: vec![
+                StateUpdate {
+                    balances: vec![
+                        balance(b"alice", b"", 100, 90),
+                        balance(b"bob", b"", 0, 10),
+                        balance(b"alice", b"usd", 5, 7),
+                    ],
+                    ..Default::default()
+                },
+                StateUpdate {
+                    balances: vec![balance(b"bob", b"usd", 1, 2), balance(b"carol", b"", 3, 4)],
+                    ..Default::default()
+                },
+                StateUpdate::default(),
+            ],
+            ..Default::default()
+        };
+        let address = |a: &[u8]| Address::new_unchecked(bs58::encode(a).into_string());
+        let tx_id = |id: &[u8]| TxId(bs58::encode(id).into_string());
+
+        let all = TransactionsBalances::from(append());
+        assert_eq!(all.tx_by_id.len(), 3);
+        assert_eq!(all.tx_by_id[&tx_id(b"tx1")].balances_by_address.len(), 2);
+        assert_eq!(all.tx_by_id[&tx_id(b"tx2")].balances_by_address.len(), 2);
+
+        let filter = BalancesFilter::default().with_addresses([address(b"alice")]);
+        let alice = TransactionsBalances::from_append_filtered(append(), &filter);
+        assert_eq!(alice.tx_by_id.len(), 1);
+        let balances = &alice.tx_by_id[&tx_id(b"tx1")].balances_by_address;
+        assert_eq!(balances.len(), 1);
+        let changes = &balances[&address(b"alice")].balance_change_by_asset;
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[&AssetId::waves()].delta(), -10);
+
+        let filter =
+            filter.with_assets([AssetId::new_unchecked(bs58::encode(b"usd").into_string())]);
+        let alice_usd = TransactionsBalances::from_append_filtered(append(), &filter);
+        let changes = &alice_usd.tx_by_id[&tx_id(b"tx1")].balances_by_address[&address(b"alice")]
+            .balance_change_by_asset;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes.values().next().unwrap().delta(), 2);
+
+        let filter = BalancesFilter::default().with_addresses([address(b"dave")]);
+        let nobody = TransactionsBalances::from_append_filtered(append(), &filter);
+        assert!(nobody.tx_by_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_transactions_timeout() {
+        use tokio::net::TcpListener;
+        use waves_protobuf_schemas::tonic::transport::Endpoint;
+
+        // A "server" which accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                connections.push(socket);
+            }
+        });
+
+        let channel = Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect_lazy();
+        let timeout = Duration::from_millis(200);
+        let client = GrpcClient::<BlockchainUpdates>::from_channel(channel).with_timeout(timeout);
+
+        let res = client.fetch_transactions_at_height(1).await;
+        assert!(
+            matches!(res, Err(Error::GrpcTimeout(t)) if t == timeout),
+            "{res:?}"
+        );
+
+        let per_call_timeout = Duration::from_millis(100);
+        let res = client
+            .fetch_transactions_at_height_with_timeout(1, Some(per_call_timeout))
+            .await;
+        assert!(
+            matches!(res, Err(Error::GrpcTimeout(t)) if t == per_call_timeout),
+            "{res:?}"
+        );
+
+        server.abort();
+    }
+}
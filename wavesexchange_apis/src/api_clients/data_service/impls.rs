@@ -1,16 +1,16 @@
 use super::{dto, DSList, DataService, InvokeScriptTransactionRequest, Sort};
-use crate::{ApiResult, HttpClient};
+use crate::{
+    models::{asset_id::normalize_waves, ids::AssetId},
+    ApiResult, Error, HttpClient,
+};
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use wavesexchange_warp::pagination::List;
 
-const HEADER_ORIGIN_NAME: &str = "Origin";
-const HEADER_ORIGIN_VALUE: &str = "waves.exchange";
-
 impl HttpClient<DataService> {
     pub async fn rates<
-        I: IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        I: IntoIterator<Item = (impl Into<AssetId>, impl Into<AssetId>)>,
         S1: AsRef<str>,
     >(
         &self,
@@ -19,23 +19,22 @@ impl HttpClient<DataService> {
         timestamp: Option<NaiveDateTime>,
     ) -> ApiResult<dto::RatesResponse> {
         let req = dto::RatesRequest {
+            // WAVES is normalized by `AssetId`
             pairs: pairs
                 .into_iter()
-                .map(|(amt, pr)| amt.into() + "/" + &pr.into())
+                .map(|(amt, pr)| {
+                    let (amt, pr): (AssetId, AssetId) = (amt.into(), pr.into());
+                    format!("{amt}/{pr}")
+                })
                 .collect(),
             timestamp,
         };
 
         let url = format!("matchers/{}/rates", matcher_address.as_ref());
 
-        self.create_req_handler(
-            self.http_post(&url)
-                .header(HEADER_ORIGIN_NAME, HEADER_ORIGIN_VALUE)
-                .json(&req),
-            "data_service::rates",
-        )
-        .execute()
-        .await
+        self.create_req_handler(self.http_post(&url).json(&req), "data_service::rates")
+            .execute()
+            .await
     }
 
     pub async fn invoke_script_transactions(
@@ -49,6 +48,7 @@ impl HttpClient<DataService> {
         sort: Option<Sort>,
         limit: usize,
     ) -> ApiResult<List<dto::InvokeScriptTransactionResponse>> {
+        let limit = self.checked_limit(limit, "data_service::invoke_script_transactions")?;
         let senders = senders.map(|s| s.into_iter().map(Into::into).collect::<Vec<_>>());
         let (sender, senders) = if match &senders {
             Some(s) => s.len() == 1,
@@ -58,27 +58,25 @@ impl HttpClient<DataService> {
         } else {
             (None, senders)
         };
-        let url = serde_qs::to_string(&InvokeScriptTransactionRequest {
+        let query = InvokeScriptTransactionRequest {
             dapp: dapp.map(Into::into),
             after: after.map(Into::into),
             function: function.map(Into::into),
-            limit: if limit == 0 { None } else { Some(limit) },
+            limit,
             sender,
             senders,
             sort,
             timeEnd: timestamp_end.map(Into::into),
             timeStart: timestamp_start.map(Into::into),
-        })
-        .unwrap();
+        };
 
         self.create_req_handler::<DSList<dto::InvokeScriptTransactionResponse>>(
-            self.http_get(format!("transactions/invoke-script?{url}"))
-                .header(HEADER_ORIGIN_NAME, HEADER_ORIGIN_VALUE),
+            self.http_get_with_query("transactions/invoke-script", &query)?,
             "data_service::invoke_script_transactions",
         )
         .execute()
         .await
-        .map(List::from)
+        .and_then(List::try_from)
     }
 
     //TODO Why this fn returns `dto::GenericTransactionResponse`
@@ -96,24 +94,41 @@ impl HttpClient<DataService> {
         );
 
         self.create_req_handler::<DSList<dto::GenericTransactionResponse>>(
-            self.http_get(&url)
-                .header(HEADER_ORIGIN_NAME, HEADER_ORIGIN_VALUE),
+            self.http_get(&url),
             "data_service::last_exchange_transaction_to_date",
         )
         .execute()
         .await
-        .map(List::from)
+        .and_then(List::try_from)
     }
 
     pub async fn asset_by_ticker(
         &self,
         ticker: impl AsRef<str>,
     ) -> ApiResult<dto::Data<Vec<dto::Data<dto::AssetInfo>>>> {
-        let url = format!("assets?ticker={}", ticker.as_ref());
-
-        self.create_req_handler(self.http_get(&url), "data_service::asset_by_ticker")
-            .execute()
+        let filter = dto::AssetsFilter::default().with_tickers([ticker.as_ref()]);
+        self.assets(filter)
             .await
+            .map(|list| dto::Data { data: list.items })
+    }
+
+    /// Search assets by multiple tickers and/or ids in one request
+    pub async fn assets(
+        &self,
+        filter: dto::AssetsFilter,
+    ) -> ApiResult<List<dto::Data<dto::AssetInfo>>> {
+        let url = format!("assets?{}", assets_query_string(&filter)?);
+
+        self.create_req_handler::<dto::AssetsList<dto::Data<dto::AssetInfo>>>(
+            self.http_get(&url),
+            "data_service::assets",
+        )
+        .execute()
+        .await
+        .and_then(|list| {
+            let has_next_page = list.is_last_page == Some(false);
+            checked_list(list.data, has_next_page, list.last_cursor)
+        })
     }
 
     //TODO The following method became really ugly. Replace with some sort of builder.
@@ -133,9 +148,10 @@ impl HttpClient<DataService> {
         limit: usize,
         after: Option<impl AsRef<str>>,
     ) -> ApiResult<List<dto::Data<dto::ExchangeTransaction>>> {
-        let query_string = serde_qs::to_string(&dto::ExchangeTransactionsQueryParams {
-            amount_asset: amount_asset_id.map(|id| id.as_ref().to_owned()),
-            price_asset: price_asset_id.map(|id| id.as_ref().to_owned()),
+        let limit = self.checked_limit(limit, "data_service::transactions_exchange")?;
+        let query = dto::ExchangeTransactionsQueryParams {
+            amount_asset: amount_asset_id.map(|id| normalize_waves(id.as_ref()).to_owned()),
+            price_asset: price_asset_id.map(|id| normalize_waves(id.as_ref()).to_owned()),
             sender: sender.map(|id| id.as_ref().to_owned()),
             matcher: matcher.map(|id| id.as_ref().to_owned()),
             time_start,
@@ -143,18 +159,15 @@ impl HttpClient<DataService> {
             sort,
             limit,
             after: after.map(|id| id.as_ref().to_owned()),
-        })
-        .unwrap();
-
-        let url = format!("transactions/exchange?{query_string}");
+        };
 
         self.create_req_handler::<DSList<dto::Data<dto::ExchangeTransaction>>>(
-            self.http_get(&url),
+            self.http_get_with_query("transactions/exchange", &query)?,
             "data_service::transactions_exchange",
         )
         .execute()
         .await
-        .map(List::from)
+        .and_then(List::try_from)
     }
 
     pub async fn pairs(&self) -> ApiResult<List<dto::Pair>> {
@@ -171,7 +184,7 @@ impl HttpClient<DataService> {
             )
             .execute()
             .await
-            .map(List::from)?;
+            .and_then(List::try_from)?;
 
         if res.items.len() == MAX_LIMIT {
             panic!("Data Service's `pairs` request returned {MAX_LIMIT} items, which is the limit, but probably there are more pairs than that. Proper parination support needed.");
@@ -179,10 +192,259 @@ impl HttpClient<DataService> {
 
         Ok(res)
     }
+
+    /// `0` means the default limit of Data Service, so it is not sent
+    fn checked_limit(&self, limit: usize, req_info: &str) -> ApiResult<Option<usize>> {
+        let max_limit = self.max_limit();
+        if limit > max_limit {
+            return Err(Error::ValidationError(format!(
+                "Request '{req_info}': limit {limit} exceeds the max of {max_limit}"
+            )));
+        }
+        Ok(if limit == 0 { None } else { Some(limit) })
+    }
 }
 
-impl<T: Serialize + DeserializeOwned> From<DSList<T>> for List<T> {
-    fn from(dsl: DSList<T>) -> Self {
-        List::new(dsl.data, !dsl.is_last_page, dsl.last_cursor)
+impl<T: Serialize + DeserializeOwned> TryFrom<DSList<T>> for List<T> {
+    type Error = Error;
+
+    /// Fails if there is a next page, but no cursor to request it
+    /// (otherwise the callers fetching all the pages would loop forever)
+    fn try_from(dsl: DSList<T>) -> ApiResult<Self> {
+        checked_list(dsl.data, !dsl.is_last_page, dsl.last_cursor)
+    }
+}
+
+fn checked_list<T: Serialize + DeserializeOwned>(
+    items: Vec<T>,
+    has_next_page: bool,
+    last_cursor: Option<String>,
+) -> ApiResult<List<T>> {
+    if has_next_page && last_cursor.is_none() {
+        return Err(Error::ResponseParseError(
+            "Data Service returned a non-last page without a cursor".to_owned(),
+        ));
+    }
+    Ok(List::new(items, has_next_page, last_cursor))
+}
+
+/// Data Service expects array params without indices (`ticker[]=a&ticker[]=b`),
+/// while `serde_qs` produces indexed ones (`ticker[0]=a&ticker[1]=b`).
+/// Values are percent-encoded by `serde_qs` (e.g. `+` in legacy tickers becomes `%2B`).
+fn assets_query_string(filter: &dto::AssetsFilter) -> ApiResult<String> {
+    let qs = serde_qs::to_string(filter).map_err(|err| {
+        Error::QueryStringError(format!("Failed to serialize the assets filter: {err}"))
+    })?;
+    let qs = qs
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => match key.split_once('[') {
+                Some((name, index)) if index.trim_end_matches(']').parse::<usize>().is_ok() => {
+                    format!("{name}[]={value}")
+                }
+                _ => param.to_owned(),
+            },
+            None => param.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    Ok(qs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_query_string() {
+        let filter = dto::AssetsFilter::default()
+            .with_tickers(["BTC", "USD-N", "WX+", "a b&c"])
+            .with_ids(["WAVES", "DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p"])
+            .with_limit(10)
+            .with_after("cursor");
+        assert_eq!(
+            assets_query_string(&filter).unwrap(),
+            "ticker[]=BTC&ticker[]=USD-N&ticker[]=WX%2B&ticker[]=a+b%26c\
+             &ids[]=WAVES&ids[]=DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p\
+             &limit=10&after=cursor"
+        );
+
+        let filter = dto::AssetsFilter::default().with_tickers(["WX+"]);
+        assert_eq!(assets_query_string(&filter).unwrap(), "ticker[]=WX%2B");
+
+        assert_eq!(
+            assets_query_string(&dto::AssetsFilter::default()).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_ds_list_pagination_check() {
+        let list = |is_last_page: bool, last_cursor: Option<&str>| DSList {
+            data: vec![1, 2],
+            last_cursor: last_cursor.map(ToOwned::to_owned),
+            is_last_page,
+        };
+
+        let res = List::try_from(list(false, Some("cursor"))).unwrap();
+        assert!(res.page_info.has_next_page);
+        assert_eq!(res.page_info.last_cursor.as_deref(), Some("cursor"));
+        assert_eq!(res.items, [1, 2]);
+
+        let res = List::try_from(list(true, None)).unwrap();
+        assert!(!res.page_info.has_next_page);
+
+        // a next page which can't be requested
+        let res = List::try_from(list(false, None));
+        assert!(matches!(res, Err(Error::ResponseParseError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_limit_validation() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+        use serde_json::json;
+
+        let empty_page = json!({ "data": [], "lastCursor": null, "isLastPage": true });
+        let mocks = MockResponses::new()
+            .on(
+                Method::GET,
+                "/transactions/invoke-script",
+                MockResponse::json(&empty_page),
+            )
+            .on(
+                Method::GET,
+                "/transactions/exchange",
+                MockResponse::json(&empty_page),
+            );
+        let client = HttpClient::<DataService>::mocked(mocks.clone());
+
+        let invoke_script = |limit| {
+            client.invoke_script_transactions(
+                None::<Vec<String>>,
+                None,
+                None,
+                None::<String>,
+                None::<String>,
+                None::<String>,
+                None,
+                limit,
+            )
+        };
+        let exchange = |limit| {
+            client.transactions_exchange(
+                None::<&str>,
+                None::<&str>,
+                None::<&str>,
+                None::<&str>,
+                None,
+                None,
+                Sort::Desc,
+                limit,
+                None::<&str>,
+            )
+        };
+
+        // zero means the default limit of Data Service
+        invoke_script(0).await.unwrap();
+        exchange(0).await.unwrap();
+        let received = mocks.received();
+        assert_eq!(received.len(), 2);
+        assert!(received
+            .iter()
+            .all(|req| !req.query.as_deref().unwrap_or("").contains("limit")));
+
+        invoke_script(100).await.unwrap();
+        exchange(100).await.unwrap();
+        let received = mocks.received();
+        assert_eq!(received.len(), 4);
+        assert!(received[2..].iter().all(|req| req
+            .query
+            .as_deref()
+            .unwrap()
+            .contains("limit=100")));
+
+        // over-limit requests are not sent
+        let res = invoke_script(101).await;
+        assert!(matches!(res, Err(Error::ValidationError(_))));
+        let res = exchange(101).await;
+        assert!(matches!(res, Err(Error::ValidationError(_))));
+        assert_eq!(mocks.received().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_origin_header() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+        use serde_json::json;
+
+        let empty_page = json!({ "data": [], "lastCursor": null, "isLastPage": true });
+        let mocks = MockResponses::new()
+            .on(
+                Method::POST,
+                "/matchers/matcher/rates",
+                MockResponse::json(&json!({ "data": [] })),
+            )
+            .on(
+                Method::GET,
+                "/transactions/invoke-script",
+                MockResponse::json(&empty_page),
+            )
+            .on(
+                Method::GET,
+                "/transactions/exchange",
+                MockResponse::json(&empty_page),
+            )
+            .on(Method::GET, "/assets", MockResponse::json(&empty_page))
+            .on(Method::GET, "/pairs", MockResponse::json(&empty_page));
+        let client = HttpClient::<DataService>::mocked(mocks.clone());
+
+        client
+            .rates("matcher", [("WAVES", "WAVES")], None)
+            .await
+            .unwrap();
+        client
+            .invoke_script_transactions(
+                None::<Vec<String>>,
+                None,
+                None,
+                None::<String>,
+                None::<String>,
+                None::<String>,
+                None,
+                10,
+            )
+            .await
+            .unwrap();
+        client
+            .last_exchange_transaction_to_date("sender", NaiveDateTime::default())
+            .await
+            .unwrap();
+        client.asset_by_ticker("USDN").await.unwrap();
+        client
+            .assets(dto::AssetsFilter::default().with_tickers(["USDN"]))
+            .await
+            .unwrap();
+        client
+            .transactions_exchange(
+                None::<&str>,
+                None::<&str>,
+                None::<&str>,
+                None::<&str>,
+                None,
+                None,
+                Sort::Desc,
+                10,
+                None::<&str>,
+            )
+            .await
+            .unwrap();
+        client.pairs().await.unwrap();
+
+        let received = mocks.received();
+        assert_eq!(received.len(), 7);
+        for req in received {
+            assert_eq!(req.header("origin"), Some("waves.exchange"), "{}", req.path);
+        }
     }
 }
@@ -1,17 +1,35 @@
 mod impls;
 
 use self::dto::*;
-use crate::BaseApi;
+use crate::{BaseApi, NetworkUrls};
+use reqwest::header::{HeaderMap, HeaderValue, ORIGIN};
 
 #[derive(Clone, Debug)]
 pub struct DataService;
 
-impl BaseApi for DataService {}
+impl BaseApi for DataService {
+    fn default_headers() -> HeaderMap {
+        // the data service gates requests on Origin
+        HeaderMap::from_iter([(ORIGIN, HeaderValue::from_static("waves.exchange"))])
+    }
+}
+
+impl NetworkUrls for DataService {
+    const MAINNET_URL: &'static str = "https://waves.exchange/api/v1/forward/data_service/v0";
+    const TESTNET_URL: &'static str =
+        "https://testnet.waves.exchange/api/v1/forward/data_service/v0";
+}
 
 pub mod dto {
+    pub use crate::models::dto::Sort;
+
+    use crate::models::{dto::DataEntryValue, serde_helpers::flexible_datetime};
+    use base64::{engine::general_purpose::STANDARD, Engine};
     use bigdecimal::BigDecimal;
     use chrono::{DateTime, NaiveDateTime, Utc};
-    use serde::{Deserialize, Serialize};
+    use serde::{de, Deserialize, Deserializer, Serialize};
+    use serde_json::value::RawValue;
+    use std::{iter, str::FromStr, vec};
 
     #[derive(Debug, Clone, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -28,13 +46,6 @@ pub mod dto {
         Failed,
     }
 
-    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
-    #[serde(rename_all = "snake_case")]
-    pub enum Sort {
-        Asc,
-        Desc,
-    }
-
     #[derive(Debug, Clone, Deserialize)]
     pub struct AssetInfo {
         pub id: String,
@@ -42,18 +53,86 @@ pub mod dto {
         pub ticker: Option<String>,
     }
 
+    /// Filter for the `assets` request. Empty filter fields are not sent.
+    #[derive(Debug, Clone, Default, Serialize)]
+    pub struct AssetsFilter {
+        #[serde(rename = "ticker", skip_serializing_if = "Vec::is_empty")]
+        pub tickers: Vec<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        pub ids: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub limit: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub after: Option<String>,
+    }
+
+    impl AssetsFilter {
+        pub fn with_tickers(
+            mut self,
+            tickers: impl IntoIterator<Item = impl Into<String>>,
+        ) -> Self {
+            self.tickers = tickers.into_iter().map(Into::into).collect();
+            self
+        }
+
+        pub fn with_ids(mut self, ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+            self.ids = ids.into_iter().map(Into::into).collect();
+            self
+        }
+
+        pub fn with_limit(mut self, limit: u32) -> Self {
+            self.limit = Some(limit);
+            self
+        }
+
+        pub fn with_after(mut self, after: impl Into<String>) -> Self {
+            self.after = Some(after.into());
+            self
+        }
+    }
+
+    /// Same as `DSList`, but the pagination fields are optional,
+    /// because the `assets` endpoint omits them when the result is not paginated
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct AssetsList<T> {
+        pub data: Vec<T>,
+        pub last_cursor: Option<String>,
+        pub is_last_page: Option<bool>,
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct ExchangeTransaction {
         pub id: String,
         pub height: u32,
+        #[serde(with = "flexible_datetime")]
         pub timestamp: DateTime<Utc>,
-        pub amount: f64,
-        pub price: f64,
+        #[serde(deserialize_with = "exact_decimal")]
+        pub amount: BigDecimal,
+        #[serde(deserialize_with = "exact_decimal")]
+        pub price: BigDecimal,
+        #[serde(deserialize_with = "exact_decimal")]
+        pub fee: BigDecimal,
+        /// `None` for WAVES
+        #[serde(default, rename = "feeAssetId")]
+        pub fee_asset: Option<String>,
         pub order1: Order,
         pub order2: Order,
     }
 
+    /// Deserialize a decimal from its JSON text, either a number or a string,
+    /// because numbers are otherwise parsed as `f64` losing the precision
+    fn exact_decimal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigDecimal, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        let text = raw.get();
+        let text = match text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+            Some(text) => text,
+            None => text,
+        };
+        BigDecimal::from_str(text).map_err(de::Error::custom)
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     #[allow(non_snake_case)]
     pub struct InvokeScriptTransactionRequest {
@@ -76,6 +155,7 @@ pub mod dto {
         pub amount: f64,
         pub order_type: OrderType,
         pub asset_pair: AssetPair,
+        #[serde(with = "flexible_datetime")]
         pub timestamp: DateTime<Utc>,
     }
 
@@ -96,11 +176,48 @@ pub mod dto {
     //TODO Why do we need this struct in API return values?
     // Looks like implementation detail (deserialization) and probably should be made private
     // and removed from all return values. Or at least write a comment justifying its use in public API.
-    #[derive(Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Data<T> {
         pub data: T,
     }
 
+    impl<T> Data<T> {
+        pub fn into_inner(self) -> T {
+            self.data
+        }
+
+        /// Deserialize `{"data": ...}` directly into the inner value,
+        /// to be used as `#[serde(deserialize_with = "Data::deserialize_unwrapped")]`
+        pub fn deserialize_unwrapped<'de, D>(deserializer: D) -> Result<T, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: Deserialize<'de>,
+        {
+            Data::deserialize(deserializer).map(Data::into_inner)
+        }
+    }
+
+    /// A list of wrapped items, e.g. `asset_by_ticker` response
+    impl<T> Data<Vec<Data<T>>> {
+        /// Unwrap both the list and the items
+        pub fn into_items(self) -> Vec<T> {
+            self.into_iter().collect()
+        }
+
+        pub fn items(&self) -> impl Iterator<Item = &T> {
+            self.data.iter().map(|item| &item.data)
+        }
+    }
+
+    impl<T> IntoIterator for Data<Vec<Data<T>>> {
+        type Item = T;
+        type IntoIter = iter::Map<vec::IntoIter<Data<T>>, fn(Data<T>) -> T>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.data.into_iter().map(Data::into_inner)
+        }
+    }
+
     #[derive(Clone, Serialize)]
     #[serde(rename_all = "camelCase")]
     pub(super) struct ExchangeTransactionsQueryParams {
@@ -111,7 +228,8 @@ pub mod dto {
         pub time_start: Option<DateTime<Utc>>,
         pub time_end: Option<DateTime<Utc>>,
         pub sort: Sort,
-        pub limit: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub limit: Option<usize>,
         pub after: Option<String>,
     }
 
@@ -165,13 +283,39 @@ pub mod dto {
         pub args: Vec<InvokeScriptArgumentResponse>,
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(tag = "type")]
     pub enum InvokeScriptArgumentResponse {
         #[serde(rename = "string")]
         String { value: String },
+        /// `base64:`-prefixed
         #[serde(rename = "binary")]
         Binary { value: String },
+        #[serde(rename = "integer")]
+        Integer { value: i64 },
+        #[serde(rename = "boolean")]
+        Boolean { value: bool },
+        #[serde(rename = "list")]
+        List {
+            value: Vec<InvokeScriptArgumentResponse>,
+        },
+    }
+
+    impl InvokeScriptArgumentResponse {
+        /// Same value as a data entry value, `None` for a list or a malformed binary
+        pub fn to_data_entry_value(&self) -> Option<DataEntryValue> {
+            let value = match self {
+                Self::String { value } => DataEntryValue::String(value.clone()),
+                Self::Binary { value } => {
+                    let value = value.strip_prefix("base64:").unwrap_or(value);
+                    DataEntryValue::Binary(STANDARD.decode(value).ok()?)
+                }
+                Self::Integer { value } => DataEntryValue::Integer(*value),
+                Self::Boolean { value } => DataEntryValue::Boolean(*value),
+                Self::List { .. } => return None,
+            };
+            Some(value)
+        }
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -209,11 +353,135 @@ pub mod dto {
     }
 }
 
-impl core::fmt::Display for Sort {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self {
-            Sort::Asc => write!(f, "asc"),
-            Sort::Desc => write!(f, "desc"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_unwrapping() {
+        use serde::Deserialize;
+
+        let json = r#"{"data": [{"data": {"id": "8LQW8f7P5d5PZM7GtZEBgaqRPGSzS3DfPuiXrURJ4AJS", "precision": 8, "ticker": "BTC"}}]}"#;
+        let assets: Data<Vec<Data<AssetInfo>>> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            assets.items().next().unwrap().ticker.as_deref(),
+            Some("BTC")
+        );
+        let assets = assets.into_items();
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].id, "8LQW8f7P5d5PZM7GtZEBgaqRPGSzS3DfPuiXrURJ4AJS");
+        assert_eq!(assets[0].precision, 8);
+
+        #[derive(Deserialize)]
+        struct Wrapper {
+            #[serde(deserialize_with = "Data::deserialize_unwrapped")]
+            asset: AssetInfo,
+        }
+        let json = r#"{"asset": {"data": {"id": "WAVES", "precision": 8, "ticker": null}}}"#;
+        let wrapper: Wrapper = serde_json::from_str(json).unwrap();
+        assert_eq!(wrapper.asset.id, "WAVES");
+        assert_eq!(wrapper.asset.ticker, None);
+    }
+
+    #[test]
+    fn test_invoke_script_arguments() {
+        use crate::models::dto::DataEntryValue;
+
+        let json = r#"{
+        "data": {
+            "id": "tx_id",
+            "height": 3000000,
+            "proofs": [],
+            "version": 2,
+            "sender": "3PSender",
+            "senderPublicKey": "pubkey",
+            "dApp": "3PDApp",
+            "call": {
+                "function": "swap",
+                "args": [
+                    {"type": "integer", "value": 100500},
+                    {"type": "list", "value": [
+                        {"type": "string", "value": "WAVES"},
+                        {"type": "boolean", "value": true},
+                        {"type": "binary", "value": "base64:AQID"}
+                    ]}
+                ]
+            },
+            "fee": 0.005
         }
+    }"#;
+        let tx: InvokeScriptTransactionResponse = serde_json::from_str(json).unwrap();
+        let args = tx.data.call.args;
+        assert_eq!(
+            args[0],
+            InvokeScriptArgumentResponse::Integer { value: 100500 }
+        );
+        assert_eq!(
+            args[0].to_data_entry_value(),
+            Some(DataEntryValue::Integer(100500))
+        );
+        assert_eq!(args[1].to_data_entry_value(), None);
+
+        let InvokeScriptArgumentResponse::List { value: list } = &args[1] else {
+            panic!("not a list: {:?}", args[1]);
+        };
+        let values = list
+            .iter()
+            .map(InvokeScriptArgumentResponse::to_data_entry_value)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                Some(DataEntryValue::String("WAVES".to_string())),
+                Some(DataEntryValue::Boolean(true)),
+                Some(DataEntryValue::Binary(vec![1, 2, 3])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exchange_transaction_precision() {
+        use bigdecimal::BigDecimal;
+        use std::str::FromStr;
+
+        let order = r#"{
+        "sender": "3PSender",
+        "amount": 12345678901234.123456789,
+        "orderType": "buy",
+        "assetPair": {"amountAsset": "WAVES", "priceAsset": "USDT"},
+        "timestamp": "2024-01-01T00:00:00.000Z"
+    }"#;
+        let json = format!(
+            r#"{{
+            "id": "tx_id",
+            "height": 3000000,
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "amount": 12345678901234.123456789,
+            "price": "0.000000012345678901",
+            "fee": 0.003,
+            "feeAssetId": "USDT",
+            "order1": {order},
+            "order2": {order}
+        }}"#
+        );
+        let tx: ExchangeTransaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            tx.amount,
+            BigDecimal::from_str("12345678901234.123456789").unwrap()
+        );
+        assert_eq!(tx.amount.to_string(), "12345678901234.123456789");
+        assert_eq!(
+            tx.price,
+            BigDecimal::from_str("0.000000012345678901").unwrap()
+        );
+        assert_eq!(tx.fee, BigDecimal::from_str("0.003").unwrap());
+        assert_eq!(tx.fee_asset.as_deref(), Some("USDT"));
+
+        // serialized values are read back exactly
+        let tx2: ExchangeTransaction =
+            serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+        assert_eq!(tx2.amount, tx.amount);
+        assert_eq!(tx2.price, tx.price);
+        assert_eq!(tx2.fee_asset, tx.fee_asset);
     }
 }
@@ -14,15 +14,14 @@ impl HttpClient<ExchangesService> {
         timestamp_gte: NaiveDateTime,
         timestamp_lt: NaiveDateTime,
     ) -> ApiResult<MatcherExchangeAggregatesResponse> {
-        let qs = serde_qs::to_string(&MatcherExchangeAggregatesRequest {
+        let query = MatcherExchangeAggregatesRequest {
             timestamp_gte: timestamp_gte.and_utc(),
             timestamp_lt: timestamp_lt.and_utc(),
-        })
-        .unwrap();
+        };
 
         let res = self
             .create_req_handler(
-                self.http_get(format!("matcher_exchange_aggregates?{}", qs)),
+                self.http_get_with_query("matcher_exchange_aggregates", &query)?,
                 "exchanges::matcher_exchange_aggregates",
             )
             .execute()
@@ -40,3 +40,222 @@ pub mod dto {
         pub signature: String,
     }
 }
+
+/// Parsing of WX access tokens (JWT), e.g. for cheap pre-checks before calling the identity service.
+///
+/// **The signature is NOT validated**, so the claims must not be trusted for authorization:
+/// anyone can craft a token with any `sub` and `exp`.
+pub mod jwt {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::{DateTime, Utc};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Claims {
+        /// Subject, usually the user's address
+        pub sub: Option<String>,
+        /// Expiration time
+        pub exp: Option<DateTime<Utc>>,
+        /// Issue time
+        pub iat: Option<DateTime<Utc>>,
+        /// Scopes, either from a space-separated string or an array of strings
+        pub scope: Vec<String>,
+        /// All claims of the token
+        pub raw: Value,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum JwtParseError {
+        #[error("JWT must consist of 3 dot-separated parts, got {0}")]
+        InvalidFormat(usize),
+
+        #[error("JWT payload is not valid base64url: {0}")]
+        InvalidBase64(#[from] base64::DecodeError),
+
+        #[error("JWT payload is not valid JSON: {0}")]
+        InvalidJson(#[from] serde_json::Error),
+
+        #[error("JWT claim '{0}' has invalid value: {1}")]
+        InvalidClaim(&'static str, Value),
+    }
+
+    /// Parse the payload of a JWT **without validating its signature**.
+    ///
+    /// Base64url with and without padding is accepted, as well as a `Bearer ` prefix.
+    /// All the claims are optional, except that present ones must be of the correct type.
+    pub fn parse_unverified(token: &str) -> Result<Claims, JwtParseError> {
+        let token = token.trim();
+        let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+        let parts = token.split('.').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(JwtParseError::InvalidFormat(parts.len()));
+        }
+        let payload = URL_SAFE_NO_PAD.decode(parts[1].trim_end_matches('='))?;
+        let raw = serde_json::from_slice::<Value>(&payload)?;
+        if !raw.is_object() {
+            return Err(JwtParseError::InvalidClaim("payload", raw));
+        }
+
+        let sub = match &raw["sub"] {
+            Value::Null => None,
+            Value::String(sub) => Some(sub.clone()),
+            other => return Err(JwtParseError::InvalidClaim("sub", other.clone())),
+        };
+        let exp = numeric_date(&raw, "exp")?;
+        let iat = numeric_date(&raw, "iat")?;
+        let scope = match &raw["scope"] {
+            Value::Null => vec![],
+            Value::String(scope) => scope.split_whitespace().map(str::to_owned).collect(),
+            Value::Array(scopes) => scopes
+                .iter()
+                .map(|s| s.as_str().map(str::to_owned))
+                .collect::<Option<_>>()
+                .ok_or_else(|| JwtParseError::InvalidClaim("scope", raw["scope"].clone()))?,
+            other => return Err(JwtParseError::InvalidClaim("scope", other.clone())),
+        };
+
+        Ok(Claims {
+            sub,
+            exp,
+            iat,
+            scope,
+            raw,
+        })
+    }
+
+    /// Seconds since the epoch, possibly fractional
+    fn numeric_date(
+        raw: &Value,
+        claim: &'static str,
+    ) -> Result<Option<DateTime<Utc>>, JwtParseError> {
+        let value = &raw[claim];
+        if value.is_null() {
+            return Ok(None);
+        }
+        value
+            .as_f64()
+            .and_then(|secs| DateTime::from_timestamp_millis((secs * 1000.0) as i64))
+            .map(Some)
+            .ok_or_else(|| JwtParseError::InvalidClaim(claim, value.clone()))
+    }
+
+    impl Claims {
+        /// Whether the token is expired, allowing for a `leeway` of clock skew.
+        /// A token without `exp` claim never expires.
+        pub fn is_expired(&self, leeway: Duration) -> bool {
+            self.is_expired_at(Utc::now(), leeway)
+        }
+
+        fn is_expired_at(&self, now: DateTime<Utc>, leeway: Duration) -> bool {
+            let (Some(exp), Ok(leeway)) = (self.exp, chrono::Duration::from_std(leeway)) else {
+                return false;
+            };
+            exp.checked_add_signed(leeway)
+                .map_or(false, |deadline| deadline <= now)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn token(payload: &str, padded: bool) -> String {
+            use base64::engine::general_purpose::URL_SAFE;
+
+            let engine = if padded { URL_SAFE } else { URL_SAFE_NO_PAD };
+            format!(
+                "{}.{}.c2lnbmF0dXJl",
+                engine.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+                engine.encode(payload)
+            )
+        }
+
+        #[test]
+        fn test_parse_unverified() {
+            let payload =
+                r#"{"sub":"3PAddress","exp":1700000000,"iat":1699996400,"scope":"general sign"}"#;
+            // 76 bytes of payload are padded with "==" in padded base64
+            for padded in [false, true] {
+                let claims = parse_unverified(&token(payload, padded)).unwrap();
+                assert_eq!(claims.sub.as_deref(), Some("3PAddress"));
+                assert_eq!(claims.exp, DateTime::from_timestamp(1700000000, 0));
+                assert_eq!(claims.iat, DateTime::from_timestamp(1699996400, 0));
+                assert_eq!(claims.scope, vec!["general", "sign"]);
+                assert_eq!(claims.raw["sub"], "3PAddress");
+            }
+            let token = token(payload, true);
+            assert!(token.contains('='));
+            assert!(parse_unverified(&format!("Bearer {token}")).is_ok());
+
+            // this payload contains "-" in base64url, which is "+" in standard base64
+            let token = self::token(r#"{"sub":"a??>>>"}"#, false);
+            assert!(token.contains('-'));
+            let claims = parse_unverified(&token).unwrap();
+            assert_eq!(claims.sub.as_deref(), Some("a??>>>"));
+        }
+
+        #[test]
+        fn test_parse_unverified_optional_claims() {
+            let claims = parse_unverified(&token(r#"{"scope":["a","b"]}"#, false)).unwrap();
+            assert_eq!(claims.sub, None);
+            assert_eq!(claims.exp, None);
+            assert_eq!(claims.iat, None);
+            assert_eq!(claims.scope, vec!["a", "b"]);
+            assert!(!claims.is_expired(Duration::ZERO));
+
+            let claims = parse_unverified(&token(r#"{"exp":1700000000.5}"#, false)).unwrap();
+            assert_eq!(
+                claims.exp,
+                DateTime::from_timestamp(1700000000, 500_000_000)
+            );
+            assert!(claims.scope.is_empty());
+        }
+
+        #[test]
+        fn test_parse_unverified_errors() {
+            assert!(matches!(
+                parse_unverified("abc.def"),
+                Err(JwtParseError::InvalidFormat(2))
+            ));
+            assert!(matches!(
+                parse_unverified("abc.d*f.ghi"),
+                Err(JwtParseError::InvalidBase64(_))
+            ));
+            assert!(matches!(
+                parse_unverified(&token(r#"{"sub":"#, false)),
+                Err(JwtParseError::InvalidJson(_))
+            ));
+            assert!(matches!(
+                parse_unverified(&token(r#"["sub"]"#, false)),
+                Err(JwtParseError::InvalidClaim("payload", _))
+            ));
+            assert!(matches!(
+                parse_unverified(&token(r#"{"exp":"tomorrow"}"#, false)),
+                Err(JwtParseError::InvalidClaim("exp", _))
+            ));
+            assert!(matches!(
+                parse_unverified(&token(r#"{"sub":42}"#, false)),
+                Err(JwtParseError::InvalidClaim("sub", _))
+            ));
+            assert!(matches!(
+                parse_unverified(&token(r#"{"scope":["a",1]}"#, false)),
+                Err(JwtParseError::InvalidClaim("scope", _))
+            ));
+        }
+
+        #[test]
+        fn test_is_expired() {
+            let claims = parse_unverified(&token(r#"{"exp":1700000000}"#, false)).unwrap();
+            let exp = DateTime::from_timestamp(1700000000, 0).unwrap();
+            let leeway = Duration::from_secs(30);
+            let secs = chrono::Duration::seconds;
+
+            assert!(!claims.is_expired_at(exp - secs(1), Duration::ZERO));
+            assert!(claims.is_expired_at(exp, Duration::ZERO));
+            assert!(!claims.is_expired_at(exp + secs(29), leeway));
+            assert!(claims.is_expired_at(exp + secs(30), leeway));
+            assert!(claims.is_expired(leeway));
+        }
+    }
+}
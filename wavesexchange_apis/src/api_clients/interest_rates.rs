@@ -1,4 +1,4 @@
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{models::asset_id::normalize_waves, ApiResult, BaseApi, HttpClient};
 
 #[derive(Clone, Debug)]
 pub struct InterestService;
@@ -7,7 +7,7 @@ impl BaseApi for InterestService {}
 
 impl HttpClient<InterestService> {
     pub async fn get(&self, asset_id: impl AsRef<str>) -> ApiResult<dto::InterestRatesResponse> {
-        let url = format!("interest_rates/{}", asset_id.as_ref());
+        let url = format!("interest_rates/{}", normalize_waves(asset_id.as_ref()));
 
         self.create_req_handler(self.http_get(&url), "interest_rates::get")
             .execute()
@@ -28,8 +28,20 @@ pub use rates::RatesService;
 pub use state::StateService;
 pub use transfers::Transfers;
 
+use reqwest::header::HeaderMap;
 use std::fmt::Debug;
 
-pub trait BaseApi: Sync + Clone + Debug {}
+pub trait BaseApi: Sync + Clone + Debug {
+    /// Headers sent with every request to the service
+    fn default_headers() -> HeaderMap {
+        HeaderMap::new()
+    }
+}
+
+/// Public deployments of a service, see `mainnet_client`/`testnet_client`
+pub trait NetworkUrls: BaseApi {
+    const MAINNET_URL: &'static str;
+    const TESTNET_URL: &'static str;
+}
 
 impl BaseApi for () {}
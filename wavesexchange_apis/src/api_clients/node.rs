@@ -1,4 +1,4 @@
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{models::ids::AssetId, ApiResult, BaseApi, HttpClient, NetworkUrls};
 use reqwest::StatusCode;
 use serde_json::json;
 
@@ -7,6 +7,11 @@ pub struct Node;
 
 impl BaseApi for Node {}
 
+impl NetworkUrls for Node {
+    const MAINNET_URL: &'static str = "https://nodes.waves.exchange";
+    const TESTNET_URL: &'static str = "https://nodes-testnet.wavesnodes.com";
+}
+
 impl HttpClient<Node> {
     pub async fn data_entries(
         &self,
@@ -45,6 +50,23 @@ impl HttpClient<Node> {
             .await
     }
 
+    /// Current mining reward and the state of the reward voting
+    pub async fn rewards_status(&self) -> ApiResult<dto::RewardsStatus> {
+        self.create_req_handler(self.http_get("blockchain/rewards"), "node::rewards_status")
+            .execute()
+            .await
+    }
+
+    /// Activation status of the blockchain features
+    pub async fn activation_status(&self) -> ApiResult<dto::ActivationStatus> {
+        self.create_req_handler(
+            self.http_get("activation/status"),
+            "node::activation_status",
+        )
+        .execute()
+        .await
+    }
+
     pub async fn addr_balance_details(
         &self,
         address: impl AsRef<str>,
@@ -56,13 +78,17 @@ impl HttpClient<Node> {
             .await
     }
 
+    /// `address` is a string or a `models::ids::Address`
     pub async fn assets_balance(
         &self,
         address: impl AsRef<str>,
-        asset_ids: impl IntoIterator<Item = impl Into<String>>,
+        asset_ids: impl IntoIterator<Item = impl Into<AssetId>>,
     ) -> ApiResult<Option<dto::MatcherBalances>> {
         let url = format!("assets/balance/{}", address.as_ref());
-        let asset_ids = asset_ids.into_iter().map(Into::into).collect::<Vec<_>>();
+        let asset_ids = asset_ids
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<AssetId>>();
         let data = json!({ "ids": asset_ids });
         self.create_req_handler(self.http_post(url).json(&data), "node::assets_balance")
             .handle_status_code(StatusCode::NOT_FOUND, |_| async { Ok(None) })
@@ -83,7 +109,10 @@ impl HttpClient<Node> {
             .await
     }
 
-    pub async fn transaction_broadcast(&self, transaction: String) -> ApiResult<serde_json::Value> {
+    pub async fn transaction_broadcast(
+        &self,
+        transaction: String,
+    ) -> ApiResult<dto::BroadcastResponse> {
         self.create_req_handler(
             self.http_post("transactions/broadcast")
                 .header("Content-Type", "application/json")
@@ -94,6 +123,37 @@ impl HttpClient<Node> {
         .await
     }
 
+    /// Transactions in the node's UTX pool (mempool).
+    /// The node always returns the whole pool, the `limit` is applied on the client side.
+    pub async fn unconfirmed_transactions(
+        &self,
+        limit: Option<usize>,
+    ) -> ApiResult<Vec<dto::UnconfirmedTx>> {
+        let mut txs: Vec<dto::UnconfirmedTx> = self
+            .create_req_handler(
+                self.http_get("transactions/unconfirmed"),
+                "node::unconfirmed_transactions",
+            )
+            .execute()
+            .await?;
+        if let Some(limit) = limit {
+            txs.truncate(limit);
+        }
+        Ok(txs)
+    }
+
+    /// A transaction in the node's UTX pool, `None` if it is not there (either not broadcasted or already mined)
+    pub async fn unconfirmed_transaction(
+        &self,
+        id: impl AsRef<str>,
+    ) -> ApiResult<Option<dto::UnconfirmedTx>> {
+        let url = format!("transactions/unconfirmed/info/{}", id.as_ref());
+        self.create_req_handler(self.http_get(url), "node::unconfirmed_transaction")
+            .handle_status_code(StatusCode::NOT_FOUND, |_| async { Ok(None) })
+            .execute()
+            .await
+    }
+
     pub async fn state_changes_by_address(
         &self,
         address: impl AsRef<str>,
@@ -126,8 +186,13 @@ impl HttpClient<Node> {
 }
 
 pub mod dto {
-    use crate::models::dto::{DataEntryValue, TypeError};
+    use crate::models::{
+        amount::scale_amount,
+        dto::{DataEntryValue, TypeError},
+        serde_helpers::flexible_datetime,
+    };
     use bigdecimal::BigDecimal;
+    use chrono::{DateTime, Utc};
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
 
@@ -166,6 +231,90 @@ pub mod dto {
         pub height: i32,
     }
 
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct RewardsStatus {
+        pub height: u32,
+        pub total_waves_amount: i64,
+        pub current_reward: i64,
+        pub min_increment: i64,
+        pub term: u32,
+        pub next_check: u32,
+        pub voting_interval_start: u32,
+        pub voting_interval: u32,
+        pub voting_threshold: u32,
+        pub votes: RewardVotes,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    pub struct RewardVotes {
+        pub increase: u32,
+        pub decrease: u32,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ActivationStatus {
+        pub height: u32,
+        pub voting_interval: u32,
+        pub voting_threshold: u32,
+        pub next_check: u32,
+        pub features: Vec<FeatureStatus>,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct FeatureStatus {
+        pub id: u16,
+        pub description: String,
+        pub blockchain_status: FeatureBlockchainStatus,
+        pub node_status: FeatureNodeStatus,
+        /// `None` unless the feature is approved
+        pub activation_height: Option<u32>,
+        /// `None` unless the feature is being voted for
+        pub supporting_blocks: Option<u32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(from = "String")]
+    pub enum FeatureBlockchainStatus {
+        Voting,
+        Approved,
+        Activated,
+        Other(String),
+    }
+
+    impl From<String> for FeatureBlockchainStatus {
+        fn from(s: String) -> Self {
+            match s.as_str() {
+                "VOTING" => Self::Voting,
+                "APPROVED" => Self::Approved,
+                "ACTIVATED" => Self::Activated,
+                _ => Self::Other(s),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(from = "String")]
+    pub enum FeatureNodeStatus {
+        Implemented,
+        NotImplemented,
+        Voted,
+        Other(String),
+    }
+
+    impl From<String> for FeatureNodeStatus {
+        fn from(s: String) -> Self {
+            match s.as_str() {
+                "IMPLEMENTED" => Self::Implemented,
+                "NOT_IMPLEMENTED" => Self::NotImplemented,
+                "VOTED" => Self::Voted,
+                _ => Self::Other(s),
+            }
+        }
+    }
+
     #[derive(Debug, Serialize)]
     pub(super) struct StateRequest {
         pub keys: Vec<String>,
@@ -212,6 +361,13 @@ pub mod dto {
         pub quantity: Option<u64>,
     }
 
+    impl BalanceItem {
+        /// Balance as a decimal value, given the decimals of the asset (e.g. `AssetDetailItem::decimals`)
+        pub fn balance_decimal(&self, decimals: u8) -> BigDecimal {
+            scale_amount(i64::try_from(self.balance).unwrap_or(i64::MAX), decimals)
+        }
+    }
+
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct AssetDetailItem {
         #[serde(rename(deserialize = "assetId"))]
@@ -234,12 +390,105 @@ pub mod dto {
         Err(AssetDetailError),
     }
 
+    /// Response of `transactions/broadcast`: the broadcasted transaction
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(try_from = "serde_json::Value")]
+    pub struct BroadcastResponse {
+        pub id: String,
+        pub tx_type: u8,
+        pub timestamp: Option<i64>,
+        /// The whole response, for the fields not covered above
+        pub raw: serde_json::Value,
+    }
+
+    impl TryFrom<serde_json::Value> for BroadcastResponse {
+        type Error = serde_json::Error;
+
+        fn try_from(raw: serde_json::Value) -> Result<Self, Self::Error> {
+            #[derive(Deserialize)]
+            struct Fields {
+                id: String,
+                #[serde(rename = "type")]
+                tx_type: u8,
+                timestamp: Option<i64>,
+            }
+
+            let Fields {
+                id,
+                tx_type,
+                timestamp,
+            } = Fields::deserialize(&raw)?;
+            Ok(BroadcastResponse {
+                id,
+                tx_type,
+                timestamp,
+                raw,
+            })
+        }
+    }
+
+    /// A transaction in the UTX pool
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(try_from = "serde_json::Value")]
+    pub struct UnconfirmedTx {
+        pub id: String,
+        pub tx_type: u8,
+        pub sender: Option<String>,
+        pub sender_public_key: Option<String>,
+        pub fee: i64,
+        /// `None` for WAVES
+        pub fee_asset_id: Option<String>,
+        pub timestamp: i64,
+        /// The whole transaction, for the type-specific fields
+        pub raw: serde_json::Value,
+    }
+
+    impl TryFrom<serde_json::Value> for UnconfirmedTx {
+        type Error = serde_json::Error;
+
+        fn try_from(raw: serde_json::Value) -> Result<Self, Self::Error> {
+            #[derive(Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            struct Fields {
+                id: String,
+                #[serde(rename = "type")]
+                tx_type: u8,
+                sender: Option<String>,
+                sender_public_key: Option<String>,
+                fee: i64,
+                fee_asset_id: Option<String>,
+                timestamp: i64,
+            }
+
+            let Fields {
+                id,
+                tx_type,
+                sender,
+                sender_public_key,
+                fee,
+                fee_asset_id,
+                timestamp,
+            } = Fields::deserialize(&raw)?;
+            Ok(UnconfirmedTx {
+                id,
+                tx_type,
+                sender,
+                sender_public_key,
+                fee,
+                fee_asset_id,
+                timestamp,
+                raw,
+            })
+        }
+    }
+
     #[derive(Deserialize, Debug, Clone)]
     pub struct StateChangesResponse {
         #[serde(rename = "id")]
         pub transaction_id: String,
         pub height: i32,
-        pub timestamp: u64,
+        #[serde(with = "flexible_datetime")]
+        pub timestamp: DateTime<Utc>,
         pub sender: String,
         #[serde(rename = "type")]
         pub transaction_type: u8,
@@ -248,6 +497,29 @@ pub mod dto {
         #[serde(rename = "dApp")]
         pub dapp: Option<String>,
         pub call: Option<StateChangesResponseCall>,
+        /// Absent in the responses of the nodes not storing failed transactions
+        #[serde(rename = "applicationStatus", default)]
+        pub application_status: Option<ApplicationStatus>,
+    }
+
+    impl StateChangesResponse {
+        /// Whether the transaction was applied successfully, rather than failed or elided
+        pub fn is_succeeded(&self) -> bool {
+            matches!(
+                self.application_status,
+                None | Some(ApplicationStatus::Succeeded)
+            )
+        }
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ApplicationStatus {
+        Succeeded,
+        ScriptExecutionFailed,
+        Elided,
+        #[serde(other)]
+        Unknown,
     }
 
     #[derive(Deserialize, Debug, Clone)]
@@ -393,3 +665,270 @@ pub mod dto {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unconfirmed_tx_deserialize() {
+        let txs: Vec<dto::UnconfirmedTx> = serde_json::from_value(json!([
+            {
+                "type": 4,
+                "id": "5JbxFZGC9rQmcPsPMTFsNWbw1C2ZMDFnBVeHnh4dx9pk",
+                "sender": "3P5Zy5zkGaBNbmEZw2Bc6fd6v8kF7HDYVRG",
+                "senderPublicKey": "6oW8LCPBvvUcz3hZ8WvF4xeUPzJyXxYbfB3bBbB2Hj3N",
+                "fee": 100000,
+                "feeAssetId": null,
+                "timestamp": 1700000000000_i64,
+                "recipient": "3PLcn8jzKzwPVz4dHkfrB4BsBYNmt9pY5jY",
+                "amount": 1
+            },
+            {
+                "type": 16,
+                "id": "BdJ5w1KJ9Wb3YRnLdLiUvtcN1UFZkfnDLKH2FEBq6hJ3",
+                "sender": "3P5Zy5zkGaBNbmEZw2Bc6fd6v8kF7HDYVRG",
+                "fee": 500000,
+                "feeAssetId": "DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p",
+                "timestamp": 1700000000001_i64
+            }
+        ]))
+        .unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].tx_type, 4);
+        assert_eq!(txs[0].fee, 100000);
+        assert_eq!(txs[0].fee_asset_id, None);
+        assert_eq!(txs[0].raw["amount"], 1);
+        assert_eq!(txs[1].sender_public_key, None);
+        assert_eq!(
+            txs[1].fee_asset_id.as_deref(),
+            Some("DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p")
+        );
+
+        let res: Result<dto::UnconfirmedTx, _> = serde_json::from_value(json!({ "id": "abc" }));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_broadcast_response_deserialize() {
+        let res: dto::BroadcastResponse = serde_json::from_str(
+            r#"{"type":4,"id":"5JbxFZGC9rQmcPsPMTFsNWbw1C2ZMDFnBVeHnh4dx9pk","timestamp":1700000000000,"version":3}"#,
+        )
+        .unwrap();
+        assert_eq!(res.id, "5JbxFZGC9rQmcPsPMTFsNWbw1C2ZMDFnBVeHnh4dx9pk");
+        assert_eq!(res.tx_type, 4);
+        assert_eq!(res.timestamp, Some(1700000000000));
+        assert_eq!(res.raw["version"], 3);
+    }
+
+    #[test]
+    fn test_rewards_status_deserialize() {
+        let mainnet: dto::RewardsStatus = serde_json::from_value(json!({
+            "height": 4100000,
+            "totalWavesAmount": 11_450_000_000_000_000_i64,
+            "currentReward": 600000000,
+            "minIncrement": 50000000,
+            "term": 100000,
+            "nextCheck": 4199999,
+            "votingIntervalStart": 4190000,
+            "votingInterval": 10000,
+            "votingThreshold": 5001,
+            "votes": { "increase": 0, "decrease": 3 },
+            "daoAddress": "3PEgG7eZHLFhcfsTSaYxgRhZsh4AxMvA4Ms",
+            "xtnBuybackAddress": "3PFjHWuH6WXNJbwnfLHqNFBpwBS5dkYjTfv"
+        }))
+        .unwrap();
+        assert_eq!(mainnet.height, 4100000);
+        assert_eq!(mainnet.total_waves_amount, 11_450_000_000_000_000);
+        assert_eq!(mainnet.current_reward, 600000000);
+        assert_eq!(mainnet.min_increment, 50000000);
+        assert_eq!(mainnet.voting_interval_start, 4190000);
+        assert_eq!(mainnet.votes.decrease, 3);
+
+        // testnet has extra fields
+        let testnet: dto::RewardsStatus = serde_json::from_value(json!({
+            "height": 3000000,
+            "totalWavesAmount": 10_200_000_000_000_000_i64,
+            "currentReward": 500000000,
+            "minIncrement": 50000000,
+            "term": 30000,
+            "termAfterCappedRewardFeature": 10000,
+            "nextCheck": 3009999,
+            "votingIntervalStart": 3005000,
+            "votingInterval": 5000,
+            "votingThreshold": 2501,
+            "votes": { "increase": 10, "decrease": 0 },
+            "daoAddress": null,
+            "xtnBuybackAddress": null,
+            "xtnBuybackRewardPeriod": 100000
+        }))
+        .unwrap();
+        assert_eq!(testnet.term, 30000);
+        assert_eq!(testnet.votes.increase, 10);
+    }
+
+    #[test]
+    fn test_activation_status_deserialize() {
+        use dto::{FeatureBlockchainStatus, FeatureNodeStatus};
+
+        let mainnet: dto::ActivationStatus = serde_json::from_value(json!({
+            "height": 4100000,
+            "votingInterval": 10000,
+            "votingThreshold": 8000,
+            "nextCheck": 4109999,
+            "features": [
+                {
+                    "id": 1,
+                    "description": "Minimum Generating Balance of 1000 WAVES",
+                    "blockchainStatus": "ACTIVATED",
+                    "nodeStatus": "IMPLEMENTED",
+                    "activationHeight": 0
+                },
+                {
+                    "id": 22,
+                    "description": "Ride V6, MetaMask support",
+                    "blockchainStatus": "APPROVED",
+                    "nodeStatus": "IMPLEMENTED",
+                    "activationHeight": 4105000
+                }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(mainnet.next_check, 4109999);
+        assert_eq!(mainnet.features.len(), 2);
+        assert_eq!(
+            mainnet.features[0].blockchain_status,
+            FeatureBlockchainStatus::Activated
+        );
+        assert_eq!(mainnet.features[0].activation_height, Some(0));
+        assert_eq!(
+            mainnet.features[1].blockchain_status,
+            FeatureBlockchainStatus::Approved
+        );
+
+        // testnet has features being voted for and not implemented by the node
+        let testnet: dto::ActivationStatus = serde_json::from_value(json!({
+            "height": 3000000,
+            "votingInterval": 5000,
+            "votingThreshold": 4000,
+            "nextCheck": 3004999,
+            "features": [
+                {
+                    "id": 23,
+                    "description": "Boost Block Reward",
+                    "blockchainStatus": "VOTING",
+                    "nodeStatus": "VOTED",
+                    "supportingBlocks": 1234
+                },
+                {
+                    "id": 99,
+                    "description": "Unknown feature",
+                    "blockchainStatus": "SUSPENDED",
+                    "nodeStatus": "NOT_IMPLEMENTED",
+                    "supportingBlocks": 0
+                }
+            ]
+        }))
+        .unwrap();
+        let voting = &testnet.features[0];
+        assert_eq!(voting.blockchain_status, FeatureBlockchainStatus::Voting);
+        assert_eq!(voting.node_status, FeatureNodeStatus::Voted);
+        assert_eq!(voting.activation_height, None);
+        assert_eq!(voting.supporting_blocks, Some(1234));
+        let unknown = &testnet.features[1];
+        assert_eq!(
+            unknown.blockchain_status,
+            FeatureBlockchainStatus::Other("SUSPENDED".to_owned())
+        );
+        assert_eq!(unknown.node_status, FeatureNodeStatus::NotImplemented);
+    }
+
+    #[test]
+    fn test_state_changes_application_status() {
+        let tx = |status: Option<&str>| {
+            let mut tx = json!({
+                "type": 16,
+                "id": "BdJ5w1KJ9Wb3YRnLdLiUvtcN1UFZkfnDLKH2FEBq6hJ3",
+                "height": 3000000,
+                "timestamp": 1700000000000_i64,
+                "sender": "3P5Zy5zkGaBNbmEZw2Bc6fd6v8kF7HDYVRG",
+                "dApp": "3PLcn8jzKzwPVz4dHkfrB4BsBYNmt9pY5jY",
+                "call": { "function": "swap", "args": [{ "type": "integer", "value": 1 }] },
+                "stateChanges": { "data": [], "transfers": [] }
+            });
+            if let Some(status) = status {
+                tx["applicationStatus"] = json!(status);
+            }
+            serde_json::from_value::<dto::StateChangesResponse>(tx).unwrap()
+        };
+
+        let succeeded = tx(Some("succeeded"));
+        assert_eq!(
+            succeeded.application_status,
+            Some(dto::ApplicationStatus::Succeeded)
+        );
+        assert!(succeeded.is_succeeded());
+
+        let failed = tx(Some("script_execution_failed"));
+        assert_eq!(
+            failed.application_status,
+            Some(dto::ApplicationStatus::ScriptExecutionFailed)
+        );
+        assert!(!failed.is_succeeded());
+
+        assert!(!tx(Some("elided")).is_succeeded());
+        assert_eq!(
+            tx(Some("new_status")).application_status,
+            Some(dto::ApplicationStatus::Unknown)
+        );
+
+        let legacy = tx(None);
+        assert_eq!(legacy.application_status, None);
+        assert!(legacy.is_succeeded());
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_transaction_not_found() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+
+        let tx = json!({
+            "type": 4,
+            "id": "abc",
+            "fee": 100000,
+            "timestamp": 1700000000000_i64
+        });
+        let mocks = MockResponses::new()
+            .on(
+                Method::GET,
+                "/transactions/unconfirmed/info/abc",
+                MockResponse::json(&tx),
+            )
+            .on(
+                Method::GET,
+                "/transactions/unconfirmed",
+                MockResponse::json(&json!([tx, tx, tx])),
+            );
+        let client = HttpClient::<Node>::mocked(mocks);
+
+        let found = client.unconfirmed_transaction("abc").await.unwrap();
+        assert_eq!(found.map(|tx| tx.id).as_deref(), Some("abc"));
+        assert!(client
+            .unconfirmed_transaction("def")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            client
+                .unconfirmed_transactions(Some(2))
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            client.unconfirmed_transactions(None).await.unwrap().len(),
+            3
+        );
+    }
+}
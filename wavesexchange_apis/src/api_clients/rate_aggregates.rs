@@ -1,4 +1,4 @@
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{models::asset_id::normalize_waves, ApiResult, BaseApi, HttpClient};
 use chrono::{Duration, NaiveDate};
 use futures::future::try_join_all;
 use itertools::Itertools;
@@ -29,8 +29,8 @@ impl HttpClient<RateAggregates> {
 
         let request_url = format!(
             "rate_aggregates?pairs[]={}/{}&timestamp__gte={:?}&timestamp__lt={:?}",
-            amount_asset_id.as_ref(),
-            price_asset_id.as_ref(),
+            normalize_waves(amount_asset_id.as_ref()),
+            normalize_waves(price_asset_id.as_ref()),
             timestamp_gte,
             timestamp_lt
         );
@@ -98,7 +98,7 @@ impl HttpClient<RateAggregates> {
 
         let qs_pairs = asset_pairs
             .into_iter()
-            .map(|(a, b)| format!("pairs[]={}/{}", a, b))
+            .map(|(a, b)| format!("pairs[]={}/{}", normalize_waves(&a), normalize_waves(&b)))
             .join("&");
 
         let timestamp_gte = start_date_inclusive
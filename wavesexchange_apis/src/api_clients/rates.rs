@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{models::ids::AssetId, ApiResult, BaseApi, Error, HttpClient};
 use std::fmt::Debug;
 
 #[derive(Clone, Debug)]
@@ -9,16 +9,34 @@ pub struct RatesService;
 impl BaseApi for RatesService {}
 
 impl HttpClient<RatesService> {
+    /// Fails with `Error::ValidationError` listing the bad pairs, without sending the request,
+    /// if an asset id contains `/`, so that the pair can't be split by the service
     pub async fn rates(
         &self,
-        asset_pairs: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        asset_pairs: impl IntoIterator<Item = (impl Into<AssetId>, impl Into<AssetId>)>,
         timestamp: Option<DateTime<Utc>>,
     ) -> ApiResult<dto::RatesResponse> {
+        // WAVES is normalized by `AssetId`
         let pairs = asset_pairs
             .into_iter()
-            .map(|(a, b)| format!("{}/{}", a.into(), b.into()))
+            .map(|(a, b)| {
+                let (a, b): (AssetId, AssetId) = (a.into(), b.into());
+                format!("{a}/{b}")
+            })
             .collect::<Vec<_>>();
 
+        let invalid_pairs = pairs
+            .iter()
+            .filter(|pair| pair.matches('/').count() != 1)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !invalid_pairs.is_empty() {
+            return Err(Error::ValidationError(format!(
+                "Request 'rates::rates': invalid asset pairs: {}",
+                invalid_pairs.join(", ")
+            )));
+        }
+
         let mut rates = vec![];
 
         for chunk_pairs in pairs.chunks(100) {
@@ -68,3 +86,42 @@ pub mod dto {
         pub timestamp: Option<DateTime<Utc>>,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_pairs() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+        use serde_json::json;
+
+        let mocks = MockResponses::new().on(
+            Method::POST,
+            "/rates",
+            MockResponse::json(&json!({ "data": [] })),
+        );
+        let client = HttpClient::<RatesService>::mocked(mocks.clone());
+
+        let pairs = [("WAVES", "a/b"), ("", "usd"), ("c/", "/d")];
+        match client.rates(pairs, None).await {
+            Err(Error::ValidationError(msg)) => {
+                assert!(
+                    msg.ends_with("invalid asset pairs: WAVES/a/b, c///d"),
+                    "{msg}"
+                )
+            }
+            res => panic!("unexpected result: {res:?}"),
+        }
+        assert!(mocks.received().is_empty());
+
+        let res = client.rates([("", "usd")], None).await.unwrap();
+        assert!(res.data.is_empty());
+        let received = mocks.received();
+        assert_eq!(
+            received[0].body_json::<serde_json::Value>().unwrap()["pairs"],
+            json!(["WAVES/usd"])
+        );
+    }
+}
@@ -1,9 +1,14 @@
-use crate::{ApiResult, BaseApi, HttpClient};
+use crate::{ApiResult, BaseApi, Error, HttpClient, NetworkUrls};
+use futures::{StreamExt, TryStreamExt};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use reqwest::StatusCode;
 use serde_json::json;
+use std::{collections::HashMap, time::Duration};
 use wavesexchange_warp::pagination::List;
 
+/// Max number of `entries` requests executed at the same time by `entries_mget` with a `HistoryQuery`
+const MGET_HISTORY_CONCURRENCY: usize = 8;
+
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub enum HistoryQuery {
@@ -11,11 +16,37 @@ pub enum HistoryQuery {
     Timestamp(String),
 }
 
+/// Bounds of fetching all the pages by `search_bounded`, unbounded by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchBounds {
+    pub max_pages: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl SearchBounds {
+    /// Fail with `Error::TooManyPages` if there are still more pages after `max_pages`
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    /// Fail with `Error::DeadlineExceeded` if all the pages are not fetched in `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct StateService;
 
 impl BaseApi for StateService {}
 
+impl NetworkUrls for StateService {
+    const MAINNET_URL: &'static str = "https://waves.exchange/api/v1/state";
+    const TESTNET_URL: &'static str = "https://testnet.waves.exchange/api/v1/state";
+}
+
 impl HttpClient<StateService> {
     pub async fn entries(
         &self,
@@ -52,20 +83,102 @@ impl HttpClient<StateService> {
             .await
     }
 
+    /// Fetch several keys of one address, the keys missing upstream are missing in the result.
+    ///
+    /// Without `history_query` it's a single search (by the `in` filter on the address and the key),
+    /// otherwise the keys are requested one by one with `entries` (at most 8 at a time),
+    /// because the search doesn't support history queries.
+    pub async fn entries_mget(
+        &self,
+        address: impl AsRef<str>,
+        keys: &[impl AsRef<str>],
+        history_query: Option<HistoryQuery>,
+    ) -> ApiResult<HashMap<String, dto::DataEntry>> {
+        let address = address.as_ref();
+        if keys.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let entries = match history_query {
+            None => {
+                let values = keys
+                    .iter()
+                    .map(|key| json!([address, key.as_ref()]))
+                    .collect::<Vec<_>>();
+                let query = json!({
+                    "filter": {
+                        "in": {
+                            "properties": [{ "address": {} }, { "key": {} }],
+                            "values": values,
+                        }
+                    }
+                });
+                self.search(query, None, None).await?.items
+            }
+            Some(history_query) => futures::stream::iter(keys)
+                .map(|key| self.entries(address, key, Some(history_query.clone())))
+                .buffered(MGET_HISTORY_CONCURRENCY)
+                .try_collect::<Vec<_>>()
+                .await?
+                .into_iter()
+                .flatten()
+                .collect(),
+        };
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.key.clone(), entry))
+            .collect())
+    }
+
+    /// Fetch all the pages of the search results.
+    /// See `search_bounded` to limit the number of pages or the time taken.
     pub async fn search(
         &self,
         query: impl Into<serde_json::Value>,
         limit: Option<u64>,
         offset: Option<u64>,
+    ) -> ApiResult<List<dto::DataEntry>> {
+        self.search_bounded(query, limit, offset, SearchBounds::default())
+            .await
+    }
+
+    /// Same as `search`, failing if all the pages are not fetched within the `bounds`
+    pub async fn search_bounded(
+        &self,
+        query: impl Into<serde_json::Value>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        bounds: SearchBounds,
+    ) -> ApiResult<List<dto::DataEntry>> {
+        let search = self.search_pages(query.into(), limit, offset, bounds.max_pages);
+        match bounds.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, search)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::DeadlineExceeded {
+                        req_info: "state::search".to_owned(),
+                        timeout,
+                    })
+                }),
+            None => search.await,
+        }
+    }
+
+    async fn search_pages(
+        &self,
+        query: serde_json::Value,
+        limit: Option<u64>,
+        offset: Option<u64>,
+        max_pages: Option<usize>,
     ) -> ApiResult<List<dto::DataEntry>> {
         let mut entries = vec![];
         let limit = limit.unwrap_or(1000);
         let offset = offset.unwrap_or(0);
 
-        let mut qv: serde_json::Value = query.into();
+        let mut qv = query;
         qv["limit"] = json!(limit);
         qv["offset"] = json!(offset);
 
+        let mut pages = 0;
         loop {
             let res: List<dto::DataEntry> = self
                 .create_req_handler::<dto::StateSearchResult>(
@@ -80,6 +193,7 @@ impl HttpClient<StateService> {
                 .map(|v| *v = (v.as_u64().unwrap() + limit).into());
 
             entries.extend(res.items);
+            pages += 1;
 
             if !res.page_info.has_next_page {
                 return Ok(List {
@@ -87,6 +201,12 @@ impl HttpClient<StateService> {
                     items: entries,
                 });
             }
+            if max_pages.is_some_and(|max_pages| pages >= max_pages) {
+                return Err(Error::TooManyPages {
+                    req_info: "state::search".to_owned(),
+                    pages,
+                });
+            }
         }
     }
 }
@@ -107,3 +227,146 @@ impl From<dto::StateSearchResult> for List<dto::DataEntry> {
         List::new(ssr.entries, ssr.has_next_page, None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_search_bounds() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+
+        let entry = json!({ "address": "addr", "key": "k", "value": 1 });
+        let endless_page = json!({ "entries": [entry], "has_next_page": true });
+        let mocks =
+            MockResponses::new().on(Method::POST, "/search", MockResponse::json(&endless_page));
+        let client = HttpClient::<StateService>::mocked(mocks.clone());
+
+        let bounds = SearchBounds::default().with_max_pages(3);
+        let res = client
+            .search_bounded(json!({}), Some(1), None, bounds)
+            .await;
+        assert!(matches!(res, Err(Error::TooManyPages { pages: 3, .. })));
+        let received = mocks.received();
+        assert_eq!(received.len(), 3);
+        let offsets = received
+            .iter()
+            .map(|req| req.body_json::<serde_json::Value>().unwrap()["offset"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [json!(0), json!(1), json!(2)]);
+
+        let mocks = MockResponses::new().on(
+            Method::POST,
+            "/search",
+            MockResponse::json(&endless_page).with_delay(Duration::from_millis(20)),
+        );
+        let client = HttpClient::<StateService>::mocked(mocks.clone());
+        let timeout = Duration::from_millis(100);
+        let bounds = SearchBounds::default().with_timeout(timeout);
+        let res = client.search_bounded(json!({}), None, None, bounds).await;
+        assert!(matches!(res, Err(Error::DeadlineExceeded { timeout: t, .. }) if t == timeout));
+        assert!(mocks.received().len() > 1);
+
+        // the last page within the bounds
+        let last_page = json!({ "entries": [entry], "has_next_page": false });
+        let mocks = MockResponses::new()
+            .on(Method::POST, "/search", MockResponse::json(&endless_page))
+            .on(Method::POST, "/search", MockResponse::json(&last_page));
+        let client = HttpClient::<StateService>::mocked(mocks);
+        let bounds = SearchBounds::default()
+            .with_max_pages(2)
+            .with_timeout(timeout);
+        let res = client
+            .search_bounded(json!({}), None, None, bounds)
+            .await
+            .unwrap();
+        assert_eq!(res.items.len(), 2);
+        assert!(!res.page_info.has_next_page);
+    }
+
+    #[tokio::test]
+    async fn test_entries_mget() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+
+        let keys = ["%s__a", "%s__b", "%s__missing"];
+        let page = json!({
+            "entries": [
+                { "address": "addr", "key": "%s__a", "value": 1 },
+                { "address": "addr", "key": "%s__b", "value": "b" },
+            ],
+            "has_next_page": false,
+        });
+        let mocks = MockResponses::new().on(Method::POST, "/search", MockResponse::json(&page));
+        let client = HttpClient::<StateService>::mocked(mocks.clone());
+
+        let entries = client.entries_mget("addr", &keys, None).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["%s__a"].value, dto::DataEntryValue::Integer(1));
+        assert!(!entries.contains_key("%s__missing"));
+
+        let received = mocks.received();
+        assert_eq!(received.len(), 1);
+        let query = received[0].body_json::<serde_json::Value>().unwrap();
+        assert_eq!(
+            query["filter"]["in"]["values"],
+            json!([
+                ["addr", "%s__a"],
+                ["addr", "%s__b"],
+                ["addr", "%s__missing"]
+            ])
+        );
+        assert_eq!(
+            query["filter"]["in"]["properties"],
+            json!([{ "address": {} }, { "key": {} }])
+        );
+
+        let no_keys: [&str; 0] = [];
+        assert!(client
+            .entries_mget("addr", &no_keys, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(mocks.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_entries_mget_history() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+
+        let entry = |key: &str| json!({ "address": "addr", "key": key, "value": 1 });
+        let mocks = MockResponses::new()
+            .on(
+                Method::GET,
+                "/entries/addr/%25s%5F%5Fa",
+                MockResponse::json(&entry("%s__a")),
+            )
+            .on(
+                Method::GET,
+                "/entries/addr/%25s%5F%5Fb%20c",
+                MockResponse::json(&entry("%s__b c")),
+            );
+        let client = HttpClient::<StateService>::mocked(mocks.clone());
+
+        let keys = vec![
+            "%s__a".to_string(),
+            "%s__b c".to_string(),
+            "%s__missing".to_string(),
+        ];
+        let entries = client
+            .entries_mget("addr", &keys, Some(HistoryQuery::Height(42)))
+            .await
+            .unwrap();
+        let mut found = entries.keys().map(String::as_str).collect::<Vec<_>>();
+        found.sort_unstable();
+        assert_eq!(found, ["%s__a", "%s__b c"]);
+
+        let received = mocks.received();
+        assert_eq!(received.len(), 3);
+        assert!(received
+            .iter()
+            .all(|req| req.query.as_deref() == Some("height=42")));
+    }
+}
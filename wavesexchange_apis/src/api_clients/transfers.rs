@@ -11,16 +11,18 @@ impl HttpClient<Transfers> {
         &self,
         req: dto::SearchTransfersRequest,
     ) -> ApiResult<List<dto::TransferResponse>> {
-        let request_url = format!("transfers?{}", serde_qs::to_string(&req).unwrap());
-
-        self.create_req_handler(self.http_get(&request_url), "transfers::get")
-            .execute()
-            .await
+        self.create_req_handler(
+            self.http_get_with_query("transfers", &req)?,
+            "transfers::get",
+        )
+        .execute()
+        .await
     }
 }
 
 pub mod dto {
-    use chrono::{DateTime, FixedOffset, Utc};
+    use crate::models::serde_helpers::flexible_datetime;
+    use chrono::{DateTime, Utc};
     use serde::{Deserialize, Serialize};
 
     #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -35,7 +37,8 @@ pub mod dto {
     pub struct TransferResponse {
         pub origin_transaction_type: TxType,
         pub sender: String,
-        pub block_timestamp: Option<DateTime<FixedOffset>>,
+        #[serde(default, with = "flexible_datetime::option")]
+        pub block_timestamp: Option<DateTime<Utc>>,
         pub recipient: Option<String>,
         pub amount: i64,
         pub asset_id: String,
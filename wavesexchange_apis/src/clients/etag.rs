@@ -0,0 +1,118 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Cache of the responses with an `ETag`, for conditional requests
+/// (see `WXRequestHandler::with_etag_cache`).
+///
+/// Keeps the last `ETag` and the decoded value of a response by request info and url.
+/// Bounded, the least recently used entries are evicted.
+/// Can be shared between requests returning values of different types.
+#[derive(Debug)]
+pub struct EtagCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    /// Incremented on every access, to find the least recently used entry
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    etag: String,
+    value: Arc<dyn Any + Send + Sync>,
+    last_used: u64,
+}
+
+impl EtagCache {
+    pub fn new(capacity: usize) -> Self {
+        EtagCache {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().map.clear();
+    }
+
+    /// Cached `ETag` and value, if the value is of the type `T`
+    pub(crate) fn get<T: Clone + 'static>(&self, key: &str) -> Option<(String, T)> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let entry = entries.map.get_mut(key)?;
+        entry.last_used = tick;
+        let value = entry.value.downcast_ref::<T>()?.clone();
+        Some((entry.etag.clone(), value))
+    }
+
+    pub(crate) fn insert<T: Send + Sync + 'static>(&self, key: String, etag: String, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let last_used = entries.tick;
+        if entries.map.len() >= self.capacity && !entries.map.contains_key(&key) {
+            let lru_key = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru_key) = lru_key {
+                entries.map.remove(&lru_key);
+            }
+        }
+        let entry = Entry {
+            etag,
+            value: Arc::new(value),
+            last_used,
+        };
+        entries.map.insert(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag_cache_lru() {
+        let cache = EtagCache::new(2);
+        cache.insert("a".to_string(), "etag-a".to_string(), 1_u32);
+        cache.insert("b".to_string(), "etag-b".to_string(), 2_u32);
+        assert_eq!(cache.get::<u32>("a"), Some(("etag-a".to_string(), 1)));
+        // wrong type
+        assert_eq!(cache.get::<String>("a"), None);
+
+        // "b" is the least recently used one
+        cache.insert("c".to_string(), "etag-c".to_string(), 3_u32);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get::<u32>("b"), None);
+        assert_eq!(cache.get::<u32>("a"), Some(("etag-a".to_string(), 1)));
+        assert_eq!(cache.get::<u32>("c"), Some(("etag-c".to_string(), 3)));
+
+        // replacing an entry doesn't evict others
+        cache.insert("c".to_string(), "etag-c2".to_string(), 4_u32);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get::<u32>("c"), Some(("etag-c2".to_string(), 4)));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -1,22 +1,186 @@
-use crate::{ApiResult, BaseApi};
-use std::{marker::PhantomData, sync::Arc};
-use waves_protobuf_schemas::tonic;
+use crate::{ApiResult, BaseApi, Error};
+use std::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use waves_protobuf_schemas::tonic::{
+    self,
+    metadata::{Ascii, MetadataValue},
+};
+use wavesexchange_log::{debug, warn};
 
 pub use waves_protobuf_schemas::waves::events::grpc::blockchain_updates_api_client::BlockchainUpdatesApiClient;
 
 #[derive(Clone, Debug)]
 pub struct GrpcClient<A: BaseApi> {
     pub grpc_client: BlockchainUpdatesApiClient<tonic::transport::Channel>,
+    timeout: Option<Duration>,
+    metadata: Vec<(&'static str, MetadataValue<Ascii>)>,
+    token_provider: Option<TokenProvider>,
     _pd: PhantomData<A>,
 }
 
+#[derive(Clone)]
+struct TokenProvider(Arc<dyn Fn() -> String + Send + Sync>);
+
+impl fmt::Debug for TokenProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TokenProvider")
+    }
+}
+
 impl<A: BaseApi> GrpcClient<A> {
     pub async fn new(blockchain_updates_url: &str) -> ApiResult<Self> {
-        Ok(GrpcClient {
-            grpc_client: BlockchainUpdatesApiClient::connect(blockchain_updates_url.to_owned())
-                .await
-                .map_err(Arc::new)?,
+        let channel = tonic::transport::Endpoint::from_shared(blockchain_updates_url.to_owned())
+            .map_err(Arc::new)?
+            .connect()
+            .await
+            .map_err(Arc::new)?;
+        Ok(Self::from_channel(channel))
+    }
+
+    /// Create a `GrpcClient` over an already configured channel
+    /// (e.g. a lazily connected one, or one with custom endpoint settings)
+    pub fn from_channel(channel: tonic::transport::Channel) -> Self {
+        GrpcClient {
+            grpc_client: BlockchainUpdatesApiClient::new(channel),
+            timeout: None,
+            metadata: Vec::new(),
+            token_provider: None,
             _pd: PhantomData,
-        })
+        }
+    }
+
+    /// Set the default deadline for every call made by this client.
+    /// Without it calls are awaited indefinitely.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Add static metadata sent with every call, e.g. an api key.
+    ///
+    /// Panics if the key is not a valid lowercase ascii metadata key.
+    pub fn with_metadata(mut self, key: &'static str, value: MetadataValue<Ascii>) -> Self {
+        self.metadata.push((key, value));
+        self
+    }
+
+    /// Set the `authorization` metadata of every call to the value returned by `provider`
+    /// (e.g. `Bearer <token>`), called per call so that rotated tokens are picked up
+    pub fn with_token_provider(
+        mut self,
+        provider: impl Fn() -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.token_provider = Some(TokenProvider(Arc::new(provider)));
+        self
+    }
+
+    /// Prepare a request with the deadline propagated to the server (`grpc-timeout` header)
+    /// and the client's metadata
+    pub(crate) fn request<T>(&self, message: T, timeout: Option<Duration>) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        if let Some(timeout) = timeout {
+            request.set_timeout(timeout);
+        }
+        let metadata = request.metadata_mut();
+        for (key, value) in &self.metadata {
+            metadata.append(*key, value.clone());
+        }
+        if let Some(TokenProvider(provider)) = &self.token_provider {
+            match MetadataValue::try_from(provider()) {
+                Ok(token) => {
+                    metadata.insert("authorization", token);
+                }
+                Err(_) => warn!("token provider returned an invalid authorization value"),
+            }
+        }
+        request
+    }
+
+    /// Await a gRPC call, failing with `Error::GrpcTimeout` if it doesn't complete in time.
+    ///
+    /// The deadline is enforced on the client side as well, because a stuck server
+    /// (or connection) will never report `DEADLINE_EXCEEDED` by itself.
+    pub(crate) async fn call<T>(
+        &self,
+        rpc_name: &str,
+        call: impl Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+        timeout: Option<Duration>,
+    ) -> ApiResult<T> {
+        debug!("calling '{}'", rpc_name);
+        let start_time = Instant::now();
+        let res = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, call).await.map_err(|_| {
+                debug!("call '{}' timed out after {:?}", rpc_name, timeout);
+                Error::GrpcTimeout(timeout)
+            })?,
+            None => call.await,
+        };
+        debug!(
+            "call '{}' took {:?}ms, status: {:?}",
+            rpc_name,
+            start_time.elapsed().as_millis(),
+            res.as_ref()
+                .map_or_else(|status| status.code(), |_| tonic::Code::Ok),
+        );
+        match res {
+            Ok(response) => Ok(response.into_inner()),
+            Err(status) if status.code() == tonic::Code::DeadlineExceeded => {
+                Err(Error::GrpcTimeout(timeout.unwrap_or_default()))
+            }
+            Err(status) => Err(Error::GrpcStatusError(Arc::new(status))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_metadata() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Clone, Debug)]
+        struct Api;
+        impl BaseApi for Api {}
+
+        let channel = tonic::transport::Endpoint::from_static("http://127.0.0.1:1").connect_lazy();
+        let token_calls = Arc::new(AtomicUsize::new(0));
+        let client = GrpcClient::<Api>::from_channel(channel)
+            .with_metadata("x-api-key", MetadataValue::from_static("secret"))
+            .with_metadata("x-tenant", MetadataValue::from_static("a"))
+            .with_metadata("x-tenant", MetadataValue::from_static("b"))
+            .with_token_provider({
+                let token_calls = token_calls.clone();
+                move || {
+                    let n = token_calls.fetch_add(1, Ordering::SeqCst);
+                    format!("Bearer token-{n}")
+                }
+            });
+
+        let request = client.request((), Some(Duration::from_secs(5)));
+        let metadata = request.metadata();
+        assert_eq!(metadata.get("x-api-key").unwrap(), "secret");
+        let tenants = metadata.get_all("x-tenant").iter().collect::<Vec<_>>();
+        assert_eq!(tenants, ["a", "b"]);
+        assert_eq!(metadata.get("authorization").unwrap(), "Bearer token-0");
+        assert!(metadata.get("grpc-timeout").is_some());
+
+        // the token is requested anew for every call
+        let request = client.request((), None);
+        assert_eq!(
+            request.metadata().get("authorization").unwrap(),
+            "Bearer token-1"
+        );
+        assert!(request.metadata().get("grpc-timeout").is_none());
     }
 }
@@ -1,12 +1,22 @@
-use crate::{error, ApiResult, BaseApi};
+use super::etag::EtagCache;
+use crate::{error, ApiResult, BaseApi, Error};
 use futures::{future::BoxFuture, Future};
-use reqwest::{Client, ClientBuilder, Error as ReqError, RequestBuilder, Response, StatusCode};
-use serde::de::DeserializeOwned;
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_NONE_MATCH},
+    Client, ClientBuilder, Error as ReqError, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use wavesexchange_log::debug;
 
+/// Default max `limit` of paginated requests, see `HttpClientBuilder::with_max_limit`
+const DEFAULT_MAX_LIMIT: usize = 100;
+
 /// A rust http interface to various waves services (non-exhaustive)
 ///
 /// Usage example:
@@ -23,6 +33,12 @@ use wavesexchange_log::debug;
 pub struct HttpClient<A: BaseApi> {
     base_url: Option<String>,
     client: Client,
+    default_headers: HeaderMap,
+    limit: Option<ConcurrencyLimit>,
+    max_limit: usize,
+    max_response_size: Option<usize>,
+    #[cfg(any(test, feature = "test-util"))]
+    pub(super) mock: Option<super::mock::MockResponses>,
     _pd: PhantomData<A>,
 }
 
@@ -43,19 +59,46 @@ impl<A: BaseApi> HttpClient<A> {
 
     fn prepare_url(&self, url: impl Into<String>) -> String {
         match &self.base_url {
-            Some(u) => format!("{}/{}", u, url.into()),
+            Some(base_url) => join_url(base_url, &url.into()),
             None => url.into(),
         }
     }
 
-    /// Perform a GET request on `self.base_url/url`
+    /// Perform a GET request on `self.base_url/url`, with the `BaseApi::default_headers` of the service.
+    /// See `join_url` for the exact joining rules.
     pub fn http_get(&self, url: impl Into<String>) -> RequestBuilder {
-        self.client.get(self.prepare_url(url))
+        self.client
+            .get(self.prepare_url(url))
+            .headers(self.default_headers.clone())
     }
 
-    /// Perform a POST request on `self.base_url/url`
+    /// Perform a POST request on `self.base_url/url`, with the `BaseApi::default_headers` of the service.
+    /// See `join_url` for the exact joining rules.
     pub fn http_post(&self, url: impl Into<String>) -> RequestBuilder {
-        self.client.post(self.prepare_url(url))
+        self.client
+            .post(self.prepare_url(url))
+            .headers(self.default_headers.clone())
+    }
+
+    /// Perform a GET request on `self.base_url/url` with the query string serialized from `query`
+    /// (appended to the query string of `url`, if any).
+    ///
+    /// Fails with `Error::QueryStringError` if the query can't be serialized.
+    pub fn http_get_with_query(
+        &self,
+        url: impl Into<String>,
+        query: &impl Serialize,
+    ) -> ApiResult<RequestBuilder> {
+        Ok(self.http_get(url_with_query(url.into(), query)?))
+    }
+
+    /// Same as `http_get_with_query`, but for POST
+    pub fn http_post_with_query(
+        &self,
+        url: impl Into<String>,
+        query: &impl Serialize,
+    ) -> ApiResult<RequestBuilder> {
+        Ok(self.http_post(url_with_query(url.into(), query)?))
     }
 
     /// Get reqwest client
@@ -70,12 +113,54 @@ impl<A: BaseApi> HttpClient<A> {
         }
     }
 
+    /// Max `limit` accepted by paginated requests (see `HttpClientBuilder::with_max_limit`)
+    pub fn max_limit(&self) -> usize {
+        self.max_limit
+    }
+
+    /// Max size of the response body (after decompression) handled by `WXRequestHandler`,
+    /// see `HttpClientBuilder::with_max_response_size`
+    pub fn max_response_size(&self) -> Option<usize> {
+        self.max_response_size
+    }
+
+    /// Number of requests being executed now, `None` if the concurrency is not limited
+    /// (see `HttpClientBuilder::with_max_concurrent_requests`)
+    pub fn in_flight_requests(&self) -> Option<usize> {
+        self.limit.as_ref().map(ConcurrencyLimit::in_flight)
+    }
+
+    /// Perform the request.
+    ///
+    /// If the concurrency is limited, the request occupies a slot only until the response headers are received,
+    /// use `create_req_handler` to hold it while the response body is handled as well.
+    ///
+    /// With the `otel` feature, an OpenTelemetry client span is created for the request
+    /// by the global tracer provider, and its context is injected into the request headers
+    /// by the global propagator (e.g. `traceparent`).
     pub async fn do_request(
         &self,
         req: RequestBuilder,
         req_info: impl Into<String>,
     ) -> ApiResult<Response> {
         let req_info = req_info.into();
+        let _permit = self.acquire_permit(&req_info).await?;
+        self.do_request_unlimited(req, req_info).await
+    }
+
+    /// Wait for a free slot if the concurrency is limited
+    async fn acquire_permit(&self, req_info: &str) -> ApiResult<Option<OwnedSemaphorePermit>> {
+        match &self.limit {
+            Some(limit) => limit.acquire(req_info).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn do_request_unlimited(
+        &self,
+        req: RequestBuilder,
+        req_info: String,
+    ) -> ApiResult<Response> {
         let request = req.build().unwrap();
         let method = request.method().as_str();
         let url = request.url().as_str();
@@ -83,12 +168,26 @@ impl<A: BaseApi> HttpClient<A> {
 
         debug!("requesting '{}', url: {}", req_info, log_method_url);
 
+        #[cfg(feature = "otel")]
+        let (request, span) = super::otel::start(request, &req_info);
+
+        #[cfg(any(test, feature = "test-util"))]
+        if let Some(mock) = &self.mock {
+            let resp = mock.respond(request).await;
+            #[cfg(feature = "otel")]
+            span.end(&resp);
+            return resp;
+        }
+
         let req_start_time = chrono::Utc::now();
         let resp = self
             .client
             .execute(request)
             .await
-            .map_err(|err| error::request_failed(err, &req_info))?;
+            .map_err(|err| error::request_failed(err, &req_info));
+        #[cfg(feature = "otel")]
+        span.end(&resp);
+        let resp = resp?;
 
         let req_end_time = chrono::Utc::now();
         debug!(
@@ -109,9 +208,125 @@ impl<A: BaseApi> HttpClient<A> {
     }
 }
 
+/// Join a base url with a relative url:
+/// * an absolute `http(s)://` url replaces the base url completely;
+/// * an empty url, or the one starting with `?` or `#`, is appended to the base url as is,
+///   so `base?x=1` and `base/?x=1` can be both requested, depending on the base url;
+/// * otherwise the parts are joined with exactly one `/`,
+///   with duplicate slashes in the path of the relative url collapsed.
+///
+/// Unlike `Url::join`, the last path segment of the base url is never replaced.
+fn join_url(base_url: &str, url: &str) -> String {
+    let is_absolute = ["http://", "https://"].iter().any(|scheme| {
+        url.get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    });
+    if is_absolute {
+        return url.to_owned();
+    }
+    if url.is_empty() || url.starts_with(['?', '#']) {
+        return format!("{base_url}{url}");
+    }
+
+    let (raw_path, rest) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+    let path = raw_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    let trailing_slash = if !path.is_empty() && raw_path.ends_with('/') {
+        "/"
+    } else {
+        ""
+    };
+    format!(
+        "{}/{path}{trailing_slash}{rest}",
+        base_url.trim_end_matches('/')
+    )
+}
+
+fn url_with_query(url: String, query: &impl Serialize) -> ApiResult<String> {
+    let query_string = serde_qs::to_string(query).map_err(|err| {
+        Error::QueryStringError(format!(
+            "Failed to serialize the query of url '{url}': {err}"
+        ))
+    })?;
+    Ok(if query_string.is_empty() {
+        url
+    } else if url.contains('?') {
+        format!("{url}&{query_string}")
+    } else {
+        format!("{url}?{query_string}")
+    })
+}
+
+/// Read the whole body, failing as soon as it exceeds `max_size` bytes.
+/// The chunks are already decompressed, so it's the decompressed size which is limited.
+async fn read_body(
+    mut resp: Response,
+    max_size: Option<usize>,
+    req_info: &str,
+) -> ApiResult<Vec<u8>> {
+    let Some(max_size) = max_size else {
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|err| error::request_failed(err, req_info))?;
+        return Ok(body.into());
+    };
+    let mut body = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|err| error::request_failed(err, req_info))?
+    {
+        if body.len() + chunk.len() > max_size {
+            return Err(Error::ResponseTooLarge {
+                req_info: req_info.to_owned(),
+                limit: max_size,
+            });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[derive(Clone, Debug)]
+struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    max_concurrent_requests: usize,
+    queue_timeout: Option<Duration>,
+}
+
+impl ConcurrencyLimit {
+    async fn acquire(&self, req_info: &str) -> ApiResult<OwnedSemaphorePermit> {
+        let permit = self.semaphore.clone().acquire_owned();
+        let permit = match self.queue_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, permit).await.map_err(|_| {
+                Error::ConcurrencyLimitTimeout {
+                    req_info: req_info.to_owned(),
+                }
+            })?,
+            None => permit.await,
+        };
+        // the semaphore is never closed
+        Ok(permit.expect("semaphore closed"))
+    }
+
+    fn in_flight(&self) -> usize {
+        self.max_concurrent_requests - self.semaphore.available_permits()
+    }
+}
+
 pub struct HttpClientBuilder<A: BaseApi> {
     base_url: Option<String>,
     builder: ClientBuilder,
+    max_concurrent_requests: Option<usize>,
+    queue_timeout: Option<Duration>,
+    max_limit: usize,
+    max_response_size: Option<usize>,
+    gzip: bool,
+    brotli: bool,
     _pd: PhantomData<A>,
 }
 
@@ -120,6 +335,12 @@ impl<A: BaseApi> HttpClientBuilder<A> {
         let this = HttpClientBuilder {
             base_url: None,
             builder: ClientBuilder::new(),
+            max_concurrent_requests: None,
+            queue_timeout: None,
+            max_limit: DEFAULT_MAX_LIMIT,
+            max_response_size: None,
+            gzip: true,
+            brotli: true,
             _pd: PhantomData,
         };
         this.with_reqwest_builder(|b| b.pool_max_idle_per_host(1))
@@ -138,10 +359,68 @@ impl<A: BaseApi> HttpClientBuilder<A> {
         self
     }
 
+    /// Limit the number of requests executed at the same time by the client (and all its clones),
+    /// to protect the upstream service during traffic spikes. Other requests wait for a free slot.
+    ///
+    /// `0` means no limit, same as without this call.
+    pub fn with_max_concurrent_requests(mut self, n: usize) -> Self {
+        self.max_concurrent_requests = (n > 0).then_some(n);
+        self
+    }
+
+    /// Fail requests waiting for a free slot longer than `timeout` with `Error::ConcurrencyLimitTimeout`.
+    /// Only makes sense along with `with_max_concurrent_requests`.
+    pub fn with_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = Some(timeout);
+        self
+    }
+
+    /// Max `limit` of paginated requests, 100 by default.
+    /// Requests with a greater limit fail with `Error::ValidationError` without being sent.
+    pub fn with_max_limit(mut self, max_limit: usize) -> Self {
+        self.max_limit = max_limit;
+        self
+    }
+
+    /// Max size of the response body handled by `WXRequestHandler`, unlimited by default.
+    /// Larger responses fail with `Error::ResponseTooLarge`.
+    ///
+    /// The size is counted while the body is received, after decompression,
+    /// so neither a wrong `Content-Length` nor a compression bomb gets past the limit.
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    /// Request and transparently decompress gzip responses, on by default.
+    /// Overrides the `gzip` setting of `with_reqwest_builder`.
+    pub fn with_gzip(mut self, enabled: bool) -> Self {
+        self.gzip = enabled;
+        self
+    }
+
+    /// Request and transparently decompress brotli responses, on by default.
+    /// Overrides the `brotli` setting of `with_reqwest_builder`.
+    pub fn with_brotli(mut self, enabled: bool) -> Self {
+        self.brotli = enabled;
+        self
+    }
+
     pub fn try_build(self) -> Result<HttpClient<A>, ReqError> {
+        let limit = self.max_concurrent_requests.map(|n| ConcurrencyLimit {
+            semaphore: Arc::new(Semaphore::new(n)),
+            max_concurrent_requests: n,
+            queue_timeout: self.queue_timeout,
+        });
         Ok(HttpClient {
             base_url: self.base_url,
-            client: self.builder.build()?,
+            client: self.builder.gzip(self.gzip).brotli(self.brotli).build()?,
+            default_headers: A::default_headers(),
+            limit,
+            max_limit: self.max_limit,
+            max_response_size: self.max_response_size,
+            #[cfg(any(test, feature = "test-util"))]
+            mock: None,
             _pd: PhantomData,
         })
     }
@@ -154,6 +433,8 @@ impl<A: BaseApi> HttpClientBuilder<A> {
 #[derive(PartialEq, Eq, Hash)]
 pub enum StatusCodes {
     Concrete(StatusCode),
+    /// All the statuses of a class, by the first digit (e.g. `5` for 5xx)
+    Class(u16),
     Other,
 }
 
@@ -165,6 +446,12 @@ impl From<StatusCode> for StatusCodes {
 
 type StatusHandler<T> = Box<dyn FnOnce(Response) -> BoxFuture<'static, ApiResult<T>> + Send>;
 
+/// Access to an `EtagCache` for the values of type `T`
+struct EtagHooks<T> {
+    get: Box<dyn Fn(&str) -> Option<(String, T)> + Send + Sync>,
+    insert: Box<dyn Fn(String, String, &T) + Send + Sync>,
+}
+
 /// Optional helper struct for handling requests-responses
 ///
 /// ```no_run
@@ -184,6 +471,10 @@ type StatusHandler<T> = Box<dyn FnOnce(Response) -> BoxFuture<'static, ApiResult
 ///         .await;
 /// # })
 /// ```
+///
+/// Redirects are followed by reqwest (up to 10 by default), so 3xx handlers run only
+/// if the client is built with the redirects disabled:
+/// `HttpClientBuilder::with_reqwest_builder(|b| b.redirect(reqwest::redirect::Policy::none()))`.
 pub struct WXRequestHandler<'cli, A, T>
 where
     A: BaseApi,
@@ -193,6 +484,7 @@ where
     req: RequestBuilder,
     req_info: String,
     status_handlers: HashMap<StatusCodes, StatusHandler<T>>,
+    etag_cache: Option<EtagHooks<T>>,
 }
 
 impl<'cli, A, T> WXRequestHandler<'cli, A, T>
@@ -210,6 +502,7 @@ where
             req,
             req_info: req_info.into(),
             status_handlers: HashMap::new(),
+            etag_cache: None,
         };
         this.set_default_handlers()
     }
@@ -227,18 +520,53 @@ where
         self
     }
 
+    /// Handle all the statuses of a class without a `handle_status_code` handler,
+    /// `class` is the first digit of the status (e.g. `5` for all 5xx)
+    pub fn handle_status_range<Fut>(
+        self,
+        class: u16,
+        handler: impl FnOnce(Response) -> Fut + Send + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = ApiResult<T>> + Send + 'static,
+    {
+        self.handle_status_code(StatusCodes::Class(class), handler)
+    }
+
+    /// Make a conditional request (`If-None-Match`) if a response with an `ETag` is cached for this request,
+    /// returning the cached value on `304 Not Modified`. Successful responses with an `ETag` are cached.
+    ///
+    /// Requests are cached by request info and url,
+    /// the ones which can't be cloned (e.g. with a streaming body) are executed as usual.
+    pub fn with_etag_cache(mut self, cache: Arc<EtagCache>) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let cache_ = cache.clone();
+        self.etag_cache = Some(EtagHooks {
+            get: Box::new(move |key| cache.get(key)),
+            insert: Box::new(move |key, etag, value| cache_.insert(key, etag, value.clone())),
+        });
+        self
+    }
+
+    fn etag_cache_key(&self) -> Option<String> {
+        let request = self.req.try_clone()?.build().ok()?;
+        Some(format!("{} {}", self.req_info, request.url()))
+    }
+
     fn set_default_handlers(self) -> Self {
+        let max_response_size = self.client.max_response_size;
         let req_info = self.req_info.clone();
         let req_info_ = req_info.clone();
         self.handle_status_code(
             StatusCodes::Concrete(StatusCode::OK),
             move |resp| async move {
-                let response = resp
-                    .text()
-                    .await
-                    .map_err(|err| error::request_failed(err, &req_info))?;
-                serde_json::from_str(&response)
-                    .map_err(|err| error::json_error(err.to_string(), req_info, response))
+                let status = resp.status();
+                // bytes rather than text, to report a non-UTF8 body as is
+                let response = read_body(resp, max_response_size, &req_info).await?;
+                serde_json::from_slice(&response)
+                    .map_err(|err| error::json_error(err, status, req_info, &response))
             },
         )
         .handle_status_code(StatusCodes::Other, move |resp| async move {
@@ -247,17 +575,488 @@ where
     }
 
     pub async fn execute(mut self) -> ApiResult<T> {
-        let resp = self.client.do_request(self.req, self.req_info).await?;
+        let etag_cache = self.etag_cache.take();
+        let etag_cache = etag_cache
+            .as_ref()
+            .and_then(|hooks| Some((hooks, self.etag_cache_key()?)));
+        let mut cached = None;
+        if let Some((hooks, key)) = &etag_cache {
+            if let Some((etag, value)) = (hooks.get)(key) {
+                self.req = self.req.header(IF_NONE_MATCH, etag);
+                cached = Some(value);
+            }
+        }
+
+        // the slot is held until the response is handled completely, including the body
+        let _permit = self.client.acquire_permit(&self.req_info).await?;
+        let resp = self
+            .client
+            .do_request_unlimited(self.req, self.req_info)
+            .await?;
         let status = resp.status();
-        let handler =
-            if let Some(handler) = self.status_handlers.remove(&StatusCodes::Concrete(status)) {
-                handler
-            } else if let Some(handler) = self.status_handlers.remove(&StatusCodes::Other) {
-                handler
-            } else {
-                // if invariants above are not satisfied, then something really bad happened
-                unreachable!("No appropriate handler for status {status} found");
-            };
-        handler(resp).await
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(value) = cached {
+                return Ok(value);
+            }
+        }
+        let etag = resp
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToOwned::to_owned);
+        let handler = [
+            StatusCodes::Concrete(status),
+            StatusCodes::Class(status.as_u16() / 100),
+            StatusCodes::Other,
+        ]
+        .iter()
+        .find_map(|code| self.status_handlers.remove(code))
+        // if invariants above are not satisfied, then something really bad happened
+        .unwrap_or_else(|| unreachable!("No appropriate handler for status {status} found"));
+        let res = handler(resp).await;
+        if let (Some((hooks, key)), Some(etag), Ok(value)) = (etag_cache, etag, &res) {
+            if status == StatusCode::OK {
+                (hooks.insert)(key, etag, value);
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_url() {
+        let cases = [
+            // plain joining
+            ("http://host", "path", "http://host/path"),
+            ("http://host/", "path", "http://host/path"),
+            ("http://host", "/path", "http://host/path"),
+            ("http://host/", "/path", "http://host/path"),
+            ("http://host//", "//path", "http://host/path"),
+            ("http://host/api", "v1/sign", "http://host/api/v1/sign"),
+            ("http://host/api/", "/v1/sign", "http://host/api/v1/sign"),
+            ("http://host/api", "v1//sign", "http://host/api/v1/sign"),
+            ("http://host/api", "v1/sign/", "http://host/api/v1/sign/"),
+            ("http://host/api", "v1//sign//", "http://host/api/v1/sign/"),
+            // relative url with a query
+            (
+                "http://host/api",
+                "search?x=1",
+                "http://host/api/search?x=1",
+            ),
+            (
+                "http://host/api/",
+                "/search?x=1",
+                "http://host/api/search?x=1",
+            ),
+            (
+                "http://host/api",
+                "search/?x=1",
+                "http://host/api/search/?x=1",
+            ),
+            (
+                "http://host/api",
+                "search?u=a//b",
+                "http://host/api/search?u=a//b",
+            ),
+            ("http://host/api", "search#f", "http://host/api/search#f"),
+            // only slashes
+            ("http://host/api", "/", "http://host/api/"),
+            ("http://host/api/", "//", "http://host/api/"),
+            ("http://host/api", "/?x=1", "http://host/api/?x=1"),
+            // empty or query-only relative url is attached to the base as is
+            ("http://host/api", "", "http://host/api"),
+            ("http://host/api/", "", "http://host/api/"),
+            ("http://host/api", "?x=1&y=2", "http://host/api?x=1&y=2"),
+            ("http://host/api/", "?x=1", "http://host/api/?x=1"),
+            ("http://host/api", "#f", "http://host/api#f"),
+            // absolute url overrides the base
+            ("http://host/api", "http://other/path", "http://other/path"),
+            (
+                "http://host/api",
+                "https://other/path?x=1",
+                "https://other/path?x=1",
+            ),
+            ("http://host/api", "HTTPS://other", "HTTPS://other"),
+            // not an absolute url
+            ("http://host/api", "httpx", "http://host/api/httpx"),
+            ("http://host/api", "https", "http://host/api/https"),
+        ];
+        for (base_url, url, expected) in cases {
+            assert_eq!(
+                join_url(base_url, url),
+                expected,
+                "base_url: {base_url:?}, url: {url:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_prepare_url() {
+        let client = HttpClient::<()>::from_base_url("https://host/api/");
+        let url = |req: RequestBuilder| req.build().unwrap().url().to_string();
+        assert_eq!(url(client.http_get("/entries")), "https://host/api/entries");
+        assert_eq!(url(client.http_post("?x=1")), "https://host/api/?x=1");
+
+        let client = HttpClient::<()>::new();
+        assert_eq!(
+            url(client.http_get("https://host/path")),
+            "https://host/path"
+        );
+    }
+
+    #[test]
+    fn test_url_with_query() {
+        #[derive(Serialize)]
+        struct Query {
+            limit: u32,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            after: Option<&'static str>,
+            ids: Vec<&'static str>,
+        }
+
+        let client = HttpClient::<()>::from_base_url("https://host/api");
+        let url = |req: ApiResult<RequestBuilder>| req.unwrap().build().unwrap().url().to_string();
+        let query = Query {
+            limit: 10,
+            after: Some("a b"),
+            ids: vec!["x", "y"],
+        };
+        assert_eq!(
+            url(client.http_get_with_query("assets", &query)),
+            "https://host/api/assets?limit=10&after=a+b&ids[0]=x&ids[1]=y"
+        );
+        assert_eq!(
+            url(client.http_post_with_query("assets?x=1", &query)),
+            "https://host/api/assets?x=1&limit=10&after=a+b&ids[0]=x&ids[1]=y"
+        );
+        let query = Query {
+            limit: 1,
+            after: None,
+            ids: vec![],
+        };
+        assert_eq!(
+            url(client.http_get_with_query("assets", &query)),
+            "https://host/api/assets?limit=1"
+        );
+
+        // only structs and maps can be serialized into a query string
+        let res = client.http_get_with_query("assets", &[1, 2]);
+        assert!(matches!(res, Err(Error::QueryStringError(_))));
+        let res = client.http_post_with_query("assets", &"limit=1");
+        assert!(matches!(res, Err(Error::QueryStringError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_requests() {
+        use crate::mock::{MockResponse, MockResponses, MOCK_BASE_URL};
+        use reqwest::Method;
+
+        let mocks = MockResponses::new().on(
+            Method::GET,
+            "/slow",
+            MockResponse::new(200, "{}").with_delay(Duration::from_millis(50)),
+        );
+        let mut client = HttpClient::<()>::builder()
+            .with_base_url(MOCK_BASE_URL)
+            .with_max_concurrent_requests(5)
+            .build();
+        client.mock = Some(mocks.clone());
+        assert_eq!(client.in_flight_requests(), Some(0));
+
+        let requests = (0..20)
+            .map(|i| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    client
+                        .create_req_handler::<serde_json::Value>(
+                            client.http_get("slow"),
+                            format!("slow {i}"),
+                        )
+                        .execute()
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut max_in_flight = 0;
+        while requests.iter().any(|r| !r.is_finished()) {
+            max_in_flight = max_in_flight.max(client.in_flight_requests().unwrap());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for request in requests {
+            assert!(request.await.unwrap().is_ok());
+        }
+        assert_eq!(max_in_flight, 5);
+        assert_eq!(client.in_flight_requests(), Some(0));
+        assert_eq!(mocks.received().len(), 20);
+        assert_eq!(HttpClient::<()>::new().in_flight_requests(), None);
+
+        let unlimited = HttpClient::<()>::builder()
+            .with_max_concurrent_requests(0)
+            .build();
+        assert_eq!(unlimited.in_flight_requests(), None);
+    }
+
+    #[tokio::test]
+    async fn test_queue_timeout() {
+        use crate::mock::{MockResponse, MockResponses, MOCK_BASE_URL};
+        use reqwest::Method;
+
+        let mocks = MockResponses::new().on(
+            Method::GET,
+            "/slow",
+            MockResponse::new(200, "{}").with_delay(Duration::from_millis(200)),
+        );
+        let mut client = HttpClient::<()>::builder()
+            .with_base_url(MOCK_BASE_URL)
+            .with_max_concurrent_requests(1)
+            .with_queue_timeout(Duration::from_millis(20))
+            .build();
+        client.mock = Some(mocks);
+
+        let first = client
+            .create_req_handler::<serde_json::Value>(client.http_get("slow"), "first")
+            .execute();
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            client
+                .create_req_handler::<serde_json::Value>(client.http_get("slow"), "second")
+                .execute()
+                .await
+        };
+        let (first, second) = tokio::join!(first, second);
+        assert!(first.is_ok());
+        assert!(matches!(
+            second,
+            Err(Error::ConcurrencyLimitTimeout { req_info }) if req_info == "second"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_etag_cache() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+        use serde::{Deserialize, Deserializer};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DESERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Clone, Debug, PartialEq)]
+        struct Counted(serde_json::Value);
+
+        impl<'de> Deserialize<'de> for Counted {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                DESERIALIZED.fetch_add(1, Ordering::SeqCst);
+                serde_json::Value::deserialize(deserializer).map(Counted)
+            }
+        }
+
+        let mocks = MockResponses::new()
+            .on(
+                Method::GET,
+                "/assets",
+                MockResponse::new(200, r#"{"v": 1}"#).with_header("ETag", r#""v1""#),
+            )
+            .on(Method::GET, "/assets", MockResponse::new(304, ""))
+            .on(
+                Method::GET,
+                "/no_etag",
+                MockResponse::new(200, r#"{"v": 2}"#),
+            );
+        let client = HttpClient::<()>::mocked(mocks.clone());
+        let cache = Arc::new(EtagCache::new(10));
+        let get = |url: &'static str| {
+            client
+                .create_req_handler::<Counted>(client.http_get(url), "get")
+                .with_etag_cache(cache.clone())
+                .execute()
+        };
+
+        let expected = Counted(serde_json::json!({ "v": 1 }));
+        assert_eq!(get("assets").await.unwrap(), expected);
+        assert_eq!(get("assets").await.unwrap(), expected);
+        assert_eq!(DESERIALIZED.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.len(), 1);
+
+        // responses without an etag are not cached
+        assert!(get("no_etag").await.is_ok());
+        assert!(get("no_etag").await.is_ok());
+        assert_eq!(DESERIALIZED.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 1);
+
+        let received = mocks.received();
+        assert_eq!(received[0].header("if-none-match"), None);
+        assert_eq!(received[1].header("if-none-match"), Some(r#""v1""#));
+        assert_eq!(received[3].header("if-none-match"), None);
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_ok_response() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+
+        let html = format!("<html><body>{}</body></html>", "maintenance ".repeat(200));
+        let mocks = MockResponses::new()
+            .on(Method::GET, "/html", MockResponse::new(200, html))
+            .on(
+                Method::GET,
+                "/json",
+                MockResponse::new(200, r#"{"v": "1"}"#),
+            );
+        let client = HttpClient::<()>::mocked(mocks);
+        let get = |url: &'static str| {
+            client
+                .create_req_handler::<HashMap<String, u32>>(client.http_get(url), "get")
+                .execute()
+        };
+
+        match get("html").await {
+            Err(Error::UnexpectedResponseBody(status, msg)) => {
+                assert_eq!(status, StatusCode::OK);
+                assert!(msg.contains("request 'get'"));
+                // the body is only shown with verbose errors
+                let verbose = crate::error::verbose_errors();
+                assert_eq!(msg.contains("<html><body>maintenance"), verbose);
+            }
+            res => panic!("unexpected result: {res:?}"),
+        }
+
+        // valid json of a wrong type
+        match get("json").await {
+            Err(Error::JsonError {
+                req_info,
+                status,
+                context,
+                ..
+            }) => {
+                assert_eq!((req_info.as_str(), status), ("get", StatusCode::OK));
+                assert_eq!(context.line, 1);
+                assert_eq!(context.snippet, r#"{"v": "1"}"#);
+            }
+            res => panic!("unexpected result: {res:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_range_handler() {
+        use crate::mock::{MockResponse, MockResponses};
+        use reqwest::Method;
+
+        let mocks = MockResponses::new()
+            .on(Method::GET, "/unavailable", MockResponse::new(503, ""))
+            .on(Method::GET, "/bad-gateway", MockResponse::new(502, ""))
+            .on(Method::GET, "/not-found", MockResponse::new(404, ""));
+        let client = HttpClient::<()>::mocked(mocks);
+        let get = |url: &'static str| {
+            client
+                .create_req_handler::<String>(client.http_get(url), "get")
+                .handle_status_range(5, |resp| async move {
+                    Ok(format!("server error {}", resp.status().as_u16()))
+                })
+                .handle_status_code(StatusCode::BAD_GATEWAY, |_| async {
+                    Ok("bad gateway".to_owned())
+                })
+                .execute()
+        };
+
+        assert_eq!(get("unavailable").await.unwrap(), "server error 503");
+        // a concrete status handler takes precedence
+        assert_eq!(get("bad-gateway").await.unwrap(), "bad gateway");
+        // other statuses are handled as usual
+        assert!(get("not-found").await.is_err());
+    }
+
+    /// Serve every connection with a gzip-encoded `body`, whatever the request is
+    async fn serve_gzip(body: &[u8]) -> (String, tokio::task::JoinHandle<()>) {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(body).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-encoding: gzip\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n",
+            gzipped.len()
+        )
+        .into_bytes();
+        response.extend(gzipped);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(&response).await;
+            }
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_gzip_response() {
+        let (url, server) = serve_gzip(br#"{"v": 1}"#).await;
+        let get = |client: HttpClient<()>| async move {
+            client
+                .create_req_handler::<HashMap<String, u32>>(client.http_get(""), "get")
+                .execute()
+                .await
+        };
+
+        let client = HttpClient::<()>::from_base_url(&url);
+        assert_eq!(get(client).await.unwrap()["v"], 1);
+
+        // the body is passed as is
+        let client = HttpClient::<()>::builder()
+            .with_base_url(&url)
+            .with_gzip(false)
+            .build();
+        let res = get(client).await;
+        assert!(
+            matches!(res, Err(Error::UnexpectedResponseBody(StatusCode::OK, _))),
+            "{res:?}"
+        );
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_max_response_size() {
+        let body = format!(r#"{{"v": "{}"}}"#, "a".repeat(100_000));
+        let (url, server) = serve_gzip(body.as_bytes()).await;
+        let get = |max_size: usize| {
+            let client = HttpClient::<()>::builder()
+                .with_base_url(&url)
+                .with_max_response_size(max_size)
+                .build();
+            async move {
+                client
+                    .create_req_handler::<HashMap<String, String>>(client.http_get(""), "get")
+                    .execute()
+                    .await
+            }
+        };
+
+        // the compressed body is way smaller than the limit
+        match get(10_000).await {
+            Err(Error::ResponseTooLarge { req_info, limit }) => {
+                assert_eq!((req_info.as_str(), limit), ("get", 10_000));
+            }
+            res => panic!("unexpected result: {res:?}"),
+        }
+        assert!(get(body.len() - 1).await.is_err());
+        assert_eq!(get(body.len()).await.unwrap()["v"].len(), 100_000);
+
+        server.abort();
     }
 }
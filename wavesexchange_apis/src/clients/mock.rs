@@ -0,0 +1,279 @@
+//! In-process mock transport for `HttpClient`, to unit-test API consumers without network access.
+//!
+//! ```no_run
+//! # use wavesexchange_apis::{mock::{MockResponse, MockResponses}, HttpClient, Node};
+//! # use reqwest::Method;
+//! # tokio_test::block_on(async {
+//! let mocks = MockResponses::new()
+//!     .on(Method::GET, "/blocks/height", MockResponse::new(500, "oops"))
+//!     .on(Method::GET, "/blocks/height", MockResponse::new(200, r#"{"height": 42}"#));
+//! let client = HttpClient::<Node>::mocked(mocks.clone());
+//!
+//! assert!(client.get_last_height().await.is_err());
+//! assert_eq!(client.get_last_height().await.unwrap().height, 42);
+//! assert_eq!(mocks.received().len(), 2);
+//! # })
+//! ```
+
+use crate::{ApiResult, BaseApi, HttpClient};
+use reqwest::{Method, Request, Response};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Base url of a mocked client, only the path and query of requests are matched
+pub const MOCK_BASE_URL: &str = "http://mock.local";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+    pub headers: Vec<(String, String)>,
+    pub delay: Option<Duration>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        MockResponse {
+            status,
+            body: body.into(),
+            headers: Vec::new(),
+            delay: None,
+        }
+    }
+
+    /// `200 OK` with the value serialized as the JSON body
+    pub fn json(value: &impl serde::Serialize) -> Self {
+        Self::new(
+            200,
+            serde_json::to_string(value).expect("mock response json"),
+        )
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Respond after the delay, e.g. to test timeouts
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// A request received by the mocked client
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    /// With lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn body_json<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+        serde_json::from_slice(self.body.as_deref()?).ok()
+    }
+}
+
+#[derive(Debug)]
+struct Route {
+    method: Method,
+    path_pattern: String,
+    responses: VecDeque<MockResponse>,
+}
+
+impl Route {
+    /// Exact path, or a prefix if the pattern ends with `*`
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        let path_matches = match self.path_pattern.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == self.path_pattern,
+        };
+        self.method == *method && path_matches
+    }
+}
+
+#[derive(Debug, Default)]
+struct Mocks {
+    routes: Vec<Route>,
+    received: Vec<MockRequest>,
+}
+
+/// Programmed responses of a mocked `HttpClient`, by method and path pattern.
+///
+/// Responses of the same route are returned in the order of `on` calls, the last one is repeated.
+/// A request not matching any route gets `404 Not Found`.
+///
+/// Cheaply cloneable, all the clones share the responses and the received requests.
+#[derive(Clone, Debug, Default)]
+pub struct MockResponses(Arc<Mutex<Mocks>>);
+
+impl MockResponses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a response for the method and the path pattern,
+    /// which is either an exact path (`/blocks/height`) or a prefix ending with `*` (`/assets/*`)
+    pub fn on(
+        self,
+        method: Method,
+        path_pattern: impl Into<String>,
+        response: MockResponse,
+    ) -> Self {
+        {
+            let mut mocks = self.0.lock().unwrap();
+            let path_pattern = path_pattern.into();
+            match mocks
+                .routes
+                .iter_mut()
+                .find(|r| r.method == method && r.path_pattern == path_pattern)
+            {
+                Some(route) => route.responses.push_back(response),
+                None => mocks.routes.push(Route {
+                    method,
+                    path_pattern,
+                    responses: VecDeque::from([response]),
+                }),
+            }
+        }
+        self
+    }
+
+    /// All the requests received so far, in order
+    pub fn received(&self) -> Vec<MockRequest> {
+        self.0.lock().unwrap().received.clone()
+    }
+
+    pub(crate) async fn respond(&self, request: Request) -> ApiResult<Response> {
+        let response = {
+            let mut mocks = self.0.lock().unwrap();
+            let url = request.url();
+            let (method, path) = (request.method().clone(), url.path().to_owned());
+            mocks.received.push(MockRequest {
+                method: method.clone(),
+                path: path.clone(),
+                query: url.query().map(ToOwned::to_owned),
+                headers: request
+                    .headers()
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+                    .collect(),
+                body: request
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .map(ToOwned::to_owned),
+            });
+            let route = mocks.routes.iter_mut().find(|r| r.matches(&method, &path));
+            match route {
+                Some(route) if route.responses.len() > 1 => route.responses.pop_front(),
+                Some(route) => route.responses.front().cloned(),
+                None => None,
+            }
+            .unwrap_or_else(|| {
+                MockResponse::new(404, format!("no mock response for {method} {path}"))
+            })
+        };
+
+        if let Some(delay) = response.delay {
+            tokio::time::sleep(delay).await;
+        }
+        let mut builder = http::Response::builder()
+            .status(response.status)
+            .header("content-type", "application/json");
+        for (name, value) in response.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder.body(response.body).expect("mock response");
+        Ok(Response::from(response))
+    }
+}
+
+impl<A: BaseApi> HttpClient<A> {
+    /// Create an `HttpClient` which doesn't do any network requests,
+    /// responding with the programmed `responses` instead.
+    pub fn mocked(responses: MockResponses) -> Self {
+        let mut client = Self::from_base_url(MOCK_BASE_URL);
+        client.mock = Some(responses);
+        client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mocked_client() {
+        use crate::{Error, Node};
+
+        let mocks = MockResponses::new()
+            .on(
+                Method::GET,
+                "/blocks/height",
+                MockResponse::new(500, "oops"),
+            )
+            .on(
+                Method::GET,
+                "/blocks/height",
+                MockResponse::json(&serde_json::json!({ "height": 42 })),
+            )
+            .on(
+                Method::GET,
+                "/addresses/balance/details/*",
+                MockResponse::new(503, "").with_delay(Duration::from_millis(10)),
+            );
+        let client = HttpClient::<Node>::mocked(mocks.clone());
+
+        let res = client.get_last_height().await;
+        assert!(matches!(res, Err(Error::InvalidStatus(status, _)) if status.as_u16() == 500));
+        assert_eq!(client.get_last_height().await.unwrap().height, 42);
+        // the last response is repeated
+        assert_eq!(client.get_last_height().await.unwrap().height, 42);
+
+        let res = client.addr_balance_details("3PAddress").await;
+        assert!(matches!(res, Err(Error::InvalidStatus(status, _)) if status.as_u16() == 503));
+
+        let res: ApiResult<serde_json::Value> = client
+            .create_req_handler(
+                client.http_post("unknown?x=1").body(r#"{"a":1}"#),
+                "unknown",
+            )
+            .execute()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidStatus(status, _)) if status.as_u16() == 404));
+
+        let received = mocks.received();
+        let paths = received.iter().map(|r| r.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "/blocks/height",
+                "/blocks/height",
+                "/blocks/height",
+                "/addresses/balance/details/3PAddress",
+                "/unknown"
+            ]
+        );
+        let last = received.last().unwrap();
+        assert_eq!(last.method, Method::POST);
+        assert_eq!(last.query.as_deref(), Some("x=1"));
+        assert_eq!(
+            last.body_json::<serde_json::Value>(),
+            Some(serde_json::json!({ "a": 1 }))
+        );
+    }
+}
@@ -1,2 +1,54 @@
+pub mod etag;
 pub mod grpc;
 pub mod http;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+#[cfg(feature = "otel")]
+mod otel;
+
+use crate::{HttpClient, NetworkUrls};
+
+/// Create an `HttpClient` pointed at the mainnet deployment of the service
+///
+/// ```no_run
+/// use wavesexchange_apis::{mainnet_client, Node};
+///
+/// # tokio_test::block_on(async {
+/// let height = mainnet_client::<Node>().get_last_height().await;
+/// # })
+/// ```
+pub fn mainnet_client<A: NetworkUrls>() -> HttpClient<A> {
+    HttpClient::from_base_url(A::MAINNET_URL)
+}
+
+/// Create an `HttpClient` pointed at the testnet deployment of the service
+pub fn testnet_client<A: NetworkUrls>() -> HttpClient<A> {
+    HttpClient::from_base_url(A::TESTNET_URL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_clients() {
+        use crate::{DataService, Node, StateService};
+
+        assert_eq!(
+            mainnet_client::<Node>().base_url(),
+            "https://nodes.waves.exchange"
+        );
+        assert_eq!(
+            testnet_client::<Node>().base_url(),
+            "https://nodes-testnet.wavesnodes.com"
+        );
+        assert_eq!(
+            testnet_client::<StateService>().base_url(),
+            "https://testnet.waves.exchange/api/v1/state"
+        );
+        assert_eq!(
+            mainnet_client::<DataService>().base_url(),
+            "https://waves.exchange/api/v1/forward/data_service/v0"
+        );
+    }
+}
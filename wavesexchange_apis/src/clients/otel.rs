@@ -0,0 +1,118 @@
+//! OpenTelemetry client spans of `HttpClient` requests, with the trace context propagated
+//! to the upstream service in the request headers (`traceparent` with the W3C propagator).
+//!
+//! The spans are created by the global tracer provider and the headers are injected
+//! by the global propagator, both have to be set up by the application.
+
+use crate::ApiResult;
+use opentelemetry::{
+    global,
+    propagation::Injector,
+    trace::{Span, SpanKind, Status, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Request, Response,
+};
+
+const TRACER_NAME: &str = "wavesexchange_apis";
+
+pub(super) struct RequestSpan(Context);
+
+/// Start a client span of `request` and inject its context into the request headers
+pub(super) fn start(mut request: Request, req_info: &str) -> (Request, RequestSpan) {
+    let tracer = global::tracer(TRACER_NAME);
+    let method = request.method().as_str().to_owned();
+    let span = tracer
+        .span_builder(method.clone())
+        .with_kind(SpanKind::Client)
+        .with_attributes([
+            KeyValue::new("http.request.method", method),
+            KeyValue::new("url.full", request.url().to_string()),
+            KeyValue::new("req_info", req_info.to_owned()),
+        ])
+        .start(&tracer);
+    let cx = Context::current_with_span(span);
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(request.headers_mut()))
+    });
+    (request, RequestSpan(cx))
+}
+
+impl RequestSpan {
+    pub(super) fn end(self, result: &ApiResult<Response>) {
+        let span = self.0.span();
+        match result {
+            Ok(resp) => {
+                let status = resp.status();
+                span.set_attribute(KeyValue::new(
+                    "http.response.status_code",
+                    i64::from(status.as_u16()),
+                ));
+                // 4xx are errors of the caller rather than of the client
+                if status.is_server_error() {
+                    span.set_status(Status::error(status.to_string()));
+                }
+            }
+            Err(err) => span.set_status(Status::error(err.to_string())),
+        }
+        span.end();
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        let name = HeaderName::from_bytes(key.as_bytes());
+        let value = HeaderValue::from_str(&value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_traceparent_injected() {
+        use crate::mock::{MockResponse, MockResponses};
+        use crate::HttpClient;
+        use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+        use reqwest::Method;
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        global::set_tracer_provider(TracerProvider::builder().build());
+
+        let mocks = MockResponses::new().on(Method::GET, "/status", MockResponse::new(200, "{}"));
+        let client = HttpClient::<()>::mocked(mocks.clone());
+        client
+            .do_request(client.http_get("status"), "status")
+            .await
+            .unwrap();
+        client
+            .create_req_handler::<serde_json::Value>(client.http_get("status"), "status")
+            .execute()
+            .await
+            .unwrap();
+
+        let received = mocks.received();
+        let traceparents = received
+            .iter()
+            .map(|req| req.header("traceparent").expect("traceparent"))
+            .collect::<Vec<_>>();
+        for traceparent in &traceparents {
+            // version-trace_id-span_id-flags, sampled
+            let parts = traceparent.split('-').collect::<Vec<_>>();
+            assert_eq!(parts.len(), 4, "{traceparent}");
+            assert_eq!(parts[0], "00");
+            assert_eq!((parts[1].len(), parts[2].len()), (32, 16));
+            assert_eq!(parts[3], "01");
+        }
+        // a span per request
+        assert_ne!(traceparents[0], traceparents[1]);
+    }
+}
@@ -1,5 +1,5 @@
 use reqwest::{Error as ReqError, Response};
-use std::sync::Arc;
+use std::{fmt, sync::Arc, sync::OnceLock, time::Duration};
 
 pub use reqwest;
 pub use waves_protobuf_schemas::tonic;
@@ -9,7 +9,7 @@ pub type ApiResult<T> = Result<T, Error>;
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
     #[error("HttpRequestError: {1} - {0}")]
-    HttpRequestError(Arc<reqwest::Error>, String),
+    HttpRequestError(#[source] Arc<reqwest::Error>, String),
 
     #[error("InvalidStatus: {1}, status code: {0}")]
     InvalidStatus(reqwest::StatusCode, String),
@@ -17,11 +17,48 @@ pub enum Error {
     #[error("ResponseParseError: {0}")]
     ResponseParseError(String),
 
+    /// The response body is a valid JSON which doesn't match the expected type.
+    /// The body around the failure is included in the message only with `WX_APIS_VERBOSE_ERRORS=1`.
+    #[error("JsonError: Failed to parse json on request '{req_info}' (status {status}): {source}{context}")]
+    JsonError {
+        req_info: String,
+        status: reqwest::StatusCode,
+        source: Arc<serde_json::Error>,
+        context: JsonErrorContext,
+    },
+
+    /// The response body is not JSON at all (e.g. an HTML page of a misconfigured proxy)
+    /// The body is included in the message only with `WX_APIS_VERBOSE_ERRORS=1`.
+    #[error("UnexpectedResponseBody: {1}, status code: {0}")]
+    UnexpectedResponseBody(reqwest::StatusCode, String),
+
     #[error("GrpcError: {0}")]
     GrpcError(#[from] Arc<tonic::transport::Error>),
 
     #[error("GrpcStatusError: {0}")]
     GrpcStatusError(#[from] Arc<tonic::Status>),
+
+    #[error("GrpcTimeout: request timed out after {0:?}")]
+    GrpcTimeout(Duration),
+
+    #[error("ConcurrencyLimitTimeout: request '{req_info}' waited too long for a free slot")]
+    ConcurrencyLimitTimeout { req_info: String },
+
+    #[error("ValidationError: {0}")]
+    ValidationError(String),
+
+    #[error("QueryStringError: {0}")]
+    QueryStringError(String),
+
+    #[error("TooManyPages: request '{req_info}' still had more data after {pages} pages")]
+    TooManyPages { req_info: String, pages: usize },
+
+    #[error("DeadlineExceeded: request '{req_info}' didn't complete in {timeout:?}")]
+    DeadlineExceeded { req_info: String, timeout: Duration },
+
+    /// The (decompressed) response body exceeds `HttpClientBuilder::with_max_response_size`
+    #[error("ResponseTooLarge: response to request '{req_info}' exceeds {limit} bytes")]
+    ResponseTooLarge { req_info: String, limit: usize },
 }
 
 pub async fn invalid_status(resp: Response, req_info: impl Into<String>) -> Error {
@@ -40,24 +77,218 @@ pub fn request_failed(err: ReqError, req_info: impl Into<String>) -> Error {
     Error::HttpRequestError(Arc::new(err), format!("Request '{req_info}' failed"))
 }
 
+/// `UnexpectedResponseBody` if the body is not a valid JSON,
+/// `JsonError` if it doesn't match the expected type
 pub fn json_error(
-    err: impl Into<String>,
+    err: serde_json::Error,
+    status: reqwest::StatusCode,
     req_info: impl Into<String>,
-    resp_body: impl Into<String>,
+    resp_body: &[u8],
 ) -> Error {
+    let req_info = req_info.into();
+    if err.is_data() {
+        let context = JsonErrorContext::new(resp_body, err.line(), err.column());
+        return Error::JsonError {
+            req_info,
+            status,
+            source: Arc::new(err),
+            context,
+        };
+    }
+    Error::UnexpectedResponseBody(
+        status,
+        unexpected_body_message(&req_info, &err, resp_body, verbose_errors()),
+    )
+}
+
+/// The response body may contain sensitive data,
+/// so it is included into the message only if `verbose` is set
+fn unexpected_body_message(
+    req_info: &str,
+    err: &serde_json::Error,
+    resp_body: &[u8],
+    verbose: bool,
+) -> String {
     const MAX_BODY_LEN: usize = 1000;
 
-    let req_info = req_info.into();
-    let err = err.into();
-    let body = {
-        let body = resp_body.into();
-        if body.len() > MAX_BODY_LEN {
-            format!("{} <...>", &body[..MAX_BODY_LEN])
+    let message = format!("Response to request '{req_info}' is not a valid json: {err}");
+    if !verbose {
+        return message;
+    }
+    let body = String::from_utf8_lossy(resp_body);
+    let body = match body.char_indices().nth(MAX_BODY_LEN) {
+        Some((end, _)) => format!("{} <...>", &body[..end]),
+        None => body.into_owned(),
+    };
+    format!(r#"{message}; body: "{body}""#)
+}
+
+const VERBOSE_ERRORS_ENV: &str = "WX_APIS_VERBOSE_ERRORS";
+
+pub(crate) fn verbose_errors() -> bool {
+    static VERBOSE: OnceLock<bool> = OnceLock::new();
+    *VERBOSE.get_or_init(|| std::env::var(VERBOSE_ERRORS_ENV).is_ok_and(|v| v == "1"))
+}
+
+/// Location of a JSON parse failure and the part of the response body around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonErrorContext {
+    /// 1-based line, as reported by `serde_json`
+    pub line: usize,
+    /// 1-based column (in bytes), as reported by `serde_json`
+    pub column: usize,
+    /// Byte offset of the failure in the body
+    pub offset: usize,
+    /// Byte offset of `snippet` in the body
+    pub snippet_start: usize,
+    /// At most `SNIPPET_LEN` bytes of the body around `offset`
+    pub snippet: String,
+}
+
+impl JsonErrorContext {
+    pub const SNIPPET_LEN: usize = 200;
+
+    pub fn new(body: &[u8], line: usize, column: usize) -> Self {
+        let line_start = body
+            .split_inclusive(|&b| b == b'\n')
+            .take(line.saturating_sub(1))
+            .map(<[u8]>::len)
+            .sum::<usize>();
+        let offset = (line_start + column.saturating_sub(1)).min(body.len());
+        // centered on the failure, shifted inwards at the ends of the body
+        let end =
+            (offset.saturating_sub(Self::SNIPPET_LEN / 2) + Self::SNIPPET_LEN).min(body.len());
+        let snippet_start = end.saturating_sub(Self::SNIPPET_LEN);
+        JsonErrorContext {
+            line,
+            column,
+            offset,
+            snippet_start,
+            // a char cut at the edges becomes U+FFFD
+            snippet: String::from_utf8_lossy(&body[snippet_start..end]).into_owned(),
+        }
+    }
+
+    fn snippet_message(&self, verbose: bool) -> String {
+        if verbose {
+            format!(r#"; body at {}: "{}""#, self.snippet_start, self.snippet)
         } else {
-            body
+            String::new()
         }
-    };
-    Error::ResponseParseError(format!(
-        r#"Failed to parse json on request '{req_info}': {err}; body: "{body}""#
-    ))
+    }
+}
+
+/// Empty unless `WX_APIS_VERBOSE_ERRORS=1`, not to leak the payloads into the logs
+impl fmt::Display for JsonErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.snippet_message(verbose_errors()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_error_context_window() {
+        let len = JsonErrorContext::SNIPPET_LEN;
+        let body = (0..1000)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect::<String>();
+        let window = |line, column| {
+            let ctx = JsonErrorContext::new(body.as_bytes(), line, column);
+            assert_eq!(
+                ctx.snippet,
+                body[ctx.snippet_start..ctx.snippet_start + len]
+            );
+            (ctx.offset, ctx.snippet_start)
+        };
+
+        // at the start of the body
+        assert_eq!(window(1, 1), (0, 0));
+        assert_eq!(window(1, 0), (0, 0));
+        assert_eq!(window(1, len / 2 + 1), (len / 2, 0));
+        // in the middle, centered
+        assert_eq!(window(1, len / 2 + 2), (len / 2 + 1, 1));
+        assert_eq!(window(1, 501), (500, 500 - len / 2));
+        // at the end of the body
+        assert_eq!(window(1, 1000 - len / 2 + 1), (1000 - len / 2, 1000 - len));
+        assert_eq!(window(1, 1000), (999, 1000 - len));
+        assert_eq!(window(1, 1001), (1000, 1000 - len));
+        assert_eq!(window(1, 5000), (1000, 1000 - len));
+
+        // shorter than the window
+        let ctx = JsonErrorContext::new(b"[1, 2", 1, 6);
+        assert_eq!((ctx.offset, ctx.snippet_start), (5, 0));
+        assert_eq!(ctx.snippet, "[1, 2");
+        let ctx = JsonErrorContext::new(b"", 1, 0);
+        assert_eq!((ctx.offset, ctx.snippet.as_str()), (0, ""));
+    }
+
+    #[test]
+    fn test_json_error_context_lines() {
+        let body = format!("{}\n{}\nxyz", "a".repeat(300), "b".repeat(300));
+        let ctx = JsonErrorContext::new(body.as_bytes(), 3, 2);
+        assert_eq!(ctx.offset, 602 + 1);
+        assert_eq!(&body[ctx.offset..], "yz");
+        assert_eq!(
+            ctx.snippet_start,
+            body.len() - JsonErrorContext::SNIPPET_LEN
+        );
+
+        let ctx = JsonErrorContext::new(body.as_bytes(), 2, 1);
+        assert_eq!(ctx.offset, 301);
+        assert_eq!(ctx.snippet_start, 301 - JsonErrorContext::SNIPPET_LEN / 2);
+
+        // a multibyte char cut at the edge
+        let body = "ж".repeat(300);
+        let ctx = JsonErrorContext::new(body.as_bytes(), 1, 302);
+        assert_eq!(ctx.snippet_start, 201);
+        assert!(ctx.snippet.starts_with('\u{FFFD}'));
+    }
+
+    #[test]
+    fn test_json_error() {
+        use std::error::Error as _;
+
+        let body = format!("{{\n  \"v\": \"1\"{}}}", " ".repeat(300));
+        let err =
+            serde_json::from_str::<std::collections::HashMap<String, u32>>(&body).unwrap_err();
+        let err = json_error(err, reqwest::StatusCode::OK, "get", body.as_bytes());
+        let Error::JsonError {
+            status, context, ..
+        } = &err
+        else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(*status, reqwest::StatusCode::OK);
+        assert_eq!(context.line, 2);
+        assert!(context.snippet.contains(r#""v": "1""#), "{context:?}");
+        assert_eq!(
+            context.snippet_message(true),
+            format!(r#"; body at 0: "{}""#, context.snippet)
+        );
+        assert_eq!(context.snippet_message(false), "");
+
+        // the root cause is available to the error chain
+        let source = err.source().expect("source");
+        assert!(source.to_string().contains("invalid type"), "{source}");
+        if !verbose_errors() {
+            assert!(!err.to_string().contains(r#""v""#));
+        }
+    }
+
+    #[test]
+    fn test_unexpected_body_message() {
+        let body = format!("<html>{}</html>", "x".repeat(2000));
+        let err = serde_json::from_str::<u32>(&body).unwrap_err();
+
+        let msg = unexpected_body_message("get", &err, body.as_bytes(), false);
+        assert!(msg.starts_with("Response to request 'get' is not a valid json: "));
+        assert!(!msg.contains("<html>"), "{msg}");
+
+        let msg = unexpected_body_message("get", &err, body.as_bytes(), true);
+        assert!(msg.contains(r#"; body: "<html>xxx"#), "{msg}");
+        assert!(msg.ends_with(r#" <...>""#), "{msg}");
+    }
 }
@@ -1,11 +1,18 @@
 mod clients;
 mod error;
+mod network;
 
 pub mod api_clients;
 pub mod models;
 
-pub use clients::{grpc::GrpcClient, http::HttpClient};
-pub use error::{ApiResult, Error};
+pub use clients::{
+    etag::EtagCache, grpc::GrpcClient, http::HttpClient, mainnet_client, testnet_client,
+};
+pub use error::{ApiResult, Error, JsonErrorContext};
+pub use network::{client, Network, Networks};
+
+#[cfg(any(test, feature = "test-util"))]
+pub use clients::mock;
 
 // Reexport api structs
 pub use api_clients::*;
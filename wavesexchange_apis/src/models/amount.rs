@@ -0,0 +1,58 @@
+//! Conversions between raw amounts (integers in the smallest units of an asset)
+//! and decimal values, by the number of decimals of the asset.
+
+use bigdecimal::{BigDecimal, Signed, ToPrimitive};
+
+/// `raw / 10^decimals`, exactly
+pub fn scale_amount(raw: i64, decimals: u8) -> BigDecimal {
+    BigDecimal::new(raw.into(), decimals.into())
+}
+
+/// `value * 10^decimals`, with the digits beyond `decimals` truncated (rounded towards zero)
+/// and the result saturated to the range of `i64`
+pub fn unscale_amount(value: &BigDecimal, decimals: u8) -> i64 {
+    let shift = BigDecimal::new(1.into(), -i64::from(decimals));
+    let raw = (value * shift).with_scale(0);
+    raw.to_i64().unwrap_or(if raw.is_negative() {
+        i64::MIN
+    } else {
+        i64::MAX
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_amount() {
+        use std::str::FromStr;
+
+        let dec = |s: &str| BigDecimal::from_str(s).unwrap();
+
+        // 8 decimals, like WAVES
+        assert_eq!(scale_amount(123_456_789, 8), dec("1.23456789"));
+        assert_eq!(scale_amount(1, 8), dec("0.00000001"));
+        assert_eq!(scale_amount(-150_000_000, 8), dec("-1.5"));
+        assert_eq!(unscale_amount(&dec("1.23456789"), 8), 123_456_789);
+        assert_eq!(unscale_amount(&dec("1.5"), 8), 150_000_000);
+        assert_eq!(unscale_amount(&dec("0.000000019"), 8), 1);
+        assert_eq!(unscale_amount(&dec("-0.000000019"), 8), -1);
+
+        // 0 decimals
+        assert_eq!(scale_amount(42, 0), dec("42"));
+        assert_eq!(unscale_amount(&dec("42"), 0), 42);
+        assert_eq!(unscale_amount(&dec("42.99"), 0), 42);
+
+        for raw in [0, 1, -1, 100_000_000, i64::MAX, i64::MIN] {
+            for decimals in [0, 2, 8, 18] {
+                assert_eq!(unscale_amount(&scale_amount(raw, decimals), decimals), raw);
+            }
+        }
+
+        // saturation
+        assert_eq!(unscale_amount(&dec("92233720368.54775808"), 8), i64::MAX);
+        assert_eq!(unscale_amount(&dec("-1e30"), 8), i64::MIN);
+        assert_eq!(unscale_amount(&scale_amount(i64::MAX, 0), 8), i64::MAX);
+    }
+}
@@ -0,0 +1,47 @@
+//! Asset id conventions.
+//!
+//! The canonical id of the WAVES pseudo-asset is the string `"WAVES"`,
+//! which is what all WX services expect in request paths, queries and bodies.
+//! Some sources (e.g. blockchain updates or raw node data) represent it as an empty string instead,
+//! so asset ids coming from such sources must be normalized with `normalize_waves`
+//! before being passed to the services.
+
+/// Canonical id of the WAVES pseudo-asset
+pub const WAVES_ASSET_ID: &str = "WAVES";
+
+/// Convert any representation of the WAVES pseudo-asset id into the canonical form,
+/// other asset ids are returned unchanged.
+#[inline]
+pub fn normalize_waves(asset_id: &str) -> &str {
+    if is_waves(asset_id) {
+        WAVES_ASSET_ID
+    } else {
+        asset_id
+    }
+}
+
+/// Whether the asset id denotes the WAVES pseudo-asset, in any representation
+#[inline]
+pub fn is_waves(asset_id: &str) -> bool {
+    asset_id.is_empty() || asset_id == WAVES_ASSET_ID
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_waves() {
+        assert_eq!(normalize_waves(""), WAVES_ASSET_ID);
+        assert_eq!(normalize_waves("WAVES"), WAVES_ASSET_ID);
+        assert_eq!(normalize_waves(""), normalize_waves("WAVES"));
+        assert_eq!(
+            normalize_waves("DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p"),
+            "DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p"
+        );
+
+        assert!(is_waves(""));
+        assert!(is_waves("WAVES"));
+        assert!(!is_waves("waves_token"));
+    }
+}
@@ -18,6 +18,23 @@ pub struct DataEntry {
     pub address: String,
 }
 
+/// Sort order of paginated requests, shared by the clients supporting it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    Asc,
+    Desc,
+}
+
+impl core::fmt::Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Sort::Asc => write!(f, "asc"),
+            Sort::Desc => write!(f, "desc"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, thiserror::Error)]
 #[error("Wrong type of a value: {0} (expected {1})")]
 pub struct TypeError(pub &'static str, pub &'static str);
@@ -151,33 +168,46 @@ impl TryFrom<DataEntryValue> for bool {
     }
 }
 
-#[test]
-fn test_data_entry_type_conversions() {
-    let v = DataEntryValue::String("test".to_string());
-    assert_eq!(v.try_as_str(), Ok("test"));
-    assert!(v.try_as_integer().is_err());
-    assert!(v.try_as_binary().is_err());
-    assert!(v.try_as_boolean().is_err());
-    assert_eq!(String::try_from(v), Ok("test".to_string()));
-
-    let v = DataEntryValue::Integer(42);
-    assert_eq!(v.try_as_integer(), Ok(42));
-    assert!(v.try_as_str().is_err());
-    assert!(v.try_as_binary().is_err());
-    assert!(v.try_as_boolean().is_err());
-    assert_eq!(i64::try_from(v), Ok(42));
-
-    let v = DataEntryValue::Binary(vec![0xAA, 0xBB]);
-    assert_eq!(v.try_as_binary(), Ok([0xAA_u8, 0xBB_u8].as_slice()));
-    assert!(v.try_as_str().is_err());
-    assert!(v.try_as_integer().is_err());
-    assert!(v.try_as_boolean().is_err());
-    assert_eq!(Vec::try_from(v), Ok(vec![0xAA, 0xBB]));
-
-    let v = DataEntryValue::Boolean(true);
-    assert_eq!(v.try_as_boolean(), Ok(true));
-    assert!(v.try_as_str().is_err());
-    assert!(v.try_as_integer().is_err());
-    assert!(v.try_as_binary().is_err());
-    assert_eq!(bool::try_from(v), Ok(true));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_entry_type_conversions() {
+        let v = DataEntryValue::String("test".to_string());
+        assert_eq!(v.try_as_str(), Ok("test"));
+        assert!(v.try_as_integer().is_err());
+        assert!(v.try_as_binary().is_err());
+        assert!(v.try_as_boolean().is_err());
+        assert_eq!(String::try_from(v), Ok("test".to_string()));
+
+        let v = DataEntryValue::Integer(42);
+        assert_eq!(v.try_as_integer(), Ok(42));
+        assert!(v.try_as_str().is_err());
+        assert!(v.try_as_binary().is_err());
+        assert!(v.try_as_boolean().is_err());
+        assert_eq!(i64::try_from(v), Ok(42));
+
+        let v = DataEntryValue::Binary(vec![0xAA, 0xBB]);
+        assert_eq!(v.try_as_binary(), Ok([0xAA_u8, 0xBB_u8].as_slice()));
+        assert!(v.try_as_str().is_err());
+        assert!(v.try_as_integer().is_err());
+        assert!(v.try_as_boolean().is_err());
+        assert_eq!(Vec::try_from(v), Ok(vec![0xAA, 0xBB]));
+
+        let v = DataEntryValue::Boolean(true);
+        assert_eq!(v.try_as_boolean(), Ok(true));
+        assert!(v.try_as_str().is_err());
+        assert!(v.try_as_integer().is_err());
+        assert!(v.try_as_binary().is_err());
+        assert_eq!(bool::try_from(v), Ok(true));
+    }
+
+    #[test]
+    fn test_sort_format() {
+        assert_eq!(Sort::Asc.to_string(), "asc");
+        assert_eq!(Sort::Desc.to_string(), "desc");
+        assert_eq!(serde_json::to_string(&Sort::Desc).unwrap(), r#""desc""#);
+        assert_eq!(serde_json::from_str::<Sort>(r#""asc""#).unwrap(), Sort::Asc);
+    }
 }
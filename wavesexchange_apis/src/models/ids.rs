@@ -0,0 +1,218 @@
+//! Typed asset ids and addresses, so that one can't be passed where the other is expected.
+//!
+//! Both are validated on `new` / `parse` / deserialization,
+//! while `From<String>` and `From<&str>` are unchecked to keep accepting plain strings
+//! in the methods taking `impl Into<AssetId>` / `impl Into<Address>`.
+
+use super::asset_id::{is_waves, normalize_waves, WAVES_ASSET_ID};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{fmt, str::FromStr};
+
+const ASSET_ID_BYTES: usize = 32;
+const ADDRESS_BYTES: usize = 26;
+const ADDRESS_VERSION: u8 = 1;
+
+#[derive(Clone, PartialEq, Eq, Debug, thiserror::Error)]
+#[error("Invalid {kind}: '{value}'")]
+pub struct InvalidId {
+    pub kind: &'static str,
+    pub value: String,
+}
+
+/// Asset id, with the WAVES pseudo-asset always in the canonical form (see `asset_id` module)
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize)]
+#[serde(transparent)]
+pub struct AssetId(String);
+
+impl AssetId {
+    /// Base58 of 32 bytes, or WAVES in any representation
+    pub fn new(id: impl Into<String>) -> Result<Self, InvalidId> {
+        let id = id.into();
+        if is_waves(&id) || decoded_len(&id) == Some(ASSET_ID_BYTES) {
+            Ok(Self::new_unchecked(id))
+        } else {
+            Err(InvalidId {
+                kind: "asset id",
+                value: id,
+            })
+        }
+    }
+
+    /// No validation except for the WAVES normalization
+    pub fn new_unchecked(id: impl Into<String>) -> Self {
+        let id = id.into();
+        if is_waves(&id) {
+            Self::waves()
+        } else {
+            AssetId(id)
+        }
+    }
+
+    pub fn waves() -> Self {
+        AssetId(WAVES_ASSET_ID.to_owned())
+    }
+
+    pub fn is_waves(&self) -> bool {
+        self.0 == WAVES_ASSET_ID
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// Address in base58
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize)]
+#[serde(transparent)]
+pub struct Address(String);
+
+impl Address {
+    /// Base58 of 26 bytes starting with the address version
+    pub fn new(address: impl Into<String>) -> Result<Self, InvalidId> {
+        let address = address.into();
+        let is_valid = bs58::decode(&address)
+            .into_vec()
+            .is_ok_and(|bytes| bytes.len() == ADDRESS_BYTES && bytes[0] == ADDRESS_VERSION);
+        if is_valid {
+            Ok(Address(address))
+        } else {
+            Err(InvalidId {
+                kind: "address",
+                value: address,
+            })
+        }
+    }
+
+    pub fn new_unchecked(address: impl Into<String>) -> Self {
+        Address(address.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+fn decoded_len(s: &str) -> Option<usize> {
+    // base58 of 32 bytes is at most 44 chars, don't decode anything longer
+    if s.is_empty() || s.len() > 44 {
+        return None;
+    }
+    bs58::decode(s).into_vec().ok().map(|bytes| bytes.len())
+}
+
+macro_rules! impl_string_newtype {
+    ($name:ident) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = InvalidId;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $name::new(s)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(s: String) -> Self {
+                $name::new_unchecked(s)
+            }
+        }
+
+        impl From<&String> for $name {
+            fn from(s: &String) -> Self {
+                $name::new_unchecked(s.as_str())
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(s: &str) -> Self {
+                $name::new_unchecked(s)
+            }
+        }
+
+        impl From<&$name> for $name {
+            fn from(id: &$name) -> Self {
+                id.clone()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                $name::new(s).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+impl_string_newtype!(AssetId);
+impl_string_newtype!(Address);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_id() {
+        let usdn = "DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p";
+        assert_eq!(AssetId::new(usdn).unwrap().as_str(), usdn);
+        assert_eq!(AssetId::new("WAVES").unwrap(), AssetId::waves());
+        assert_eq!(AssetId::new("").unwrap(), AssetId::waves());
+        assert!(AssetId::new("").unwrap().is_waves());
+        assert_eq!(normalize_waves(AssetId::from("").as_str()), WAVES_ASSET_ID);
+
+        // an address is not an asset id
+        let address = "3PLcn8jzKzwPVz4dHkfrB4BsBYNmt9pY5jY";
+        assert!(AssetId::new(address).is_err());
+        assert!(AssetId::new("not base58: 0OIl").is_err());
+        assert!("waves".parse::<AssetId>().is_err());
+
+        // unchecked
+        assert_eq!(AssetId::from(address).to_string(), address);
+
+        let id: AssetId = serde_json::from_str(&format!(r#""{usdn}""#)).unwrap();
+        assert_eq!(serde_json::to_string(&id).unwrap(), format!(r#""{usdn}""#));
+        assert!(serde_json::from_str::<AssetId>(&format!(r#""{address}""#)).is_err());
+    }
+
+    #[test]
+    fn test_address() {
+        let address = "3PLcn8jzKzwPVz4dHkfrB4BsBYNmt9pY5jY";
+        assert_eq!(Address::new(address).unwrap().as_ref(), address);
+        assert_eq!(address.parse::<Address>().unwrap().into_inner(), address);
+
+        // an asset id is not an address
+        let err = Address::new("DG2xFkPdDwKUoBkzGAhQtLpSGzfXLiCYPEzeKH2Ad24p").unwrap_err();
+        assert_eq!(err.kind, "address");
+        assert!(Address::new("WAVES").is_err());
+        assert!(Address::new("").is_err());
+
+        let parsed: Address = serde_json::from_str(&format!(r#""{address}""#)).unwrap();
+        assert_eq!(String::from(parsed), address);
+        assert!(serde_json::from_str::<Address>(r#""WAVES""#).is_err());
+    }
+}
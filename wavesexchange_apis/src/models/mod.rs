@@ -1,2 +1,6 @@
+pub mod amount;
+pub mod asset_id;
 mod conversions;
 pub mod dto;
+pub mod ids;
+pub mod serde_helpers;
@@ -0,0 +1,179 @@
+//! Helpers for `#[serde(with = "...")]`
+
+/// Lenient `DateTime<Utc>`, because the services are not consistent in the format of timestamps.
+///
+/// Accepted forms:
+/// * RFC3339 with a timezone (`2023-05-01T10:00:00.000Z`, `2023-05-01T13:00:00+03:00`)
+/// * naive `2023-05-01T10:00:00.000` (UTC is assumed)
+/// * integer milliseconds since the epoch (`1682935200000`)
+///
+/// Always serialized as RFC3339 in UTC.
+///
+/// ```
+/// use chrono::{DateTime, Utc};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Tx {
+///     #[serde(with = "wavesexchange_apis::models::serde_helpers::flexible_datetime")]
+///     timestamp: DateTime<Utc>,
+///     #[serde(default, with = "wavesexchange_apis::models::serde_helpers::flexible_datetime::option")]
+///     block_timestamp: Option<DateTime<Utc>>,
+/// }
+/// ```
+pub mod flexible_datetime {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use serde::{de, Deserializer, Serialize, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(
+        value: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        deserializer.deserialize_any(Visitor)
+    }
+
+    /// Same as the parent module, for `Option<DateTime<Utc>>` (`null` is `None`)
+    pub mod option {
+        use super::*;
+        use serde::Deserialize;
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            value.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrapper(#[serde(deserialize_with = "super::deserialize")] DateTime<Utc>);
+
+            let value = Option::<Wrapper>::deserialize(deserializer)?;
+            Ok(value.map(|Wrapper(dt)| dt))
+        }
+    }
+
+    struct Visitor;
+
+    impl<'de> de::Visitor<'de> for Visitor {
+        type Value = DateTime<Utc>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an RFC3339 datetime, a naive datetime in UTC or epoch milliseconds")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+            if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+                return Ok(dt.with_timezone(&Utc));
+            }
+            match s.parse::<NaiveDateTime>() {
+                Ok(dt) => Ok(dt.and_utc()),
+                Err(_) => Err(E::invalid_value(de::Unexpected::Str(s), &self)),
+            }
+        }
+
+        fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Self::Value, E> {
+            DateTime::from_timestamp_millis(millis)
+                .ok_or_else(|| E::invalid_value(de::Unexpected::Signed(millis), &self))
+        }
+
+        fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Self::Value, E> {
+            i64::try_from(millis)
+                .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(millis), &self))
+                .and_then(|millis| self.visit_i64(millis))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{DateTime, Utc};
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    struct Timestamps {
+        #[serde(with = "flexible_datetime")]
+        timestamp: DateTime<Utc>,
+        #[serde(default, with = "flexible_datetime::option")]
+        optional: Option<DateTime<Utc>>,
+    }
+
+    fn parse(value: serde_json::Value) -> Result<DateTime<Utc>, serde_json::Error> {
+        let value = serde_json::json!({ "timestamp": value });
+        serde_json::from_value::<Timestamps>(value).map(|t| t.timestamp)
+    }
+
+    #[test]
+    fn test_flexible_datetime() {
+        use chrono::TimeZone;
+        use serde_json::json;
+
+        let expected = Utc.with_ymd_and_hms(2023, 5, 1, 10, 0, 0).unwrap();
+
+        // RFC3339
+        assert_eq!(parse(json!("2023-05-01T10:00:00.000Z")).unwrap(), expected);
+        assert_eq!(parse(json!("2023-05-01T10:00:00Z")).unwrap(), expected);
+        assert_eq!(parse(json!("2023-05-01T13:00:00+03:00")).unwrap(), expected);
+        // naive
+        assert_eq!(parse(json!("2023-05-01T10:00:00.000")).unwrap(), expected);
+        assert_eq!(parse(json!("2023-05-01T10:00:00")).unwrap(), expected);
+        // epoch millis
+        assert_eq!(parse(json!(1682935200000_u64)).unwrap(), expected);
+        assert_eq!(
+            parse(json!(1682935200123_i64)).unwrap(),
+            expected + chrono::Duration::milliseconds(123)
+        );
+        assert_eq!(parse(json!(-1000)).unwrap().timestamp(), -1);
+    }
+
+    #[test]
+    fn test_flexible_datetime_garbage() {
+        use serde_json::json;
+
+        for value in [
+            json!("garbage"),
+            json!(""),
+            json!("2023-05-01"),
+            json!("2023-13-01T10:00:00Z"),
+            json!(1.5),
+            json!(true),
+            json!(u64::MAX),
+        ] {
+            assert!(parse(value.clone()).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_flexible_datetime_serialize() {
+        use serde_json::json;
+
+        let t: Timestamps = serde_json::from_value(json!({
+            "timestamp": "2023-05-01T13:00:00.5+03:00",
+            "optional": 1682935200000_u64,
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&t).unwrap(),
+            json!({
+                "timestamp": "2023-05-01T10:00:00.500Z",
+                "optional": "2023-05-01T10:00:00Z",
+            })
+        );
+
+        let t: Timestamps =
+            serde_json::from_value(json!({ "timestamp": 0, "optional": null })).unwrap();
+        assert_eq!(t.optional, None);
+        let t: Timestamps = serde_json::from_value(json!({ "timestamp": 0 })).unwrap();
+        assert_eq!(t.optional, None);
+    }
+}
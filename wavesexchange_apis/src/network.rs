@@ -0,0 +1,120 @@
+use crate::{AssetsService, BaseApi, DataService, HttpClient, NetworkUrls, Node, StateService};
+use std::{any::TypeId, collections::HashMap};
+
+/// Deployment of the services to connect to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    /// Local/dev deployment, with the base url of every used service set explicitly
+    Custom(Networks),
+}
+
+impl Network {
+    pub fn networks(&self) -> Networks {
+        match self {
+            Network::Mainnet => Networks::mainnet(),
+            Network::Testnet => Networks::testnet(),
+            Network::Custom(networks) => networks.clone(),
+        }
+    }
+}
+
+/// Base urls of the services on some network
+///
+/// ```
+/// use wavesexchange_apis::{Network, Networks, Node, StateService};
+///
+/// let network = Network::Custom(
+///     Networks::testnet().with_url::<Node>("http://localhost:6869"),
+/// );
+/// let networks = network.networks();
+/// assert_eq!(networks.url::<Node>(), Some("http://localhost:6869"));
+/// assert_eq!(networks.url::<StateService>(), Some("https://testnet.waves.exchange/api/v1/state"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Networks {
+    urls: HashMap<TypeId, String>,
+}
+
+impl Networks {
+    /// Without any urls, to be set with `with_url`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All the services with `NetworkUrls` on mainnet
+    pub fn mainnet() -> Self {
+        Self::new()
+            .with_mainnet_url::<Node>()
+            .with_mainnet_url::<StateService>()
+            .with_mainnet_url::<AssetsService>()
+            .with_mainnet_url::<DataService>()
+    }
+
+    /// All the services with `NetworkUrls` on testnet
+    pub fn testnet() -> Self {
+        Self::new()
+            .with_testnet_url::<Node>()
+            .with_testnet_url::<StateService>()
+            .with_testnet_url::<AssetsService>()
+            .with_testnet_url::<DataService>()
+    }
+
+    /// Set (or override) the base url of the service
+    pub fn with_url<A: BaseApi + 'static>(mut self, url: impl Into<String>) -> Self {
+        self.urls.insert(TypeId::of::<A>(), url.into());
+        self
+    }
+
+    pub fn url<A: BaseApi + 'static>(&self) -> Option<&str> {
+        self.urls.get(&TypeId::of::<A>()).map(String::as_str)
+    }
+
+    fn with_mainnet_url<A: NetworkUrls + 'static>(self) -> Self {
+        self.with_url::<A>(A::MAINNET_URL)
+    }
+
+    fn with_testnet_url<A: NetworkUrls + 'static>(self) -> Self {
+        self.with_url::<A>(A::TESTNET_URL)
+    }
+}
+
+/// Create an `HttpClient` of the service on the network,
+/// `None` if the base url of the service on this network is unknown
+///
+/// ```
+/// use wavesexchange_apis::{client, Network, Node};
+///
+/// let node = client::<Node>(&Network::Testnet).unwrap();
+/// ```
+pub fn client<A: BaseApi + 'static>(network: &Network) -> Option<HttpClient<A>> {
+    network.networks().url::<A>().map(HttpClient::from_base_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_client() {
+        use crate::{Identity, Matcher};
+
+        let node = client::<Node>(&Network::Testnet).unwrap();
+        assert_eq!(node.base_url(), "https://nodes-testnet.wavesnodes.com");
+        let node = client::<Node>(&Network::Mainnet).unwrap();
+        assert_eq!(node.base_url(), "https://nodes.waves.exchange");
+        assert!(client::<Identity>(&Network::Mainnet).is_none());
+
+        let custom = Network::Custom(
+            Networks::new()
+                .with_url::<Node>("http://localhost:6869")
+                .with_url::<Matcher>("http://localhost:6886"),
+        );
+        let node = client::<Node>(&custom).unwrap();
+        assert_eq!(node.base_url(), "http://localhost:6869");
+        let matcher = client::<Matcher>(&custom).unwrap();
+        assert_eq!(matcher.base_url(), "http://localhost:6886");
+        assert!(client::<StateService>(&custom).is_none());
+    }
+}
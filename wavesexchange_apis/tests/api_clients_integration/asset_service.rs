@@ -13,7 +13,7 @@ mod mainnet {
             .await
             .unwrap();
         let resp = &resp.data[0];
-        let dto::AssetInfo::Full(data) = resp.data.as_ref().unwrap() else {
+        let dto::AssetInfo::Full(data) = resp.data.found().unwrap() else {
             panic!("Wrong output format");
         };
         assert_eq!(&data.id, "WAVES");
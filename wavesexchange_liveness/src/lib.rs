@@ -13,17 +13,114 @@ extern crate diesel2 as diesel; // Diesel 2.x
 compile_error!("Either feature \"diesel1\" or \"diesel2\" must be enabled for this crate, but not both.");
 
 use diesel::{
-    sql_query, sql_types::BigInt, Connection, PgConnection, QueryableByName, RunQueryDsl,
+    result::QueryResult, sql_query, sql_types::BigInt, Connection, PgConnection, QueryableByName,
+    RunQueryDsl,
+};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use std::time::{Duration, Instant};
 use tokio::{sync::mpsc, task, time};
 use wavesexchange_warp::endpoints::Readiness;
 
 const LAST_BLOCK_TIMESTAMP_QUERY: &str = "SELECT time_stamp FROM blocks_microblocks WHERE time_stamp IS NOT NULL AND time_stamp != 0 ORDER BY uid DESC LIMIT 1";
+const LAST_BLOCK_PROGRESS_QUERY: &str = "SELECT time_stamp, uid AS progress FROM blocks_microblocks WHERE time_stamp IS NOT NULL AND time_stamp != 0 ORDER BY uid DESC LIMIT 1";
+
+/// What is considered a progress of the blockchain data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressSignal {
+    /// Increase of the last block timestamp (the `time_stamp` column)
+    #[default]
+    Timestamp,
+    /// Increase of a monotonic value, e.g. `uid`, selected by the query as the `progress` column
+    /// (along with `time_stamp`, which is only logged)
+    Progress,
+    /// Increase of either the timestamp or the `progress` column
+    TimestampOrProgress,
+}
 
 struct LastBlock {
     timestamp: i64,
+    progress: i64,
     last_change: Instant,
+    first_read: bool,
+    /// The first read block was already too old, and there was no progress since then
+    stale_on_startup: bool,
+}
+
+impl LastBlock {
+    fn new(now: Instant) -> Self {
+        LastBlock {
+            timestamp: 0,
+            progress: i64::MIN,
+            last_change: now,
+            first_read: true,
+            stale_on_startup: false,
+        }
+    }
+
+    /// To be called before the first `update`: consider the block stale right away
+    /// if its timestamp (in milliseconds) is older than `max_block_age` by the wall clock
+    fn check_startup_age(
+        &mut self,
+        timestamp: i64,
+        wall_clock: SystemTime,
+        max_block_age: Duration,
+    ) {
+        if !self.first_read {
+            return;
+        }
+        let block_time = UNIX_EPOCH + Duration::from_millis(timestamp.max(0) as u64);
+        let block_age = wall_clock.duration_since(block_time).unwrap_or_default();
+        if block_age > max_block_age {
+            log::warn!("Last block is {:?} old on startup", block_age);
+            self.stale_on_startup = true;
+        }
+    }
+
+    fn update(
+        &mut self,
+        timestamp: i64,
+        progress: Option<i64>,
+        signal: ProgressSignal,
+        now: Instant,
+        max_block_age: Duration,
+    ) -> Readiness {
+        let timestamp_advanced = timestamp > self.timestamp;
+        let progress_advanced = progress.map_or(false, |progress| progress > self.progress);
+        let advanced = match signal {
+            ProgressSignal::Timestamp => timestamp_advanced,
+            ProgressSignal::Progress => progress_advanced,
+            ProgressSignal::TimestampOrProgress => timestamp_advanced || progress_advanced,
+        };
+        if timestamp_advanced {
+            self.timestamp = timestamp;
+        }
+        if progress_advanced {
+            self.progress = progress.unwrap_or(self.progress);
+        }
+
+        let first_read = std::mem::replace(&mut self.first_read, false);
+        if advanced && !first_read {
+            self.stale_on_startup = false;
+        }
+
+        if self.stale_on_startup {
+            if advanced {
+                self.last_change = now;
+            }
+            Readiness::Dead
+        } else if advanced {
+            self.last_change = now;
+            Readiness::Ready
+        } else if now.duration_since(self.last_change) > max_block_age {
+            Readiness::Dead
+        } else {
+            Readiness::Ready
+        }
+    }
 }
 
 #[derive(QueryableByName)]
@@ -33,81 +130,677 @@ struct LastBlockTimestamp {
     time_stamp: i64,
 }
 
+#[derive(QueryableByName)]
+struct LastBlockProgress {
+    #[cfg_attr(feature = "diesel1", sql_type = "BigInt")] // for Diesel 1.x
+    #[cfg_attr(feature = "diesel2", diesel(sql_type = BigInt))] // for Diesel 2.x
+    time_stamp: i64,
+    #[cfg_attr(feature = "diesel1", sql_type = "BigInt")] // for Diesel 1.x
+    #[cfg_attr(feature = "diesel2", diesel(sql_type = BigInt))] // for Diesel 2.x
+    progress: i64,
+}
+
+type RunQuery = dyn Fn(&mut PgConnection) -> QueryResult<Option<(i64, Option<i64>)>> + Send + Sync;
+
+/// How the probe reads the last state of the data, for the data not having a `BigInt` timestamp column
+/// (see `LivenessProbeBuilder::with_staleness_query`).
+///
+/// The value read is considered both the timestamp and the progress value,
+/// so its increase is a progress with any `ProgressSignal`.
+#[derive(Clone)]
+pub struct StalenessQuery {
+    run: Arc<RunQuery>,
+    /// For debugging
+    sql: Option<String>,
+    /// The value is a timestamp in milliseconds, so it can be checked on startup
+    is_timestamp: bool,
+}
+
+impl StalenessQuery {
+    /// Read a value increasing with the progress, `None` if there is no data yet.
+    /// Not considered a timestamp by `LivenessProbeBuilder::with_stale_check_on_startup`.
+    pub fn new(
+        read: impl Fn(&mut PgConnection) -> QueryResult<Option<i64>> + Send + Sync + 'static,
+    ) -> Self {
+        StalenessQuery {
+            run: Arc::new(move |conn| read(conn).map(|value| value.map(|v| (v, Some(v))))),
+            sql: None,
+            is_timestamp: false,
+        }
+    }
+
+    /// Query selecting a `timestamptz` column named `time_stamp`, the first row is used.
+    /// The timestamp is converted to milliseconds since the epoch.
+    pub fn timestamptz(sql: impl AsRef<str>) -> Self {
+        let sql = format!(
+            "SELECT (EXTRACT(EPOCH FROM time_stamp) * 1000)::BIGINT AS time_stamp FROM ({}) AS staleness",
+            trim_sql(sql.as_ref())
+        );
+        StalenessQuery {
+            is_timestamp: true,
+            ..Self::big_int(sql)
+        }
+    }
+
+    /// Query whose number of rows grows with the progress, e.g. of the processed events.
+    pub fn count(sql: impl AsRef<str>) -> Self {
+        let sql = format!(
+            "SELECT COUNT(*)::BIGINT AS time_stamp FROM ({}) AS staleness",
+            trim_sql(sql.as_ref())
+        );
+        Self::big_int(sql)
+    }
+
+    /// Query selecting a `BigInt` column named `time_stamp`
+    fn big_int(sql: String) -> Self {
+        let query = sql.clone();
+        StalenessQuery {
+            run: Arc::new(move |conn| {
+                sql_query(&query)
+                    .load::<LastBlockTimestamp>(conn)
+                    .map(|results| {
+                        results
+                            .into_iter()
+                            .next()
+                            .map(|r| (r.time_stamp, Some(r.time_stamp)))
+                    })
+            }),
+            sql: Some(sql),
+            is_timestamp: false,
+        }
+    }
+
+    /// Query selecting the last block's `time_stamp` (and `progress`, depending on the progress signal)
+    fn last_block(sql: String, progress_signal: ProgressSignal) -> Self {
+        let query = sql.clone();
+        let run: Arc<RunQuery> = match progress_signal {
+            ProgressSignal::Timestamp => Arc::new(move |conn| {
+                sql_query(&query)
+                    .load::<LastBlockTimestamp>(conn)
+                    .map(|results| results.into_iter().next().map(|r| (r.time_stamp, None)))
+            }),
+            _ => Arc::new(move |conn| {
+                sql_query(&query)
+                    .load::<LastBlockProgress>(conn)
+                    .map(|results| {
+                        results
+                            .into_iter()
+                            .next()
+                            .map(|r| (r.time_stamp, Some(r.progress)))
+                    })
+            }),
+        };
+        StalenessQuery {
+            run,
+            sql: Some(sql),
+            is_timestamp: true,
+        }
+    }
+}
+
+fn trim_sql(sql: &str) -> &str {
+    sql.trim().trim_end_matches(';').trim_end()
+}
+
+impl fmt::Debug for StalenessQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StalenessQuery")
+            .field("sql", &self.sql)
+            .field("is_timestamp", &self.is_timestamp)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for StalenessQuery {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(&self.run), Arc::as_ptr(&other.run))
+    }
+}
+
+impl Eq for StalenessQuery {}
+
+/// Postgres `sslmode` connection parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    Allow,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Allow => "allow",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+}
+
+/// TLS options of the probe connection, overriding the ones given in the DB url (if any).
+///
+/// Works with both URL (`postgres://user@[::1]:5432/db?connect_timeout=5`)
+/// and key/value (`host=db.local dbname=blockchain`) connection strings.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub ssl_mode: Option<SslMode>,
+    /// CA bundle (`sslrootcert`) to verify the server certificate with
+    pub root_cert: Option<PathBuf>,
+    /// Client certificate (`sslcert`)
+    pub client_cert: Option<PathBuf>,
+    /// Client private key (`sslkey`)
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Require TLS, verifying the server certificate and host name with the given CA bundle
+    pub fn verify_full(root_cert: impl Into<PathBuf>) -> Self {
+        TlsConfig {
+            ssl_mode: Some(SslMode::VerifyFull),
+            root_cert: Some(root_cert.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_ssl_mode(mut self, ssl_mode: SslMode) -> Self {
+        self.ssl_mode = Some(ssl_mode);
+        self
+    }
+
+    pub fn with_client_cert(
+        mut self,
+        client_cert: impl Into<PathBuf>,
+        client_key: impl Into<PathBuf>,
+    ) -> Self {
+        self.client_cert = Some(client_cert.into());
+        self.client_key = Some(client_key.into());
+        self
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let path = |p: &PathBuf| p.to_string_lossy().into_owned();
+        let mut params = vec![];
+        if let Some(ssl_mode) = self.ssl_mode {
+            params.push(("sslmode", ssl_mode.as_str().to_owned()));
+        }
+        if let Some(root_cert) = &self.root_cert {
+            params.push(("sslrootcert", path(root_cert)));
+        }
+        if let Some(client_cert) = &self.client_cert {
+            params.push(("sslcert", path(client_cert)));
+        }
+        if let Some(client_key) = &self.client_key {
+            params.push(("sslkey", path(client_key)));
+        }
+        params
+    }
+
+    /// Connection string with the TLS options applied
+    pub fn apply(&self, db_url: &str) -> String {
+        let params = self.params();
+        if params.is_empty() {
+            return db_url.to_owned();
+        }
+
+        let is_url = db_url.starts_with("postgres://") || db_url.starts_with("postgresql://");
+        if is_url {
+            let (base, query) = db_url.split_once('?').unwrap_or((db_url, ""));
+            let query = query
+                .split('&')
+                .filter(|pair| {
+                    let key = pair.split('=').next().unwrap_or_default();
+                    !pair.is_empty() && !params.iter().any(|(k, _)| *k == key)
+                })
+                .map(str::to_owned)
+                .chain(
+                    params
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", k, url_encode(v))),
+                )
+                .collect::<Vec<_>>();
+            format!("{}?{}", base, query.join("&"))
+        } else {
+            // In key/value connection strings the last occurrence of a keyword wins
+            let params = params
+                .iter()
+                .map(|(k, v)| format!("{}='{}'", k, v.replace('\\', "\\\\").replace('\'', "\\'")))
+                .collect::<Vec<_>>();
+            format!("{} {}", db_url.trim_end(), params.join(" "))
+        }
+    }
+}
+
+fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_MAX_BLOCK_AGE: Duration = Duration::from_secs(300);
+
+/// Start a liveness probe with the given settings, see `LivenessProbe::builder` for more options.
 pub fn channel(
     db_url: String,
     poll_interval_secs: u64,
     max_block_age: Duration,
     custom_query: Option<String>,
 ) -> mpsc::UnboundedReceiver<Readiness> {
-    let (readiness_tx, readiness_rx) = mpsc::unbounded_channel();
-
-    let mut last_block = LastBlock {
-        timestamp: 0,
-        last_change: Instant::now(),
-    };
-    let query = custom_query.unwrap_or(LAST_BLOCK_TIMESTAMP_QUERY.to_string());
-
-    task::spawn(async move {
-        let mut send = {
-            let mut last_status = Readiness::Ready;
-            let mut last_time = None;
-            move |status: Readiness, timestamp: Option<i64>| {
-                if status != last_status {
-                    if let Some(timestamp) = timestamp {
-                        log::debug!("Current timestamp: {}", timestamp);
+    let probe = LivenessProbe::builder(db_url)
+        .with_poll_interval(Duration::from_secs(poll_interval_secs))
+        .with_max_block_age(max_block_age);
+    match custom_query {
+        Some(query) => probe.with_query(query),
+        None => probe,
+    }
+    .start()
+}
+
+/// Liveness probe, periodically checking the progress of the last block in the database.
+///
+/// ```no_run
+/// # use wavesexchange_liveness::{LivenessProbe, ProgressSignal};
+/// # use std::time::Duration;
+/// # let db_url = String::new();
+/// let readiness_channel = LivenessProbe::builder(db_url)
+///     .with_poll_interval(Duration::from_secs(5))
+///     .with_max_block_age(Duration::from_secs(300))
+///     .with_progress_signal(ProgressSignal::TimestampOrProgress)
+///     .start();
+/// ```
+pub struct LivenessProbe;
+
+impl LivenessProbe {
+    pub fn builder(db_url: impl Into<String>) -> LivenessProbeBuilder {
+        LivenessProbeBuilder {
+            db_url: db_url.into(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_block_age: DEFAULT_MAX_BLOCK_AGE,
+            query: None,
+            staleness_query: None,
+            tls: TlsConfig::default(),
+            progress_signal: ProgressSignal::default(),
+            check_stale_on_startup: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LivenessProbeBuilder {
+    db_url: String,
+    poll_interval: Duration,
+    max_block_age: Duration,
+    query: Option<String>,
+    staleness_query: Option<StalenessQuery>,
+    tls: TlsConfig,
+    progress_signal: ProgressSignal,
+    check_stale_on_startup: bool,
+}
+
+impl LivenessProbeBuilder {
+    /// Interval between the database queries, `DEFAULT_POLL_INTERVAL` by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Max time without progress before reporting `Dead`, `DEFAULT_MAX_BLOCK_AGE` by default.
+    pub fn with_max_block_age(mut self, max_block_age: Duration) -> Self {
+        self.max_block_age = max_block_age;
+        self
+    }
+
+    /// Query selecting the last block's `time_stamp` (and `progress`, depending on the progress signal).
+    pub fn with_query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Read the state of the data with `query` rather than the last block query,
+    /// e.g. `StalenessQuery::timestamptz` or `StalenessQuery::count`. Overrides `with_query`.
+    pub fn with_staleness_query(mut self, query: StalenessQuery) -> Self {
+        self.staleness_query = Some(query);
+        self
+    }
+
+    /// TLS options of the database connection.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// What is considered a progress, `ProgressSignal::Timestamp` by default.
+    pub fn with_progress_signal(mut self, progress_signal: ProgressSignal) -> Self {
+        self.progress_signal = progress_signal;
+        self
+    }
+
+    /// Report `Dead` right away if the first read block is already older than the max block age
+    /// by the wall clock (the block timestamp is expected in milliseconds), until the next progress.
+    /// Otherwise the first read is always considered a progress.
+    ///
+    /// Skipped for the staleness queries not reading a timestamp (`StalenessQuery::new` and `count`).
+    pub fn with_stale_check_on_startup(mut self) -> Self {
+        self.check_stale_on_startup = true;
+        self
+    }
+
+    fn query(&self) -> String {
+        match (&self.query, self.progress_signal) {
+            (Some(query), _) => query.clone(),
+            (None, ProgressSignal::Timestamp) => LAST_BLOCK_TIMESTAMP_QUERY.to_string(),
+            (None, _) => LAST_BLOCK_PROGRESS_QUERY.to_string(),
+        }
+    }
+
+    fn staleness_query(&self) -> StalenessQuery {
+        match &self.staleness_query {
+            Some(query) => query.clone(),
+            None => StalenessQuery::last_block(self.query(), self.progress_signal),
+        }
+    }
+
+    /// Start polling the database on a separate Tokio task, reporting the readiness to the returned channel.
+    pub fn start(self) -> mpsc::UnboundedReceiver<Readiness> {
+        let (readiness_tx, readiness_rx) = mpsc::unbounded_channel();
+        let query = self.staleness_query();
+        let check_stale_on_startup = self.check_stale_on_startup && query.is_timestamp;
+        let LivenessProbeBuilder {
+            db_url,
+            poll_interval,
+            max_block_age,
+            tls,
+            progress_signal,
+            ..
+        } = self;
+        let db_url = tls.apply(&db_url);
+
+        let mut last_block = LastBlock::new(Instant::now());
+
+        task::spawn(async move {
+            let mut send = {
+                let mut last_status = Readiness::Ready;
+                let mut last_time = None;
+                move |status: Readiness, timestamp: Option<i64>| {
+                    if status != last_status {
+                        if let Some(timestamp) = timestamp {
+                            log::debug!("Current timestamp: {}", timestamp);
+                        }
+                        #[rustfmt::skip]
+                        log::debug!("Sending status: {:?} (prev status was {:?} at time {:?})", status, last_status, last_time);
                     }
-                    #[rustfmt::skip]
-                    log::debug!("Sending status: {:?} (prev status was {:?} at time {:?})", status, last_status, last_time);
-                }
-                if readiness_tx.send(status).is_err() {
-                    log::error!("Failed to send {:?} status", status);
+                    if readiness_tx.send(status).is_err() {
+                        log::error!("Failed to send {:?} status", status);
+                    }
+                    last_status = status;
+                    last_time = timestamp;
                 }
-                last_status = status;
-                last_time = timestamp;
-            }
-        };
+            };
 
-        loop {
-            time::sleep(Duration::from_secs(poll_interval_secs)).await;
-
-            match PgConnection::establish(&db_url) {
-                Ok(mut conn) => {
-                    let query_result = sql_query(&query)
-                        .load::<LastBlockTimestamp>(&mut conn)
-                        .map(|results| results.into_iter().next().map(|result| result.time_stamp));
-
-                    match query_result {
-                        Ok(last_block_timestamp) => {
-                            if let Some(timestamp) = last_block_timestamp {
-                                let now = Instant::now();
-                                if timestamp > last_block.timestamp {
-                                    last_block.timestamp = timestamp;
-                                    last_block.last_change = now;
-                                    send(Readiness::Ready, last_block_timestamp);
-                                } else {
-                                    if now.duration_since(last_block.last_change) > max_block_age {
-                                        send(Readiness::Dead, last_block_timestamp);
-                                    } else {
-                                        send(Readiness::Ready, last_block_timestamp);
+            loop {
+                time::sleep(poll_interval).await;
+
+                match PgConnection::establish(&db_url) {
+                    Ok(mut conn) => {
+                        let query_result = (query.run)(&mut conn);
+
+                        match query_result {
+                            Ok(last_block_data) => {
+                                let last_block_timestamp =
+                                    last_block_data.map(|(timestamp, _)| timestamp);
+                                if let Some((timestamp, progress)) = last_block_data {
+                                    if check_stale_on_startup {
+                                        last_block.check_startup_age(
+                                            timestamp,
+                                            SystemTime::now(),
+                                            max_block_age,
+                                        );
                                     }
+                                    let status = last_block.update(
+                                        timestamp,
+                                        progress,
+                                        progress_signal,
+                                        Instant::now(),
+                                        max_block_age,
+                                    );
+                                    send(status, last_block_timestamp);
+                                } else {
+                                    log::error!("Could not get last block timestamp");
+                                    send(Readiness::Ready, last_block_timestamp);
                                 }
-                            } else {
-                                log::error!("Could not get last block timestamp");
-                                send(Readiness::Ready, last_block_timestamp);
                             }
-                        }
-                        Err(err) => {
-                            log::error!("Error while fetching last block timestamp: {}", err);
-                            send(Readiness::Dead, None);
+                            Err(err) => {
+                                log::error!("Error while fetching last block timestamp: {}", err);
+                                send(Readiness::Dead, None);
+                            }
                         }
                     }
-                }
-                Err(err) => {
-                    log::error!("Error establishing database connection: {}", err);
+                    Err(err) => {
+                        log::error!("Error establishing database connection: {}", err);
+                    }
                 }
             }
+        });
+
+        readiness_rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_progress_signal() {
+        let max_block_age = Duration::from_secs(60);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Timestamp stalls while uid advances
+        let polls = [(0, 1000, 1), (30, 1000, 2), (90, 1000, 3), (180, 1000, 3)];
+
+        let mut last_block = LastBlock::new(start);
+        let statuses = polls
+            .iter()
+            .map(|&(t, timestamp, uid)| {
+                last_block.update(
+                    timestamp,
+                    Some(uid),
+                    ProgressSignal::Timestamp,
+                    at(t),
+                    max_block_age,
+                )
+            })
+            .collect::<Vec<_>>();
+        use Readiness::{Dead, Ready};
+        assert_eq!(statuses, [Ready, Ready, Dead, Dead]);
+
+        for signal in [
+            ProgressSignal::Progress,
+            ProgressSignal::TimestampOrProgress,
+        ] {
+            let mut last_block = LastBlock::new(start);
+            let statuses = polls
+                .iter()
+                .map(|&(t, timestamp, uid)| {
+                    last_block.update(timestamp, Some(uid), signal, at(t), max_block_age)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(statuses, [Ready, Ready, Ready, Dead], "{signal:?}");
         }
-    });
 
-    readiness_rx
+        // Timestamp advances while uid stalls
+        let mut last_block = LastBlock::new(start);
+        last_block.update(
+            1000,
+            Some(1),
+            ProgressSignal::Progress,
+            at(0),
+            max_block_age,
+        );
+        let status = last_block.update(
+            2000,
+            Some(1),
+            ProgressSignal::Progress,
+            at(90),
+            max_block_age,
+        );
+        assert_eq!(status, Dead);
+
+        let mut last_block = LastBlock::new(start);
+        let signal = ProgressSignal::TimestampOrProgress;
+        last_block.update(1000, Some(1), signal, at(0), max_block_age);
+        let status = last_block.update(2000, Some(1), signal, at(90), max_block_age);
+        assert_eq!(status, Ready);
+    }
+
+    #[test]
+    fn check_stale_on_startup() {
+        use Readiness::{Dead, Ready};
+
+        let max_block_age = Duration::from_secs(60);
+        let start = Instant::now();
+        let wall_clock = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ms = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let signal = ProgressSignal::Timestamp;
+
+        // the first read is 2 minutes old
+        let old_timestamp = ms(wall_clock - Duration::from_secs(120));
+        let mut last_block = LastBlock::new(start);
+        last_block.check_startup_age(old_timestamp, wall_clock, max_block_age);
+        let status = last_block.update(old_timestamp, None, signal, start, max_block_age);
+        assert_eq!(status, Dead);
+        // still no progress
+        let at = start + Duration::from_secs(5);
+        let status = last_block.update(old_timestamp, None, signal, at, max_block_age);
+        assert_eq!(status, Dead);
+        // a new block
+        let status = last_block.update(old_timestamp + 1, None, signal, at, max_block_age);
+        assert_eq!(status, Ready);
+
+        // without the startup check the first read is always a progress
+        let mut last_block = LastBlock::new(start);
+        let status = last_block.update(old_timestamp, None, signal, start, max_block_age);
+        assert_eq!(status, Ready);
+
+        // a fresh block on startup
+        let fresh_timestamp = ms(wall_clock - Duration::from_secs(10));
+        let mut last_block = LastBlock::new(start);
+        last_block.check_startup_age(fresh_timestamp, wall_clock, max_block_age);
+        let status = last_block.update(fresh_timestamp, None, signal, start, max_block_age);
+        assert_eq!(status, Ready);
+    }
+
+    #[test]
+    fn check_builder() {
+        let builder = LivenessProbe::builder("postgres://localhost/db");
+        assert_eq!(builder.poll_interval, DEFAULT_POLL_INTERVAL);
+        assert_eq!(builder.max_block_age, DEFAULT_MAX_BLOCK_AGE);
+        assert_eq!(builder.progress_signal, ProgressSignal::Timestamp);
+        assert!(!builder.check_stale_on_startup);
+        assert_eq!(builder.query(), LAST_BLOCK_TIMESTAMP_QUERY);
+
+        let tls = TlsConfig::default().with_ssl_mode(SslMode::Require);
+        let builder = builder
+            .with_poll_interval(Duration::from_secs(1))
+            .with_max_block_age(Duration::from_secs(30))
+            .with_tls(tls.clone())
+            .with_progress_signal(ProgressSignal::Progress)
+            .with_stale_check_on_startup();
+        assert_eq!(
+            builder,
+            LivenessProbeBuilder {
+                db_url: "postgres://localhost/db".to_string(),
+                poll_interval: Duration::from_secs(1),
+                max_block_age: Duration::from_secs(30),
+                query: None,
+                staleness_query: None,
+                tls,
+                progress_signal: ProgressSignal::Progress,
+                check_stale_on_startup: true,
+            }
+        );
+        assert_eq!(builder.query(), LAST_BLOCK_PROGRESS_QUERY);
+
+        let builder = builder.with_query("SELECT 1 AS time_stamp, 1 AS progress");
+        assert_eq!(builder.query(), "SELECT 1 AS time_stamp, 1 AS progress");
+    }
+
+    #[test]
+    fn check_staleness_query() {
+        let query = StalenessQuery::timestamptz(
+            "SELECT updated_at AS time_stamp FROM events ORDER BY id DESC LIMIT 1;\n",
+        );
+        assert_eq!(
+            query.sql.as_deref(),
+            Some("SELECT (EXTRACT(EPOCH FROM time_stamp) * 1000)::BIGINT AS time_stamp FROM (SELECT updated_at AS time_stamp FROM events ORDER BY id DESC LIMIT 1) AS staleness")
+        );
+        assert!(query.is_timestamp);
+
+        let query = StalenessQuery::count("SELECT id FROM processed_events");
+        assert_eq!(
+            query.sql.as_deref(),
+            Some("SELECT COUNT(*)::BIGINT AS time_stamp FROM (SELECT id FROM processed_events) AS staleness")
+        );
+        assert!(!query.is_timestamp);
+
+        let query = StalenessQuery::new(|_conn| Ok(Some(42)));
+        assert_eq!(query.sql, None);
+        assert!(!query.is_timestamp);
+
+        // the last block query by default
+        let builder = LivenessProbe::builder("postgres://localhost/db");
+        let default_query = builder.staleness_query();
+        assert_eq!(
+            default_query.sql.as_deref(),
+            Some(LAST_BLOCK_TIMESTAMP_QUERY)
+        );
+        assert!(default_query.is_timestamp);
+        let builder = builder.with_staleness_query(query.clone());
+        assert_eq!(builder.staleness_query(), query);
+        assert_ne!(builder.staleness_query(), default_query);
+    }
+
+    #[test]
+    fn check_tls_config() {
+        let tls = TlsConfig::verify_full("/etc/ssl/certs/rds ca.pem")
+            .with_client_cert("/certs/client.crt", "/certs/client.key");
+
+        assert_eq!(
+            tls.apply("postgres://user:pass@[::1]:5432/db"),
+            "postgres://user:pass@[::1]:5432/db?sslmode=verify-full\
+             &sslrootcert=/etc/ssl/certs/rds%20ca.pem\
+             &sslcert=/certs/client.crt&sslkey=/certs/client.key"
+        );
+
+        let tls = TlsConfig::default().with_ssl_mode(SslMode::Require);
+        assert_eq!(
+            tls.apply("postgresql://db.local?sslmode=disable&connect_timeout=5"),
+            "postgresql://db.local?connect_timeout=5&sslmode=require"
+        );
+        assert_eq!(
+            tls.apply("host=db.local dbname=blockchain sslmode=disable"),
+            "host=db.local dbname=blockchain sslmode=disable sslmode='require'"
+        );
+
+        let tls = TlsConfig::verify_full("/certs/it's ca.pem");
+        assert_eq!(
+            tls.apply("host=::1"),
+            "host=::1 sslmode='verify-full' sslrootcert='/certs/it\\'s ca.pem'"
+        );
+
+        let url = "postgres://[::1]/db?sslmode=prefer";
+        assert_eq!(TlsConfig::default().apply(url), url);
+    }
 }
@@ -2,9 +2,11 @@ use anymap::{any::Any, Map};
 use cached::async_sync::Mutex;
 use dataloader::cached::Cache as DlCache;
 use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 static CACHES: Lazy<Mutex<Map<dyn Any + Send + Sync>>> = Lazy::new(|| Mutex::new(Map::new()));
 
@@ -24,6 +26,56 @@ pub struct Cacher<K: CacheKey, V: CacheVal, C: CacheBounds<K, V>> {
     cache: C,
     cache_strategy: Box<dyn Fn(&K, &V) -> bool + Send + 'static>,
     keys_to_drop: Vec<K>,
+    /// Copies of the loaded values to be served if the loader fails
+    /// (see `CachedLoader::serve_expired_on_error`)
+    stale: Option<TimedValues<K, V>>,
+    negative: Option<NegativeValues<K, V>>,
+}
+
+/// Values retained independently of the main cache for a bounded period
+struct TimedValues<K, V> {
+    ttl: Duration,
+    values: HashMap<K, (V, Instant)>,
+    last_purge: Instant,
+}
+
+/// Negative values (see `CachedLoader::is_negative`), cached with their own ttl
+/// instead of the main cache
+struct NegativeValues<K, V> {
+    values: TimedValues<K, V>,
+    is_negative: Box<dyn Fn(&V) -> bool + Send + 'static>,
+}
+
+impl<K: CacheKey, V: CacheVal> TimedValues<K, V> {
+    fn new(ttl: Duration) -> Self {
+        TimedValues {
+            ttl,
+            values: HashMap::new(),
+            last_purge: Instant::now(),
+        }
+    }
+
+    fn insert(&mut self, key: K, val: V) {
+        // Purge outdated values from time to time to keep the memory usage bounded
+        if self.last_purge.elapsed() > self.ttl {
+            let ttl = self.ttl;
+            self.values
+                .retain(|_, (_, loaded_at)| loaded_at.elapsed() <= ttl);
+            self.last_purge = Instant::now();
+        }
+        self.values.insert(key, (val, Instant::now()));
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        match self.values.get(key) {
+            Some((val, loaded_at)) if loaded_at.elapsed() <= self.ttl => Some(val),
+            _ => None,
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.values.remove(key);
+    }
 }
 
 impl<K: CacheKey, V: CacheVal, C: CacheBounds<K, V>> DlCache for &mut Cacher<K, V, C> {
@@ -31,12 +83,29 @@ impl<K: CacheKey, V: CacheVal, C: CacheBounds<K, V>> DlCache for &mut Cacher<K,
     type Val = V;
 
     fn get(&mut self, key: &Self::Key) -> Option<&Self::Val> {
-        self.cache.cache_get(key)
+        match self.negative.as_ref().and_then(|neg| neg.values.get(key)) {
+            Some(val) => Some(val),
+            None => self.cache.cache_get(key),
+        }
     }
 
     fn insert(&mut self, key: Self::Key, val: Self::Val) {
+        // The value is put into the main cache anyway to be returned by the current load,
+        // uncached ones are dropped from it afterwards (see `cleanup`)
+        match &mut self.negative {
+            Some(neg) if (neg.is_negative)(&val) => {
+                neg.values.insert(key.clone(), val.clone());
+                self.add_key_to_drop(&key);
+                self.cache.cache_set(key, val);
+                return;
+            }
+            Some(neg) => neg.values.remove(&key),
+            None => {}
+        }
         if !(self.cache_strategy)(&key, &val) {
             self.add_key_to_drop(&key)
+        } else if let Some(stale) = &mut self.stale {
+            stale.insert(key.clone(), val.clone());
         }
         self.cache.cache_set(key, val);
     }
@@ -51,17 +120,31 @@ impl<K: CacheKey, V: CacheVal, C: CacheBounds<K, V>> DlCache for &mut Cacher<K,
 }
 
 impl<K: CacheKey, V: CacheVal, C: CacheBounds<K, V>> Cacher<K, V, C> {
-    fn new(cache: C, strategy_fn: impl Fn(&K, &V) -> bool + SharedObj) -> Cacher<K, V, C> {
+    fn new(
+        cache: C,
+        strategy_fn: impl Fn(&K, &V) -> bool + SharedObj,
+        stale_ttl: Option<Duration>,
+        is_negative_fn: impl Fn(&V) -> bool + SharedObj,
+        negative_ttl: Option<Duration>,
+    ) -> Cacher<K, V, C> {
         Cacher {
             cache,
             cache_strategy: Box::new(strategy_fn),
             keys_to_drop: Vec::new(),
+            stale: stale_ttl.map(TimedValues::new),
+            negative: negative_ttl.map(|ttl| NegativeValues {
+                values: TimedValues::new(ttl),
+                is_negative: Box::new(is_negative_fn),
+            }),
         }
     }
 
     pub async fn get_or_init(
         inner_cache_fn: impl FnOnce() -> C,
         strategy_fn: impl Fn(&K, &V) -> bool + SharedObj,
+        stale_ttl: Option<Duration>,
+        is_negative_fn: impl Fn(&V) -> bool + SharedObj,
+        negative_ttl: Option<Duration>,
     ) -> Arc<Mutex<Cacher<K, V, C>>> {
         let mut caches = CACHES.lock().await;
         let entry = caches
@@ -69,10 +152,18 @@ impl<K: CacheKey, V: CacheVal, C: CacheBounds<K, V>> Cacher<K, V, C> {
             .or_insert(Arc::new(Mutex::new(Self::new(
                 inner_cache_fn(),
                 strategy_fn,
+                stale_ttl,
+                is_negative_fn,
+                negative_ttl,
             ))));
         entry.clone()
     }
 
+    /// Last loaded value of the key, if it was loaded not longer than the stale ttl ago
+    pub fn get_stale(&self, key: &K) -> Option<V> {
+        self.stale.as_ref()?.get(key).cloned()
+    }
+
     pub fn add_key_to_drop(&mut self, key: &K) {
         self.keys_to_drop.push(key.clone())
     }
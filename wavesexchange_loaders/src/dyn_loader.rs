@@ -0,0 +1,91 @@
+use crate::cacher::ErrBounds;
+use crate::error::LoaderError;
+use crate::loaders::Loader;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Object-safe counterpart of the `Loader` trait.
+///
+/// Allows to store loaders behind a trait object (see `BoxedLoader`)
+/// instead of threading them through generic type parameters.
+pub trait DynLoader<K, V, E: ErrBounds> {
+    fn load(&self, key: K) -> BoxFuture<'_, Result<V, LoaderError<E>>>;
+
+    fn load_many(&self, keys: Vec<K>) -> BoxFuture<'_, Result<HashMap<K, V>, LoaderError<E>>>;
+}
+
+/// A type-erased shared loader
+pub type BoxedLoader<K, V, E> = Arc<dyn DynLoader<K, V, E> + Send + Sync>;
+
+/// Adapter that makes any `CachedLoader` or `NonCachedLoader` usable as a `DynLoader`.
+///
+/// `DynLoader` can't be implemented for loaders directly, because the same type
+/// is allowed to implement both `CachedLoader` and `NonCachedLoader`,
+/// so the adapter explicitly selects which of the two `Loader` impls to use.
+///
+/// ```
+/// # use async_trait::async_trait;
+/// use wavesexchange_loaders::{BoxedLoader, LoaderAdapter, NonCachedLoader};
+///
+/// #[derive(Clone)]
+/// struct MyLoader;
+///
+/// #[async_trait]
+/// impl NonCachedLoader<i32, String> for MyLoader {
+///     type Error = ();
+///
+///     async fn load_fn(&mut self, keys: &[i32]) -> Result<Vec<String>, Self::Error> {
+///         Ok(keys.iter().map(|k| k.to_string()).collect())
+///     }
+/// }
+///
+/// # tokio_test::block_on(async {
+/// let loader: BoxedLoader<i32, String, ()> = LoaderAdapter::non_cached(MyLoader).into_boxed();
+/// assert_eq!(loader.load(42).await, Ok("42".to_string()));
+/// # })
+/// ```
+pub struct LoaderAdapter<L, const HAS_CACHE: bool>(L);
+
+impl<L> LoaderAdapter<L, true> {
+    pub fn cached(loader: L) -> Self {
+        LoaderAdapter(loader)
+    }
+}
+
+impl<L> LoaderAdapter<L, false> {
+    pub fn non_cached(loader: L) -> Self {
+        LoaderAdapter(loader)
+    }
+}
+
+impl<L, const HAS_CACHE: bool> LoaderAdapter<L, HAS_CACHE> {
+    pub fn into_boxed<K, V, E>(self) -> BoxedLoader<K, V, E>
+    where
+        E: ErrBounds,
+        Self: DynLoader<K, V, E> + Send + Sync + 'static,
+    {
+        Arc::new(self)
+    }
+
+    pub fn into_inner(self) -> L {
+        self.0
+    }
+}
+
+impl<K, V, E, L, const HAS_CACHE: bool> DynLoader<K, V, E> for LoaderAdapter<L, HAS_CACHE>
+where
+    E: ErrBounds,
+    L: Loader<K, V, E, HAS_CACHE> + Send + Sync + 'static,
+{
+    fn load(&self, key: K) -> BoxFuture<'_, Result<V, LoaderError<E>>> {
+        <L as Loader<K, V, E, HAS_CACHE>>::load(&self.0, key)
+    }
+
+    fn load_many(&self, keys: Vec<K>) -> BoxFuture<'_, Result<HashMap<K, V>, LoaderError<E>>> {
+        <L as Loader<K, V, E, HAS_CACHE>>::load_many(&self.0, keys)
+    }
+}
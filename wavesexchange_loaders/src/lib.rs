@@ -45,10 +45,12 @@ assert_eq!(result.ok(), Some("answer: 42".to_string()));
 */
 
 mod cacher;
+mod dyn_loader;
 mod error;
 mod loaders;
 
 pub use cached::{SizedCache, TimedCache, TimedSizedCache, UnboundCache};
+pub use dyn_loader::{BoxFuture, BoxedLoader, DynLoader, LoaderAdapter};
 pub use error::LoaderError;
 pub use loaders::{CachedLoader, InnerCachedLoader, InnerLoader, Loader, NonCachedLoader};
 
@@ -351,6 +353,287 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_serve_expired_on_error() {
+        use super::{CachedLoader, Loader, TimedCache};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static UPSTREAM_FAILS: AtomicBool = AtomicBool::new(false);
+
+        #[derive(Clone)]
+        struct Loadable;
+
+        #[async_trait]
+        impl CachedLoader<u16, String> for Loadable {
+            type Cache = TimedCache<u16, String>;
+            type Error = String;
+
+            async fn load_fn(&mut self, keys: &[u16]) -> Result<Vec<String>, Self::Error> {
+                if UPSTREAM_FAILS.load(Ordering::SeqCst) {
+                    return Err("upstream is down".to_string());
+                }
+                Ok(keys.into_iter().map(|k| format!("num: {}", k)).collect())
+            }
+
+            fn init_cache() -> Self::Cache {
+                TimedCache::with_lifespan(1)
+            }
+
+            fn serve_expired_on_error() -> bool {
+                true
+            }
+
+            fn error_grace_ttl() -> Duration {
+                Duration::from_secs(3)
+            }
+        }
+
+        let loader = Loadable {};
+        assert_eq!(loader.load(1).await, Ok("num: 1".to_string()));
+        assert_eq!(loader.load(2).await, Ok("num: 2".to_string()));
+
+        // cached values expire, and upstream starts failing
+        sleep(Duration::from_millis(1500)).await;
+        UPSTREAM_FAILS.store(true, Ordering::SeqCst);
+
+        // expired values are served within the grace period
+        assert_eq!(loader.load(1).await, Ok("num: 1".to_string()));
+        let many = loader.load_many(vec![1, 2]).await.unwrap();
+        assert_eq!(many[&1], "num: 1");
+        assert_eq!(many[&2], "num: 2");
+
+        // no fallback for values that were never loaded
+        let err = Err(LoaderError::Other("upstream is down".to_string()));
+        assert_eq!(loader.load(3).await, err);
+        assert!(matches!(
+            loader.load_many(vec![1, 3]).await,
+            Err(LoaderError::Other(_))
+        ));
+
+        // the grace period is over
+        sleep(Duration::from_secs(2)).await;
+        assert_eq!(loader.load(1).await, err);
+
+        // upstream is back
+        UPSTREAM_FAILS.store(false, Ordering::SeqCst);
+        assert_eq!(loader.load(1).await, Ok("num: 1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_negative_caching() {
+        use super::{CachedLoader, Loader, TimedCache};
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        static LOAD_FN_CALLS: AtomicUsize = AtomicUsize::new(0);
+        static FOUND: AtomicBool = AtomicBool::new(false);
+
+        #[derive(Clone)]
+        struct Loadable;
+
+        #[async_trait]
+        impl CachedLoader<u32, Option<String>> for Loadable {
+            type Cache = TimedCache<u32, Option<String>>;
+            type Error = ();
+
+            async fn load_fn(&mut self, keys: &[u32]) -> Result<Vec<Option<String>>, Self::Error> {
+                LOAD_FN_CALLS.fetch_add(1, Ordering::SeqCst);
+                let found = FOUND.load(Ordering::SeqCst);
+                Ok(keys
+                    .into_iter()
+                    .map(|k| found.then(|| format!("num: {}", k)))
+                    .collect())
+            }
+
+            fn init_cache() -> Self::Cache {
+                TimedCache::with_lifespan(60)
+            }
+
+            fn cache_strategy(_: &u32, v: &Option<String>) -> bool {
+                v.is_some()
+            }
+
+            fn is_negative(v: &Option<String>) -> bool {
+                v.is_none()
+            }
+
+            fn negative_ttl() -> Option<Duration> {
+                Some(Duration::from_millis(300))
+            }
+        }
+
+        let calls = || LOAD_FN_CALLS.load(Ordering::SeqCst);
+        let loader = Loadable {};
+        assert_eq!(loader.load(1).await, Ok(None));
+        assert_eq!(calls(), 1);
+
+        // served from cache within the negative ttl
+        assert_eq!(loader.load(1).await, Ok(None));
+        assert_eq!(loader.load_many(vec![1]).await.unwrap()[&1], None);
+        assert_eq!(calls(), 1);
+
+        // re-fetched after it
+        sleep(Duration::from_millis(400)).await;
+        assert_eq!(loader.load(1).await, Ok(None));
+        assert_eq!(calls(), 2);
+
+        // a positive value replaces the negative one and is cached with the cache lifespan
+        FOUND.store(true, Ordering::SeqCst);
+        sleep(Duration::from_millis(400)).await;
+        assert_eq!(loader.load(1).await, Ok(Some("num: 1".to_string())));
+        assert_eq!(calls(), 3);
+        sleep(Duration::from_millis(400)).await;
+        assert_eq!(loader.load(1).await, Ok(Some("num: 1".to_string())));
+        assert_eq!(calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_max_batch_size() {
+        use super::{CachedLoader, Loader, UnboundCache};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static LOAD_FN_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Clone)]
+        struct Loadable;
+
+        #[async_trait]
+        impl CachedLoader<u32, u64> for Loadable {
+            type Cache = UnboundCache<u32, u64>;
+            type Error = String;
+
+            async fn load_fn(&mut self, keys: &[u32]) -> Result<Vec<u64>, Self::Error> {
+                LOAD_FN_CALLS.fetch_add(1, Ordering::SeqCst);
+                assert!(keys.len() <= 100, "batch of {} keys", keys.len());
+                Ok(keys.into_iter().map(|&k| k as u64 * 10).collect())
+            }
+
+            fn init_cache() -> Self::Cache {
+                UnboundCache::new()
+            }
+
+            fn max_batch_size() -> Option<usize> {
+                Some(100)
+            }
+        }
+
+        let loader = Loadable {};
+        let many = loader.load_many((0..250).collect()).await.unwrap();
+        assert_eq!(LOAD_FN_CALLS.load(Ordering::SeqCst), 3);
+        assert_eq!(many.len(), 250);
+        assert!(many.iter().all(|(&k, &v)| v == k as u64 * 10));
+    }
+
+    #[tokio::test]
+    async fn test_max_batch_size_chunk_error() {
+        use super::{CachedLoader, Loader, UnboundCache};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static LOAD_FN_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Clone)]
+        struct Loadable;
+
+        #[async_trait]
+        impl CachedLoader<u16, u64> for Loadable {
+            type Cache = UnboundCache<u16, u64>;
+            type Error = String;
+
+            async fn load_fn(&mut self, keys: &[u16]) -> Result<Vec<u64>, Self::Error> {
+                LOAD_FN_CALLS.fetch_add(1, Ordering::SeqCst);
+                // the 2nd chunk fails
+                if keys.contains(&150) {
+                    return Err("bad chunk".to_string());
+                }
+                Ok(keys.into_iter().map(|&k| k as u64 * 10).collect())
+            }
+
+            fn init_cache() -> Self::Cache {
+                UnboundCache::new()
+            }
+
+            fn max_batch_size() -> Option<usize> {
+                Some(100)
+            }
+
+            fn batch_concurrency() -> usize {
+                2
+            }
+        }
+
+        let loader = Loadable {};
+        assert_eq!(
+            loader.load_many((0..250).collect()).await,
+            Err(LoaderError::Other("bad chunk".to_string()))
+        );
+        assert_eq!(LOAD_FN_CALLS.load(Ordering::SeqCst), 3);
+
+        // keys of the successful chunks are cached
+        assert_eq!(loader.load(1).await, Ok(10));
+        assert_eq!(LOAD_FN_CALLS.load(Ordering::SeqCst), 3);
+
+        // keys of the failed chunk are not
+        assert_eq!(
+            loader.load(150).await,
+            Err(LoaderError::Other("bad chunk".to_string()))
+        );
+        assert_eq!(LOAD_FN_CALLS.load(Ordering::SeqCst), 4);
+        assert_eq!(loader.load(120).await, Ok(1200));
+        assert_eq!(LOAD_FN_CALLS.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_boxed_loaders() {
+        use super::{BoxedLoader, CachedLoader, LoaderAdapter, NonCachedLoader, UnboundCache};
+
+        #[derive(Clone)]
+        struct Doubler;
+
+        #[async_trait]
+        impl NonCachedLoader<u64, u64> for Doubler {
+            type Error = ();
+
+            async fn load_fn(&mut self, keys: &[u64]) -> Result<Vec<u64>, Self::Error> {
+                Ok(keys.into_iter().map(|&k| k * 2).collect())
+            }
+        }
+
+        #[derive(Clone)]
+        struct Squarer;
+
+        #[async_trait]
+        impl CachedLoader<u64, u64> for Squarer {
+            type Cache = UnboundCache<u64, u64>;
+            type Error = ();
+
+            async fn load_fn(&mut self, keys: &[u64]) -> Result<Vec<u64>, Self::Error> {
+                Ok(keys.into_iter().map(|&k| k * k).collect())
+            }
+
+            fn init_cache() -> Self::Cache {
+                UnboundCache::new()
+            }
+        }
+
+        // different loader implementations behind the same type
+        let loaders: Vec<BoxedLoader<u64, u64, ()>> = vec![
+            LoaderAdapter::non_cached(Doubler).into_boxed(),
+            LoaderAdapter::cached(Squarer).into_boxed(),
+        ];
+
+        assert_eq!(loaders[0].load(3).await, Ok(6));
+        assert_eq!(loaders[1].load(3).await, Ok(9));
+
+        let many = loaders[0].load_many(vec![1, 2]).await.unwrap();
+        assert_eq!(many.len(), 2);
+        assert_eq!(many[&1], 2);
+        assert_eq!(many[&2], 4);
+
+        let many = loaders[1].load_many(vec![4, 5]).await.unwrap();
+        assert_eq!(many.len(), 2);
+        assert_eq!(many[&4], 16);
+        assert_eq!(many[&5], 25);
+    }
+
     #[tokio::test]
     async fn test_load_fn_missed_some_values() {
         use super::NonCachedLoader;
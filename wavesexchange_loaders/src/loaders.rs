@@ -1,8 +1,11 @@
 use crate::cacher::{CacheBounds, CacheKey, CacheVal, Cacher, ErrBounds, SharedObj};
 use crate::error::LoaderError;
 use dataloader::{cached, non_cached, BatchFn};
+use futures::{stream, StreamExt};
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::time::Duration;
+use wavesexchange_log::warn;
 
 pub type InnerLoader<'b, K, V, L> = non_cached::Loader<
     K,
@@ -35,6 +38,20 @@ pub trait NonCachedLoader<K: CacheKey, V: CacheVal>: SharedObj + Clone {
     /// It is important to return as many values as keys were provided,
     /// otherwise dataloader wouldn't process them and return `LoaderError::MissingValues`
     async fn load_fn(&mut self, keys: &[K]) -> Result<Vec<V>, Self::Error>;
+
+    /// Max number of keys passed to a single `load_fn` call, `None` for no limit.
+    /// Larger batches are split into chunks, the results are merged.
+    #[inline]
+    fn max_batch_size() -> Option<usize> {
+        None
+    }
+
+    /// Max number of `load_fn` calls (on the chunks of a batch) running concurrently,
+    /// each on a clone of the loader. With `1` chunks are loaded sequentially.
+    #[inline]
+    fn batch_concurrency() -> usize {
+        1
+    }
 }
 
 #[async_trait]
@@ -66,11 +83,67 @@ pub trait CachedLoader<K: CacheKey, V: CacheVal>: SharedObj + Clone {
     /// See params for all caches [`here`](https://docs.rs/cached/latest/cached/#structs)
     fn init_cache() -> Self::Cache;
 
+    /// Max number of keys passed to a single `load_fn` call, `None` for no limit.
+    /// Larger batches are split into chunks, the results are merged.
+    /// If some chunk fails, its keys are not cached.
+    #[inline]
+    fn max_batch_size() -> Option<usize> {
+        None
+    }
+
+    /// Max number of `load_fn` calls (on the chunks of a batch) running concurrently,
+    /// each on a clone of the loader. With `1` chunks are loaded sequentially.
+    #[inline]
+    fn batch_concurrency() -> usize {
+        1
+    }
+
     /// Determine values that will be cached, i.e. only `Some(...)`, but not `None`
     #[inline]
     fn cache_strategy(_: &K, _: &V) -> bool {
         true
     }
+
+    /// Determine negative values, i.e. `None` for keys not found upstream.
+    ///
+    /// Used only if `negative_ttl` is set.
+    #[inline]
+    fn is_negative(_: &V) -> bool {
+        false
+    }
+
+    /// Opt-in negative caching: negative values (see `is_negative`) are cached
+    /// for this period instead of the cache lifespan, regardless of `cache_strategy`.
+    /// A positive value loaded for the key afterwards replaces the negative one.
+    #[inline]
+    fn negative_ttl() -> Option<Duration> {
+        None
+    }
+
+    /// Opt-in graceful degradation: if `load_fn` fails, serve the last loaded value
+    /// of the key (with a warning logged) instead of returning `LoaderError::Other`,
+    /// even if the value has already expired from the cache.
+    #[inline]
+    fn serve_expired_on_error() -> bool {
+        false
+    }
+
+    /// How long a loaded value can be served on error, counting from the moment it was loaded.
+    /// Should be longer than the cache lifespan, otherwise the value expires before it is needed.
+    ///
+    /// Used only if `serve_expired_on_error` is enabled.
+    #[inline]
+    fn error_grace_ttl() -> Duration {
+        Duration::from_secs(300)
+    }
+}
+
+fn stale_ttl<K: CacheKey, V: CacheVal, L: CachedLoader<K, V>>() -> Option<Duration> {
+    if L::serve_expired_on_error() {
+        Some(L::error_grace_ttl())
+    } else {
+        None
+    }
 }
 
 /// Just import this trait and use `.load()` or `.load_many()` on any struct
@@ -113,26 +186,71 @@ where
 {
     async fn load(&self, key: K) -> Result<V, LoaderError<L::Error>> {
         let mut batch_wrapper = BatchFnWrapper::<_, _, _, _, true>::new(self.clone());
-        let cache = Cacher::get_or_init(Self::init_cache, Self::cache_strategy).await;
+        let cache = Cacher::get_or_init(
+            Self::init_cache,
+            Self::cache_strategy,
+            stale_ttl::<K, V, L>(),
+            Self::is_negative,
+            Self::negative_ttl(),
+        )
+        .await;
         let mut cache_lock = cache.lock().await;
         let loader = InnerCachedLoader::with_cache(&mut batch_wrapper, &mut *cache_lock);
         let result = Self::init_loader(loader).try_load(key.clone()).await;
-        if batch_wrapper.error.is_some() {
-            cache_lock.add_key_to_drop(&key);
+        if let Some(err @ LoaderError::Other(_)) = &batch_wrapper.error {
+            if let Some(value) = cache_lock.get_stale(&key) {
+                warn!(
+                    "Loader failed, serving expired value";
+                    "key" => format!("{:?}", key),
+                    "error" => format!("{:?}", err)
+                );
+                cache_lock.add_key_to_drop(&key);
+                cache_lock.cleanup();
+                return Ok(value);
+            }
         }
+        batch_wrapper
+            .failed_keys
+            .iter()
+            .for_each(|key| cache_lock.add_key_to_drop(key));
         cache_lock.cleanup();
         parse_loader_result(result, batch_wrapper.error)
     }
 
     async fn load_many(&self, keys: Vec<K>) -> Result<HashMap<K, V>, LoaderError<L::Error>> {
         let mut batch_wrapper = BatchFnWrapper::<_, _, _, _, true>::new(self.clone());
-        let cache = Cacher::get_or_init(Self::init_cache, Self::cache_strategy).await;
+        let cache = Cacher::get_or_init(
+            Self::init_cache,
+            Self::cache_strategy,
+            stale_ttl::<K, V, L>(),
+            Self::is_negative,
+            Self::negative_ttl(),
+        )
+        .await;
         let mut cache_lock = cache.lock().await;
         let loader = InnerCachedLoader::with_cache(&mut batch_wrapper, &mut *cache_lock);
         let result = Self::init_loader(loader).try_load_many(keys.clone()).await;
-        if batch_wrapper.error.is_some() {
-            keys.iter().for_each(|key| cache_lock.add_key_to_drop(key));
+        if let Some(err @ LoaderError::Other(_)) = &batch_wrapper.error {
+            // Only if all the requested values are available, otherwise the error is returned
+            let values = keys
+                .iter()
+                .map(|key| Some((key.clone(), cache_lock.get_stale(key)?)))
+                .collect::<Option<HashMap<_, _>>>();
+            if let Some(values) = values {
+                warn!(
+                    "Loader failed, serving expired values";
+                    "keys" => format!("{:?}", keys),
+                    "error" => format!("{:?}", err)
+                );
+                keys.iter().for_each(|key| cache_lock.add_key_to_drop(key));
+                cache_lock.cleanup();
+                return Ok(values);
+            }
         }
+        batch_wrapper
+            .failed_keys
+            .iter()
+            .for_each(|key| cache_lock.add_key_to_drop(key));
         cache_lock.cleanup();
         parse_loader_result(result, batch_wrapper.error)
     }
@@ -141,6 +259,8 @@ where
 pub struct BatchFnWrapper<K, V, C, E: ErrBounds, const HAS_CACHE: bool> {
     inner: C,
     error: Option<LoaderError<E>>,
+    /// Keys of the failed `load_fn` calls
+    failed_keys: Vec<K>,
     _pd: (PhantomData<K>, PhantomData<V>),
 }
 
@@ -149,6 +269,7 @@ impl<K: CacheKey, V: CacheVal, L: NonCachedLoader<K, V>> BatchFnWrapper<K, V, L,
         BatchFnWrapper {
             inner,
             error: None,
+            failed_keys: Vec::new(),
             _pd: (PhantomData, PhantomData),
         }
     }
@@ -159,6 +280,7 @@ impl<K: CacheKey, V: CacheVal, L: CachedLoader<K, V>> BatchFnWrapper<K, V, L, L:
         BatchFnWrapper {
             inner,
             error: None,
+            failed_keys: Vec::new(),
             _pd: (PhantomData, PhantomData),
         }
     }
@@ -169,11 +291,24 @@ impl<K: CacheKey, V: CacheVal, C: NonCachedLoader<K, V>> BatchFn<K, V>
     for &mut BatchFnWrapper<K, V, C, C::Error, false>
 {
     async fn load(&mut self, keys: &[K]) -> HashMap<K, V> {
-        let values = self.inner.load_fn(keys).await;
-        check_values(keys, values).unwrap_or_else(|e| {
-            self.error = Some(e);
-            HashMap::new()
-        })
+        let chunks = split_batch(keys, C::max_batch_size());
+        let results = if C::batch_concurrency() <= 1 {
+            let mut results = Vec::with_capacity(chunks.len());
+            for chunk in &chunks {
+                results.push(self.inner.load_fn(chunk).await);
+            }
+            results
+        } else {
+            stream::iter(&chunks)
+                .map(|chunk| {
+                    let mut inner = self.inner.clone();
+                    async move { inner.load_fn(chunk).await }
+                })
+                .buffered(C::batch_concurrency())
+                .collect()
+                .await
+        };
+        merge_chunks(&chunks, results, &mut self.error, &mut self.failed_keys)
     }
 }
 
@@ -182,12 +317,52 @@ impl<K: CacheKey, V: CacheVal, C: CachedLoader<K, V>> BatchFn<K, V>
     for &mut BatchFnWrapper<K, V, C, C::Error, true>
 {
     async fn load(&mut self, keys: &[K]) -> HashMap<K, V> {
-        let values = self.inner.load_fn(keys).await;
-        check_values(keys, values).unwrap_or_else(|e| {
-            self.error = Some(e);
-            HashMap::new()
-        })
+        let chunks = split_batch(keys, C::max_batch_size());
+        let results = if C::batch_concurrency() <= 1 {
+            let mut results = Vec::with_capacity(chunks.len());
+            for chunk in &chunks {
+                results.push(self.inner.load_fn(chunk).await);
+            }
+            results
+        } else {
+            stream::iter(&chunks)
+                .map(|chunk| {
+                    let mut inner = self.inner.clone();
+                    async move { inner.load_fn(chunk).await }
+                })
+                .buffered(C::batch_concurrency())
+                .collect()
+                .await
+        };
+        merge_chunks(&chunks, results, &mut self.error, &mut self.failed_keys)
+    }
+}
+
+fn split_batch<K>(keys: &[K], max_batch_size: Option<usize>) -> Vec<&[K]> {
+    match max_batch_size {
+        Some(max_batch_size) if max_batch_size > 0 => keys.chunks(max_batch_size).collect(),
+        _ => vec![keys],
+    }
+}
+
+/// Values of the successfully loaded chunks; the first error is saved, the keys of all failed chunks are collected
+fn merge_chunks<K: CacheKey, V: CacheVal, E: ErrBounds>(
+    chunks: &[&[K]],
+    results: Vec<Result<Vec<V>, E>>,
+    error: &mut Option<LoaderError<E>>,
+    failed_keys: &mut Vec<K>,
+) -> HashMap<K, V> {
+    let mut values = HashMap::new();
+    for (chunk, result) in chunks.iter().zip(results) {
+        match check_values(chunk, result) {
+            Ok(chunk_values) => values.extend(chunk_values),
+            Err(e) => {
+                failed_keys.extend_from_slice(chunk);
+                error.get_or_insert(e);
+            }
+        }
     }
+    values
 }
 
 fn check_values<K: CacheKey, V: CacheVal, E: ErrBounds>(
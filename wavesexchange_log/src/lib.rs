@@ -1,9 +1,12 @@
 pub use ::slog;
 
 use crate::format::OutputFormat;
+use crate::overflow::{AsyncDrain, OverflowStrategy};
 use once_cell::sync::Lazy;
 use slog::{o, Drain, FnValue, Logger, PushFnValue, Record};
-use std::sync::Mutex;
+use std::sync::{Mutex, Once};
+
+pub use overflow::dropped_records;
 
 pub static LOGGER: Lazy<slog::Logger> = Lazy::new(|| init_logger());
 
@@ -12,16 +15,20 @@ fn init_logger() -> Logger {
         OutputFormat::PlainText => {
             let decorator = slog_term::PlainDecorator::new(std::io::stdout());
             let drain = slog_term::FullFormat::new(decorator).build().fuse();
-            let drain = slog_async::Async::new(drain).chan_size(1000).build().fuse();
+            let drain = AsyncDrain::new(drain, OverflowStrategy::from_env());
             let drain = slog_envlogger::new(drain).fuse();
             let drain = Mutex::new(drain).map(slog::Fuse);
+            #[cfg(any(test, feature = "capture"))]
+            let drain = capture::CaptureDrain(drain);
             slog::Logger::root(drain, o!())
         }
         OutputFormat::Json => {
             let drain = slog_json::Json::new(std::io::stdout()).build().fuse();
-            let drain = slog_async::Async::new(drain).chan_size(1000).build().fuse();
+            let drain = AsyncDrain::new(drain, OverflowStrategy::from_env());
             let drain = slog_envlogger::new(drain).fuse();
             let drain = Mutex::new(drain).map(slog::Fuse);
+            #[cfg(any(test, feature = "capture"))]
+            let drain = capture::CaptureDrain(drain);
             slog::Logger::root(
                 drain,
                 o!(
@@ -44,6 +51,92 @@ fn init_logger() -> Logger {
     }
 }
 
+static PANIC_HOOK: Once = Once::new();
+
+/// Log panics as `crit` records, so that they appear in the configured output (plain text or JSON),
+/// with the panic message, location, thread name and, if enabled by `RUST_BACKTRACE`, a backtrace.
+/// The previously installed hook is called afterwards, so the default stderr output is kept.
+///
+/// Call it once at startup, subsequent calls do nothing.
+///
+/// ```no_run
+/// wavesexchange_log::install_panic_hook();
+/// ```
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let prev_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            panic_hook::log_panic(info.payload(), info.location());
+            prev_hook(info);
+        }));
+    });
+}
+
+mod panic_hook {
+    use std::any::Any;
+    use std::backtrace::{Backtrace, BacktraceStatus};
+    use std::fmt::{self, Write};
+    use std::panic::Location;
+    use std::thread;
+
+    /// Backtraces are long, only the top frames are logged
+    const MAX_BACKTRACE_LEN: usize = 4096;
+
+    pub(crate) fn log_panic(payload: &(dyn Any + Send), location: Option<&Location>) {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => *s,
+            None => match payload.downcast_ref::<String>() {
+                Some(s) => s.as_str(),
+                None => "Box<dyn Any>",
+            },
+        };
+        let location = match location {
+            Some(location) => format!("{}:{}", location.file(), location.line()),
+            None => String::from("<unknown>"),
+        };
+        let thread = thread::current();
+        let thread = thread.name().unwrap_or("<unnamed>");
+        let backtrace = Backtrace::capture();
+        let backtrace = match backtrace.status() {
+            BacktraceStatus::Captured => Some(truncated(&backtrace, MAX_BACKTRACE_LEN)),
+            _ => None,
+        };
+        crate::crit!(
+            "panicked at {}: {}", location, message;
+            "panic" => message,
+            "location" => &location,
+            "thread" => thread,
+            "backtrace" => backtrace
+        );
+    }
+
+    /// Format the value up to `max_len` bytes, without formatting the rest of it
+    pub(crate) fn truncated(value: &impl fmt::Display, max_len: usize) -> String {
+        struct Truncating(String, usize);
+
+        impl Write for Truncating {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                let available = self.1 - self.0.len();
+                if s.len() <= available {
+                    self.0.push_str(s);
+                    return Ok(());
+                }
+                let mut end = available;
+                while !s.is_char_boundary(end) {
+                    end -= 1;
+                }
+                self.0.push_str(&s[..end]);
+                self.0.push_str("...");
+                Err(fmt::Error)
+            }
+        }
+
+        let mut buf = Truncating(String::with_capacity(max_len), max_len);
+        let _ = write!(buf, "{}", value);
+        buf.0
+    }
+}
+
 #[macro_export]
 macro_rules! trace(
     ($arg:literal) => {
@@ -122,6 +215,44 @@ macro_rules! crit(
     };
 );
 
+/// Log only every `rate`-th invocation of this call site (the first one included),
+/// for the statements in hot loops, which would flood the output otherwise.
+/// The records are annotated with `sampled=<rate>`.
+///
+/// Takes the level (`trace`, `debug`, `info`, `warn`, `error` or `crit`), the rate,
+/// and a format string with its arguments and optional key-values, like the other macros.
+///
+/// ```no_run
+/// # use wavesexchange_log::sampled;
+/// # let orders = vec![1, 2, 3];
+/// for order in orders {
+///     sampled!(debug, 1000, "matching order {}", order; "side" => "buy");
+/// }
+/// ```
+#[macro_export]
+macro_rules! sampled(
+    ($level:ident, $rate:expr, $fmt:literal $(, $args:expr)* $(; $($kv:tt)*)?) => {{
+        static COUNTER: ::std::sync::atomic::AtomicU64 = ::std::sync::atomic::AtomicU64::new(0);
+        let rate: u64 = $rate;
+        if $crate::sampling::is_sampled(&COUNTER, rate) {
+            $crate::slog::$level!(
+                $crate::LOGGER, $fmt $(, $args)*; "sampled" => rate $(, $($kv)*)?
+            )
+        }
+    }};
+);
+
+#[doc(hidden)]
+pub mod sampling {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Whether the current invocation of a `sampled!` call site is logged, rate 0 is same as 1
+    #[inline]
+    pub fn is_sampled(counter: &AtomicU64, rate: u64) -> bool {
+        counter.fetch_add(1, Ordering::Relaxed) % rate.max(1) == 0
+    }
+}
+
 /// Use this macro to set up a scope timer,
 /// which logs execution time of a code block.
 ///
@@ -247,6 +378,328 @@ pub mod scopetimer {
     }
 }
 
+/// In-memory capture of log records, to assert log output in tests.
+///
+/// Available in tests of this crate, or with the `capture` feature enabled:
+/// ```toml
+/// [dev-dependencies]
+/// wavesexchange_log = { ..., features = ["capture"] }
+/// ```
+///
+/// ```ignore
+/// # use wavesexchange_log::{capture::LogCapture, info};
+/// let capture = LogCapture::start();
+/// info!("user {} logged in", 42; "source" => "web");
+/// assert!(capture
+///     .lines()
+///     .contains(&"INFO user 42 logged in; source=web".to_string()));
+/// ```
+#[cfg(any(test, feature = "capture"))]
+pub mod capture {
+    use once_cell::sync::Lazy;
+    use slog::{Drain, Key, Level, OwnedKVList, Record, KV};
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc, Mutex,
+        },
+    };
+
+    static SINKS: Lazy<Mutex<Vec<(u64, mpsc::Sender<CapturedRecord>)>>> =
+        Lazy::new(|| Mutex::new(Vec::new()));
+    static NEXT_SINK_ID: AtomicU64 = AtomicU64::new(0);
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct CapturedRecord {
+        pub level: Level,
+        pub module: &'static str,
+        pub msg: String,
+        /// Key-values of the record itself (without the ones of the logger)
+        pub kv: Vec<(String, String)>,
+    }
+
+    impl CapturedRecord {
+        fn new(record: &Record) -> Self {
+            let mut kv = KvCollector(Vec::new());
+            let _ = record.kv().serialize(record, &mut kv);
+            CapturedRecord {
+                level: record.level(),
+                module: record.module(),
+                msg: record.msg().to_string(),
+                kv: kv.0,
+            }
+        }
+
+        pub fn get(&self, key: &str) -> Option<&str> {
+            self.kv
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// `INFO message; key1=value1, key2=value2`
+    impl fmt::Display for CapturedRecord {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} {}", self.level.as_short_str(), self.msg)?;
+            for (i, (key, value)) in self.kv.iter().enumerate() {
+                let sep = if i == 0 { "; " } else { ", " };
+                write!(f, "{}{}={}", sep, key, value)?;
+            }
+            Ok(())
+        }
+    }
+
+    struct KvCollector(Vec<(String, String)>);
+
+    impl slog::Serializer for KvCollector {
+        fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+            self.0.push((key.to_string(), val.to_string()));
+            Ok(())
+        }
+    }
+
+    /// Captures records logged via `LOGGER` from any thread, until dropped.
+    ///
+    /// Records are captured regardless of the `RUST_LOG` filter,
+    /// and are still written to the output as usual.
+    /// Tests run in parallel, so filter the captured records by message or module.
+    pub struct LogCapture {
+        id: u64,
+        records: mpsc::Receiver<CapturedRecord>,
+    }
+
+    impl LogCapture {
+        pub fn start() -> Self {
+            Lazy::force(&crate::LOGGER);
+            let id = NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed);
+            let (tx, rx) = mpsc::channel();
+            SINKS.lock().unwrap().push((id, tx));
+            LogCapture { id, records: rx }
+        }
+
+        /// Records captured since the start or the previous call
+        pub fn records(&self) -> Vec<CapturedRecord> {
+            self.records.try_iter().collect()
+        }
+
+        /// Same as `records`, formatted as `INFO message; key=value`
+        pub fn lines(&self) -> Vec<String> {
+            self.records().iter().map(ToString::to_string).collect()
+        }
+    }
+
+    impl Drop for LogCapture {
+        fn drop(&mut self) {
+            if let Ok(mut sinks) = SINKS.lock() {
+                sinks.retain(|(id, _)| *id != self.id);
+            }
+        }
+    }
+
+    /// Sends every record to the active captures, then passes it to the inner drain
+    pub(crate) struct CaptureDrain<D>(pub(crate) D);
+
+    impl<D: Drain> Drain for CaptureDrain<D> {
+        type Ok = D::Ok;
+        type Err = D::Err;
+
+        fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            {
+                let sinks = SINKS.lock().unwrap();
+                if !sinks.is_empty() {
+                    let captured = CapturedRecord::new(record);
+                    for (_, sink) in sinks.iter() {
+                        let _ = sink.send(captured.clone());
+                    }
+                }
+            }
+            self.0.log(record, values)
+        }
+    }
+}
+
+mod overflow {
+    use slog::{Drain, Level, Never, OwnedKVList, Record};
+    use slog_async::AsyncCore;
+    use std::{
+        env,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+    /// Number of log records dropped since the start because the output couldn't keep up
+    /// (the async channel was full), see `RUST_LOG_OVERFLOW`
+    pub fn dropped_records() -> u64 {
+        DROPPED.load(Ordering::Relaxed)
+    }
+
+    /// What to do with a record when the async channel is full
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub(crate) enum OverflowStrategy {
+        /// Drop the record, counting it in `dropped_records`
+        Drop,
+        /// Wait for a free slot, slowing down the logging threads
+        Block,
+    }
+
+    impl OverflowStrategy {
+        const ENV_NAME: &'static str = "RUST_LOG_OVERFLOW";
+
+        pub(crate) fn from_env() -> Self {
+            match env::var(Self::ENV_NAME).ok().unwrap_or_default().as_str() {
+                "drop" | "" => Self::Drop,
+                "block" => Self::Block,
+                other => panic!("Unrecognized {} value: '{}'", Self::ENV_NAME, other),
+            }
+        }
+    }
+
+    /// Asynchronous drain counting the dropped records.
+    ///
+    /// `slog_async::Async` only reports the number of dropped records in the output,
+    /// so `AsyncCore` is used directly. The drops are reported with a warning record
+    /// as soon as the channel accepts records again.
+    pub(crate) struct AsyncDrain {
+        core: AsyncCore,
+        unreported: AtomicU64,
+    }
+
+    impl AsyncDrain {
+        const CHAN_SIZE: usize = 1000;
+
+        pub(crate) fn new<D>(drain: D, strategy: OverflowStrategy) -> Self
+        where
+            D: Drain<Ok = (), Err = Never> + Send + 'static,
+        {
+            Self::with_chan_size(drain, strategy, Self::CHAN_SIZE)
+        }
+
+        fn with_chan_size<D>(drain: D, strategy: OverflowStrategy, chan_size: usize) -> Self
+        where
+            D: Drain<Ok = (), Err = Never> + Send + 'static,
+        {
+            let core = AsyncCore::custom(drain)
+                .chan_size(chan_size)
+                .blocking(strategy == OverflowStrategy::Block)
+                .build();
+            AsyncDrain {
+                core,
+                unreported: AtomicU64::new(0),
+            }
+        }
+
+        fn report_dropped(&self, values: &OwnedKVList) {
+            let dropped = self.unreported.swap(0, Ordering::Relaxed);
+            if dropped == 0 {
+                return;
+            }
+            let res = self.core.log(
+                &slog::record!(
+                    Level::Warning,
+                    "",
+                    &format_args!("{} log records dropped", dropped),
+                    slog::b!("dropped_records" => dropped)
+                ),
+                values,
+            );
+            if res.is_err() {
+                // full again, to be reported later along with the new drops
+                self.unreported.fetch_add(dropped, Ordering::Relaxed);
+            }
+        }
+    }
+
+    impl Drain for AsyncDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), Never> {
+            match self.core.log(record, values) {
+                Ok(()) => self.report_dropped(values),
+                // either `Full` or `Fatal` (the logging thread is gone), the record is lost anyway
+                Err(_) => {
+                    DROPPED.fetch_add(1, Ordering::Relaxed);
+                    self.unreported.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use slog::{info, o, Logger};
+        use std::{
+            sync::{Arc, Mutex},
+            thread,
+            time::Duration,
+        };
+
+        /// Collects the messages, slowly
+        struct SlowDrain(Arc<Mutex<Vec<String>>>);
+
+        impl Drain for SlowDrain {
+            type Ok = ();
+            type Err = Never;
+
+            fn log(&self, record: &Record, _: &OwnedKVList) -> Result<(), Never> {
+                thread::sleep(Duration::from_millis(5));
+                self.0.lock().unwrap().push(record.msg().to_string());
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn dropped_records_counted_and_reported() {
+            let messages = Arc::new(Mutex::new(Vec::new()));
+            let drain =
+                AsyncDrain::with_chan_size(SlowDrain(messages.clone()), OverflowStrategy::Drop, 2);
+            let logger = Logger::root(drain, o!());
+            let before = dropped_records();
+
+            for i in 0..100 {
+                info!(logger, "flood {}", i);
+            }
+            let dropped = dropped_records() - before;
+            assert!(dropped > 0);
+
+            // the channel is free again
+            thread::sleep(Duration::from_millis(100));
+            info!(logger, "after the flood");
+            drop(logger); // waits for the logging thread
+
+            let messages = messages.lock().unwrap();
+            let flood = messages.iter().filter(|m| m.starts_with("flood")).count();
+            assert!(flood < 100);
+            // reported as soon as a record is accepted again, maybe in several parts
+            let reported = messages
+                .iter()
+                .filter_map(|m| m.strip_suffix(" log records dropped"))
+                .map(|n| n.parse::<u64>().unwrap())
+                .sum::<u64>();
+            assert_eq!(reported, dropped);
+            assert!(messages.contains(&"after the flood".to_string()));
+        }
+
+        #[test]
+        fn blocking_drops_nothing() {
+            let messages = Arc::new(Mutex::new(Vec::new()));
+            let drain =
+                AsyncDrain::with_chan_size(SlowDrain(messages.clone()), OverflowStrategy::Block, 2);
+            let logger = Logger::root(drain, o!());
+            for i in 0..20 {
+                info!(logger, "blocking {}", i);
+            }
+            drop(logger);
+            assert_eq!(messages.lock().unwrap().len(), 20);
+        }
+    }
+}
+
 mod format {
     use std::env;
 
@@ -281,3 +734,115 @@ mod format {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::LogCapture;
+    use slog::Level;
+
+    #[test]
+    fn capture_info() {
+        let capture = LogCapture::start();
+        info!("captured {}", 42; "key" => "value");
+        debug!("captured debug");
+
+        let records = capture
+            .records()
+            .into_iter()
+            .filter(|r| r.msg.starts_with("captured"))
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].level, Level::Info);
+        assert_eq!(records[0].msg, "captured 42");
+        assert_eq!(records[0].get("key"), Some("value"));
+        assert_eq!(records[0].module, module_path!());
+        assert_eq!(records[0].to_string(), "INFO captured 42; key=value");
+        assert_eq!(records[1].to_string(), "DEBG captured debug");
+
+        // other tests log in parallel, so only the records of this one are checked
+        let own_lines = |capture: &LogCapture| {
+            capture
+                .records()
+                .into_iter()
+                .filter(|r| r.msg.starts_with("captured"))
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(own_lines(&capture).is_empty());
+        let second = LogCapture::start();
+        info!("captured again");
+        assert_eq!(own_lines(&capture), vec!["INFO captured again"]);
+        assert_eq!(own_lines(&second), vec!["INFO captured again"]);
+    }
+
+    #[test]
+    fn panic_hook() {
+        crate::install_panic_hook();
+        crate::install_panic_hook();
+        let capture = LogCapture::start();
+
+        let res = std::thread::Builder::new()
+            .name("panicking".to_string())
+            .spawn(|| panic!("boom {}", 42))
+            .unwrap()
+            .join();
+        assert!(res.is_err());
+
+        let records = capture
+            .records()
+            .into_iter()
+            .filter(|r| r.get("panic") == Some("boom 42"))
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, Level::Critical);
+        assert_eq!(records[0].get("thread"), Some("panicking"));
+        let location = records[0].get("location").unwrap();
+        assert!(location.contains("src/lib.rs:"), "{}", location);
+        assert!(records[0].msg.ends_with(": boom 42"));
+    }
+
+    #[test]
+    fn sampled_cadence() {
+        let capture = LogCapture::start();
+        for i in 0..10 {
+            sampled!(info, 3, "sampled {}", i; "key" => "value");
+        }
+        for i in 0..3 {
+            sampled!(debug, 1, "sampled every {}", i);
+        }
+
+        let records = capture
+            .records()
+            .into_iter()
+            .filter(|r| r.msg.starts_with("sampled "))
+            .collect::<Vec<_>>();
+        let msgs = records.iter().map(|r| r.msg.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            msgs,
+            vec![
+                "sampled 0",
+                "sampled 3",
+                "sampled 6",
+                "sampled 9",
+                "sampled every 0",
+                "sampled every 1",
+                "sampled every 2",
+            ]
+        );
+        assert_eq!(records[0].level, Level::Info);
+        assert_eq!(records[0].get("sampled"), Some("3"));
+        assert_eq!(records[0].get("key"), Some("value"));
+        assert_eq!(records[4].level, Level::Debug);
+        assert_eq!(records[4].get("sampled"), Some("1"));
+    }
+
+    #[test]
+    fn panic_hook_truncated_backtrace() {
+        use crate::panic_hook::truncated;
+
+        assert_eq!(truncated(&"short", 10), "short");
+        assert_eq!(truncated(&"exactly 10", 10), "exactly 10");
+        assert_eq!(truncated(&"this is too long", 10), "this is to...");
+        assert_eq!(truncated(&"ééééé", 5), "éé...");
+    }
+}
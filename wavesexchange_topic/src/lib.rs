@@ -1,10 +1,11 @@
 //! Subscription topic: an URI which can be parsed
 //! into a machine-readable data struct describing client's subscription.
 
-use std::sync::Arc;
+use std::{borrow::Cow, cmp::Ordering, sync::Arc};
 use url::Url;
 
-pub use parse_and_format::parse::TopicParseError;
+pub use codec::TopicDecodeError;
+pub use parse_and_format::parse::{TopicParseError, TopicParseOptions};
 
 /// A cheaply cloneable (`Arc` inside) subscription topic struct.
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -21,6 +22,8 @@ pub enum TopicKind {
     Transaction,
     LeasingBalance,
     ExchangePair,
+    /// A kind this version doesn't know, only with `TopicParseOptions::allow_unknown_kinds`
+    Unknown,
 }
 
 /// A parsed Topic representation
@@ -33,6 +36,13 @@ pub enum TopicData {
     Transaction(Transaction),
     LeasingBalance(LeasingBalance),
     ExchangePair(ExchangePair),
+    /// Topic of an unknown kind, kept as is to be forwarded
+    /// (only with `TopicParseOptions::allow_unknown_kinds`)
+    Unknown {
+        kind: String,
+        path: String,
+        query: Option<String>,
+    },
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -40,9 +50,20 @@ pub struct ConfigResource {
     pub file: ConfigFile,
 }
 
+/// Config file subscription, e.g. `topic://config/path/to/file?version=42&format=yaml`
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct ConfigFile {
     pub path: String,
+    /// Pinned version of the file, latest if not set
+    pub version: Option<u64>,
+    /// Requested format of the file, as stored if not set
+    pub format: Option<ConfigFormat>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -113,6 +134,19 @@ pub struct TransactionExchange {
     pub price_asset: String,
 }
 
+/// Key for grouping transaction topics regardless of the subscriber's address,
+/// so that a single transaction event can be routed to all interested subscribers at once.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TransactionGroupKey {
+    /// Transactions of the given type (by-address subscriptions)
+    ByType(TransactionType),
+    /// Exchange transactions of the given asset pair
+    ExchangePair {
+        amount_asset: String,
+        price_asset: String,
+    },
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct LeasingBalance {
     pub address: String,
@@ -133,9 +167,9 @@ mod parse_and_format {
         use crate::ExchangePair;
 
         use super::super::{
-            BlockchainHeight, ConfigFile, ConfigResource, LeasingBalance, State, StateSingle,
-            TestResource, Topic, TopicData, TopicKind, Transaction, TransactionByAddress,
-            TransactionExchange, TransactionType,
+            BlockchainHeight, ConfigFile, ConfigFormat, ConfigResource, LeasingBalance, State,
+            StateSingle, TestResource, Topic, TopicData, TopicKind, Transaction,
+            TransactionByAddress, TransactionExchange, TransactionType,
         };
         use super::{maybe_string::MaybeString, serde_state, url_escape};
 
@@ -173,19 +207,86 @@ mod parse_and_format {
 
             #[error("Invalid exchange pairs data")]
             InvalidExchangePair,
+
+            #[error("Topic URI is too long (max {0} bytes)")]
+            UriTooLong(usize),
+
+            #[error("Too many 'address__in' entries (max {0})")]
+            TooManyAddresses(usize),
+
+            #[error("Too many 'key__match_any' entries (max {0})")]
+            TooManyKeyPatterns(usize),
+
+            #[error("Key pattern is too long (max {0} bytes)")]
+            KeyPatternTooLong(usize),
+        }
+
+        /// Limits of the topic parser, protecting from the topics taking too much memory.
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub struct TopicParseOptions {
+            /// Max length of the whole topic URI, in bytes
+            pub max_uri_len: usize,
+            /// Max number of `address__in` entries of a 'state' topic
+            pub max_addresses: usize,
+            /// Max number of `key__match_any` entries of a 'state' topic
+            pub max_key_patterns: usize,
+            /// Max length of a single decoded `key__match_any` entry, in bytes
+            pub max_key_pattern_len: usize,
+            /// Accept topics of unknown kinds as `TopicKind::Unknown` instead of failing
+            /// with `InvalidTopicKind`, e.g. to forward topics of the kinds newer than this version
+            pub allow_unknown_kinds: bool,
+        }
+
+        impl Default for TopicParseOptions {
+            fn default() -> Self {
+                TopicParseOptions {
+                    max_uri_len: 16 * 1024,
+                    max_addresses: 100,
+                    max_key_patterns: 100,
+                    max_key_pattern_len: 1024,
+                    allow_unknown_kinds: false,
+                }
+            }
+        }
+
+        impl TopicParseOptions {
+            /// No limits and any kind, for the URIs formatted from an already parsed `TopicData`
+            pub(crate) fn unlimited() -> Self {
+                TopicParseOptions {
+                    max_uri_len: usize::MAX,
+                    max_addresses: usize::MAX,
+                    max_key_patterns: usize::MAX,
+                    max_key_pattern_len: usize::MAX,
+                    allow_unknown_kinds: true,
+                }
+            }
         }
 
         impl Topic {
+            /// Parse with the default `TopicParseOptions`
             pub fn parse_str(topic_uri: &str) -> Result<Self, TopicParseError> {
+                Self::parse_str_with_options(topic_uri, &TopicParseOptions::default())
+            }
+
+            pub fn parse_str_with_options(
+                topic_uri: &str,
+                options: &TopicParseOptions,
+            ) -> Result<Self, TopicParseError> {
+                if topic_uri.len() > options.max_uri_len {
+                    return Err(TopicParseError::UriTooLong(options.max_uri_len));
+                }
                 let mut url = Url::parse(topic_uri)?;
-                Self::validate_and_canonicalize_topic_url(&mut url)?;
+                Self::validate_and_canonicalize_topic_url(&mut url, options)?;
 
                 Ok(Topic {
                     topic_url: Arc::new(url),
                 })
             }
 
-            fn validate_and_canonicalize_topic_url(url: &mut Url) -> Result<(), TopicParseError> {
+            fn validate_and_canonicalize_topic_url(
+                url: &mut Url,
+                options: &TopicParseOptions,
+            ) -> Result<(), TopicParseError> {
                 if url.scheme() != "topic"
                     || url.cannot_be_a_base()
                     || url.username() != ""
@@ -200,9 +301,25 @@ mod parse_and_format {
                     .host_str()
                     .ok_or(TopicParseError::InvalidTopicKind(MaybeString(None)))?;
 
-                let topic_kind = TopicKind::parse(topic_kind_str).ok_or_else(|| {
-                    TopicParseError::InvalidTopicKind(MaybeString(Some(topic_kind_str.to_owned())))
-                })?;
+                let topic_kind = match TopicKind::parse(topic_kind_str) {
+                    Some(topic_kind) => topic_kind,
+                    None if options.allow_unknown_kinds => TopicKind::Unknown,
+                    None => {
+                        return Err(TopicParseError::InvalidTopicKind(MaybeString(Some(
+                            topic_kind_str.to_owned(),
+                        ))))
+                    }
+                };
+
+                // Canonicalize: a single trailing slash is ignored,
+                // because clients concatenating path parts often add it.
+                // Unknown kinds are kept as is.
+                if topic_kind != TopicKind::Unknown {
+                    if let Some(path) = url.path().strip_suffix('/') {
+                        let path = path.to_owned();
+                        url.set_path(&path);
+                    }
+                }
 
                 fn is_empty(s: Option<impl AsRef<str>>) -> bool {
                     match s {
@@ -214,9 +331,33 @@ mod parse_and_format {
                 match topic_kind {
                     TopicKind::Config => {
                         let config_file_path = url.path();
-                        if config_file_path.is_empty() || url.query().is_some() {
+                        if config_file_path.is_empty() || url.query() == Some("") {
                             return Err(TopicParseError::InvalidConfigTopic);
                         }
+                        if url.query().is_some() {
+                            let mut version = None;
+                            let mut format = None;
+                            for (k, v) in url.query_pairs() {
+                                let is_ok = match &*k {
+                                    "version" if version.is_none() => {
+                                        version = v.parse::<u64>().ok();
+                                        version.is_some()
+                                    }
+                                    "format" if format.is_none() => {
+                                        format = ConfigFormat::parse(&v);
+                                        format.is_some()
+                                    }
+                                    // unknown or repeated parameter
+                                    _ => false,
+                                };
+                                if !is_ok {
+                                    return Err(TopicParseError::InvalidConfigTopic);
+                                }
+                            }
+                            // Canonicalize
+                            let query = config_query(version, format).expect("config query");
+                            url.set_query(Some(&query));
+                        }
                     }
                     TopicKind::State => {
                         let is_single = url.query().is_none();
@@ -238,15 +379,34 @@ mod parse_and_format {
                                 url_escape::encode(key.map(url_escape::decode).unwrap().as_ref())
                             ));
                         } else {
-                            let is_ok = url.query_pairs().all(|(k, v)| {
+                            let (mut addresses, mut key_patterns) = (0, 0);
+                            for (k, v) in url.query_pairs() {
+                                if v.is_empty() {
+                                    return Err(TopicParseError::InvalidStateTopic);
+                                }
                                 let key = url_escape::decode(&*k);
-                                let key_ok = key.starts_with("address__in[")
-                                    || key.starts_with("key__match_any[");
-                                let value_ok = !v.is_empty();
-                                key_ok && value_ok
-                            });
-                            if !is_ok {
-                                return Err(TopicParseError::InvalidStateTopic);
+                                if key.starts_with("address__in[") {
+                                    addresses += 1;
+                                    if addresses > options.max_addresses {
+                                        return Err(TopicParseError::TooManyAddresses(
+                                            options.max_addresses,
+                                        ));
+                                    }
+                                } else if key.starts_with("key__match_any[") {
+                                    key_patterns += 1;
+                                    if key_patterns > options.max_key_patterns {
+                                        return Err(TopicParseError::TooManyKeyPatterns(
+                                            options.max_key_patterns,
+                                        ));
+                                    }
+                                    if v.len() > options.max_key_pattern_len {
+                                        return Err(TopicParseError::KeyPatternTooLong(
+                                            options.max_key_pattern_len,
+                                        ));
+                                    }
+                                } else {
+                                    return Err(TopicParseError::InvalidStateTopic);
+                                }
                             }
                             // Canonicalize
                             let query = url.query().unwrap(); // unwrap is safe here
@@ -262,6 +422,12 @@ mod parse_and_format {
                         if !is_ok {
                             return Err(TopicParseError::InvalidTestResourceTopic);
                         }
+                        if !is_empty(url.query()) {
+                            // Canonicalize
+                            let query = url.query().unwrap(); // unwrap is safe here
+                            let query = crate::canonical_query(query);
+                            url.set_query(Some(&query));
+                        }
                     }
                     TopicKind::BlockchainHeight => {
                         let is_ok = url.path().is_empty() && is_empty(url.query());
@@ -319,6 +485,8 @@ mod parse_and_format {
                     TopicKind::ExchangePair => {
                         Topic::extract_exchange_pairs(&url)?;
                     }
+                    // the path and the query are opaque
+                    TopicKind::Unknown => {}
                 }
 
                 Ok(())
@@ -356,16 +524,22 @@ mod parse_and_format {
                 // This is checked by `validate()` during parse stage, so `expect()` is safe
                 let topic_kind_str = url.host_str().expect("host_str");
 
-                // Same safety guarantee
-                let topic_kind = TopicKind::parse(topic_kind_str).expect("topic_kind");
+                // Same safety guarantee, unless allowed to be unknown
+                let topic_kind = topic.kind();
 
                 // URL is checked by `validate()` during parse stage, so all `expect()` calls are safe
                 match topic_kind {
                     TopicKind::Config => {
                         let config_file_path = url.path().to_owned();
+                        let version =
+                            query_get(url, "version").map(|v| v.parse::<u64>().expect("version"));
+                        let format = query_get(url, "format")
+                            .map(|f| ConfigFormat::parse(&f).expect("format"));
                         TopicData::Config(ConfigResource {
                             file: ConfigFile {
                                 path: config_file_path,
+                                version,
+                                format,
                             },
                         })
                     }
@@ -438,10 +612,31 @@ mod parse_and_format {
                     TopicKind::ExchangePair => TopicData::ExchangePair({
                         Topic::extract_exchange_pairs(&url).expect("invalid pair")
                     }),
+                    TopicKind::Unknown => TopicData::Unknown {
+                        kind: topic_kind_str.to_owned(),
+                        path: url.path().to_owned(),
+                        query: url.query().map(|q| q.to_owned()),
+                    },
                 }
             }
         }
 
+        /// Query string of the config topic with the parameters in the fixed order,
+        /// `None` if there are no parameters
+        pub(super) fn config_query(
+            version: Option<u64>,
+            format: Option<ConfigFormat>,
+        ) -> Option<String> {
+            let version = version.map(|v| format!("version={}", v));
+            let format = format.map(|f| format!("format={}", f));
+            let params = version.into_iter().chain(format).collect::<Vec<_>>();
+            if params.is_empty() {
+                None
+            } else {
+                Some(params.join("&"))
+            }
+        }
+
         fn query_get<'a>(url: &'a Url, key: &str) -> Option<Cow<'a, str>> {
             url.query_pairs().find_map(|(k, v)| {
                 if k == key && !v.is_empty() {
@@ -453,15 +648,48 @@ mod parse_and_format {
         }
 
         impl TopicKind {
+            /// All topic kinds along with their canonical names (the host part of the topic URI).
+            ///
+            /// This is the single source of truth for both parsing and formatting of topic kinds.
+            pub const ALL: &'static [(TopicKind, &'static str)] = &[
+                (TopicKind::Config, "config"),
+                (TopicKind::State, "state"),
+                (TopicKind::TestResource, "test_resource"),
+                (TopicKind::BlockchainHeight, "blockchain_height"),
+                (TopicKind::Transaction, "transactions"),
+                (TopicKind::LeasingBalance, "leasing_balance"),
+                (TopicKind::ExchangePair, "pairs"),
+            ];
+
             pub(in super::super) fn parse(s: &str) -> Option<Self> {
+                Self::ALL
+                    .iter()
+                    .find(|&&(_, name)| name == s)
+                    .map(|&(kind, _)| kind)
+            }
+
+            /// Canonical name of this topic kind, exactly as it appears in the topic URI.
+            /// This is the inverse of the topic kind parsing, except for `Unknown`.
+            ///
+            /// `Unknown` is `"unknown"` for any unknown kind, which doesn't parse back.
+            /// The actual kind of such a topic is `Topic::raw_kind_str`.
+            pub fn as_str(self) -> &'static str {
+                if self == TopicKind::Unknown {
+                    return "unknown";
+                }
+                Self::ALL
+                    .iter()
+                    .find(|&&(kind, _)| kind == self)
+                    .map(|&(_, name)| name)
+                    .expect("invariant broken: topic kind missing from TopicKind::ALL")
+            }
+        }
+
+        impl ConfigFormat {
+            fn parse(s: &str) -> Option<Self> {
                 match s {
-                    "config" => Some(TopicKind::Config),
-                    "state" => Some(TopicKind::State),
-                    "test_resource" => Some(TopicKind::TestResource),
-                    "blockchain_height" => Some(TopicKind::BlockchainHeight),
-                    "transactions" => Some(TopicKind::Transaction),
-                    "leasing_balance" => Some(TopicKind::LeasingBalance),
-                    "pairs" => Some(TopicKind::ExchangePair),
+                    "json" => Some(ConfigFormat::Json),
+                    "yaml" => Some(ConfigFormat::Yaml),
                     _ => None,
                 }
             }
@@ -525,6 +753,86 @@ mod parse_and_format {
             Ok(())
         }
 
+        #[test]
+        fn topic_kind_str_test() -> anyhow::Result<()> {
+            // Exhaustive match makes sure this test is updated when a new kind is added
+            fn check_covered(kind: TopicKind) {
+                match kind {
+                    TopicKind::Config
+                    | TopicKind::State
+                    | TopicKind::TestResource
+                    | TopicKind::BlockchainHeight
+                    | TopicKind::Transaction
+                    | TopicKind::LeasingBalance
+                    | TopicKind::ExchangePair => {}
+                    // not a parseable kind, so not in `ALL`
+                    TopicKind::Unknown => {}
+                }
+            }
+
+            assert_eq!(TopicKind::ALL.len(), 7);
+            for &(kind, name) in TopicKind::ALL {
+                check_covered(kind);
+                assert_eq!(kind.as_str(), name);
+                assert_eq!(TopicKind::parse(kind.as_str()), Some(kind));
+            }
+
+            let topic = Topic::parse_str("topic://transactions?type=all&address=some_address")?;
+            assert_eq!(topic.kind_str(), "transactions");
+            let topic = Topic::parse_str("topic://pairs/amount_asset/price_asset")?;
+            assert_eq!(topic.kind_str(), "pairs");
+
+            Ok(())
+        }
+
+        #[test]
+        fn topic_config_test() -> anyhow::Result<()> {
+            let topic = Topic::parse_str("topic://config/some/path")?;
+            let config = topic.data().as_config().cloned().expect("config");
+            assert_eq!(
+                config.file,
+                ConfigFile {
+                    path: "/some/path".to_string(),
+                    version: None,
+                    format: None,
+                }
+            );
+
+            let topic = Topic::parse_str("topic://config/some/path?format=yaml&version=042")?;
+            assert_eq!(
+                topic.to_string(),
+                "topic://config/some/path?version=42&format=yaml"
+            );
+            let config = topic.data().as_config().cloned().expect("config");
+            assert_eq!(config.file.version, Some(42));
+            assert_eq!(config.file.format, Some(ConfigFormat::Yaml));
+
+            let topic = Topic::parse_str("topic://config/some/path?format=json")?;
+            let config = topic.data().as_config().cloned().expect("config");
+            assert_eq!(config.file.version, None);
+            assert_eq!(config.file.format, Some(ConfigFormat::Json));
+
+            for invalid in [
+                "topic://config",
+                "topic://config/some/path?",
+                "topic://config/some/path?unknown=1",
+                "topic://config/some/path?version=42&unknown=1",
+                "topic://config/some/path?version=-1",
+                "topic://config/some/path?version=",
+                "topic://config/some/path?version=1&version=2",
+                "topic://config/some/path?format=xml",
+            ] {
+                assert_eq!(
+                    Topic::parse_str(invalid).unwrap_err(),
+                    TopicParseError::InvalidConfigTopic,
+                    "{}",
+                    invalid
+                );
+            }
+
+            Ok(())
+        }
+
         #[test]
         fn topic_state_test() -> anyhow::Result<()> {
             let topic_data = Topic::parse_str("topic://state/some_address/some_key")?.data();
@@ -548,10 +856,10 @@ mod parse_and_format {
             assert!(matches!(state, State::MultiPatterns(_)));
             if let State::MultiPatterns(ref state) = state {
                 assert_eq!(state.addresses, vec!["addr1", "addr2"]);
-                assert_eq!(state.key_patterns, vec!["pattern1", "pattern*2"]);
+                assert_eq!(state.key_patterns, vec!["pattern*2", "pattern1"]);
             }
             assert_eq!(
-                "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=pattern1&key__match_any[1]=pattern*2".to_string(),
+                "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=pattern*2&key__match_any[1]=pattern1".to_string(),
                 topic_data.as_uri_string(),
             );
 
@@ -563,16 +871,140 @@ mod parse_and_format {
             assert!(matches!(state, State::MultiPatterns(_)));
             if let State::MultiPatterns(ref state) = state {
                 assert_eq!(state.addresses, vec!["addr1", "addr2"]);
-                assert_eq!(state.key_patterns, vec!["pattern1", "pattern*2"]);
+                assert_eq!(state.key_patterns, vec!["pattern*2", "pattern1"]);
             }
             assert_eq!(
-                "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=pattern1&key__match_any[1]=pattern*2".to_string(),
+                "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=pattern*2&key__match_any[1]=pattern1".to_string(),
                 topic_data.as_uri_string(),
             );
 
             Ok(())
         }
 
+        #[test]
+        fn topic_state_multi_patterns_canonical_test() -> anyhow::Result<()> {
+            let topic = Topic::parse_str("topic://state?address__in[]=addr2&address__in[]=addr1&address__in[]=addr2&key__match_any[]=b*&key__match_any[]=a*")?;
+            let same = Topic::parse_str("topic://state?address__in[]=addr1&address__in[]=addr2&key__match_any[]=a*&key__match_any[]=b*&key__match_any[]=a*")?;
+            assert_eq!(topic, same);
+            assert_eq!(
+                topic.to_string(),
+                "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=a*&key__match_any[1]=b*"
+            );
+            let data = topic.data();
+            let state = data.as_state_multi().unwrap();
+            assert_eq!(state.addresses, vec!["addr1", "addr2"]);
+            assert_eq!(state.key_patterns, vec!["a*", "b*"]);
+
+            // same for the topics built from data
+            let data = TopicData::State(State::MultiPatterns(crate::StateMultiPatterns {
+                addresses: vec!["addr2".into(), "addr1".into(), "addr1".into()],
+                key_patterns: vec!["b*".into(), "a*".into()],
+            }));
+            assert_eq!(data.as_topic(), topic);
+
+            Ok(())
+        }
+
+        #[test]
+        fn topic_state_special_chars_test() -> anyhow::Result<()> {
+            let keys = [
+                "a/b",
+                "/",
+                "what?",
+                "#hash",
+                "100%",
+                "%2F",
+                "%%41",
+                "a/b?c=d#e%f g",
+                "ключ/значение",
+            ];
+            for key in keys {
+                let data = TopicData::State(State::Single(StateSingle {
+                    address: "some/address?".to_string(),
+                    key: key.to_string(),
+                }));
+                let uri = data.as_uri_string();
+
+                // format -> parse
+                let topic = Topic::parse_str(&uri)?;
+                assert_eq!(topic.to_string(), uri, "{}", key);
+                let parsed = topic.data();
+                let state = parsed.as_state_single().expect("state single");
+                assert_eq!(state.address, "some/address?");
+                assert_eq!(state.key, key);
+
+                // parse -> format -> parse
+                assert_eq!(parsed.as_uri_string(), uri, "{}", key);
+                assert_eq!(Topic::parse_str(&parsed.as_uri_string())?, topic);
+            }
+
+            // Literal slashes are segment separators, encoded ones are part of the key
+            let topic = Topic::parse_str("topic://state/addr/a%2Fb")?;
+            assert_eq!(topic.data().as_state_single().unwrap().key, "a/b");
+            let error = Topic::parse_str("topic://state/addr/a/b");
+            assert_eq!(error.unwrap_err(), TopicParseError::InvalidStateTopic);
+
+            Ok(())
+        }
+
+        #[test]
+        fn topic_parse_limits_test() -> anyhow::Result<()> {
+            fn state_uri(addresses: usize, patterns: usize, pattern: &str) -> String {
+                let addresses = (0..addresses).map(|i| format!("address__in[{i}]=addr{i}"));
+                let patterns = (0..patterns).map(|i| format!("key__match_any[{i}]={pattern}"));
+                let query = addresses.chain(patterns).collect::<Vec<_>>().join("&");
+                format!("topic://state?{query}")
+            }
+
+            let huge = format!("topic://test_resource/{}", "a".repeat(1024 * 1024));
+            let error = Topic::parse_str(&huge).unwrap_err();
+            assert_eq!(error, TopicParseError::UriTooLong(16 * 1024));
+            let huge = state_uri(100_000, 0, "");
+            assert_eq!(
+                Topic::parse_str(&huge).unwrap_err(),
+                TopicParseError::UriTooLong(16 * 1024)
+            );
+
+            Topic::parse_str(&state_uri(100, 100, "key*"))?;
+            assert_eq!(
+                Topic::parse_str(&state_uri(101, 1, "key*")).unwrap_err(),
+                TopicParseError::TooManyAddresses(100)
+            );
+            assert_eq!(
+                Topic::parse_str(&state_uri(1, 101, "key*")).unwrap_err(),
+                TopicParseError::TooManyKeyPatterns(100)
+            );
+
+            // the length of a pattern is checked after decoding
+            Topic::parse_str(&state_uri(1, 1, &"%2A".repeat(1024)))?;
+            assert_eq!(
+                Topic::parse_str(&state_uri(1, 1, &"k".repeat(1025))).unwrap_err(),
+                TopicParseError::KeyPatternTooLong(1024)
+            );
+
+            let options = TopicParseOptions {
+                max_uri_len: 64 * 1024,
+                max_addresses: 1000,
+                max_key_patterns: 2,
+                max_key_pattern_len: 4,
+                allow_unknown_kinds: false,
+            };
+            let uri = state_uri(1000, 2, "key*");
+            let topic = Topic::parse_str_with_options(&uri, &options)?;
+            assert!(Topic::parse_str(&uri).is_err());
+            assert_eq!(topic.kind(), TopicKind::State);
+            assert_eq!(topic.data().as_topic(), topic);
+            assert_eq!(
+                Topic::parse_str_with_options(&state_uri(1, 1, "key_*"), &options).unwrap_err(),
+                TopicParseError::KeyPatternTooLong(4)
+            );
+            assert_eq!(
+                Topic::parse_str_with_options(&state_uri(1, 3, "k"), &options).unwrap_err(),
+                TopicParseError::TooManyKeyPatterns(2)
+            );
+            Ok(())
+        }
+
         #[test]
         fn transaction_topic_test() -> anyhow::Result<()> {
             let topic_data =
@@ -680,14 +1112,125 @@ mod parse_and_format {
 
             Ok(())
         }
+
+        #[test]
+        fn test_resource_query_canonicalization() -> anyhow::Result<()> {
+            let t1 = Topic::parse_str("topic://test_resource/p?a=1&b=2")?;
+            let t2 = Topic::parse_str("topic://test_resource/p?b=2&a=1")?;
+            assert_eq!(t1, t2);
+            assert_eq!(t1.as_uri_string(), "topic://test_resource/p?a=1&b=2");
+
+            let hash = |t: &Topic| {
+                use std::hash::{Hash, Hasher};
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                t.hash(&mut hasher);
+                hasher.finish()
+            };
+            assert_eq!(hash(&t1), hash(&t2));
+
+            // Repeated keys are sorted by value
+            let t1 = Topic::parse_str("topic://test_resource/p?k=2&a=x&k=1")?;
+            let t2 = Topic::parse_str("topic://test_resource/p?k=1&k=2&a=x")?;
+            assert_eq!(t1, t2);
+            assert_eq!(t1.as_uri_string(), "topic://test_resource/p?a=x&k=1&k=2");
+
+            // Percent-encoded values are decoded and re-encoded consistently
+            let t1 = Topic::parse_str("topic://test_resource/p?v=a%20b&k=%2B")?;
+            let t2 = Topic::parse_str("topic://test_resource/p?k=%2b&v=a+b")?;
+            assert_eq!(t1, t2);
+            assert_eq!(t1.as_uri_string(), "topic://test_resource/p?k=%2B&v=a+b");
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_resource_query_access() -> anyhow::Result<()> {
+            let topic = Topic::parse_str("topic://test_resource/p?k=2&v=a%20b&k=1&e=")?;
+            let data = topic.data();
+            let res = data.as_test_resource().expect("test resource");
+            assert_eq!(
+                res.query_pairs(),
+                vec![
+                    ("e".to_string(), "".to_string()),
+                    ("k".to_string(), "1".to_string()),
+                    ("k".to_string(), "2".to_string()),
+                    ("v".to_string(), "a b".to_string()),
+                ]
+            );
+            assert_eq!(res.query_get("k").as_deref(), Some("1"));
+            assert_eq!(res.query_get("v").as_deref(), Some("a b"));
+            assert_eq!(res.query_get("e").as_deref(), Some(""));
+            assert_eq!(res.query_get("x"), None);
+
+            let res = TestResource {
+                path: "/p".to_string(),
+                query: None,
+            };
+            assert!(res.query_pairs().is_empty());
+            assert_eq!(res.query_get("k"), None);
+
+            Ok(())
+        }
+
+        #[test]
+        fn unknown_kind_test() -> anyhow::Result<()> {
+            use std::collections::HashSet;
+
+            let lenient = TopicParseOptions {
+                allow_unknown_kinds: true,
+                ..TopicParseOptions::default()
+            };
+            let uri = "topic://new_kind/some/path?b=2&a=1";
+
+            // strict by default
+            assert_eq!(
+                Topic::parse_str(uri).unwrap_err(),
+                TopicParseError::InvalidTopicKind(MaybeString(Some("new_kind".to_owned())))
+            );
+
+            let topic = Topic::parse_str_with_options(uri, &lenient)?;
+            assert_eq!(topic.kind(), TopicKind::Unknown);
+            assert_eq!(topic.kind_str(), "unknown");
+            assert_eq!(topic.raw_kind_str(), "new_kind");
+            assert!(!topic.is_multi_topic());
+            let data = topic.data();
+            assert_eq!(
+                data,
+                TopicData::Unknown {
+                    kind: "new_kind".to_owned(),
+                    path: "/some/path".to_owned(),
+                    query: Some("b=2&a=1".to_owned()),
+                }
+            );
+            assert!(!data.is_multi_topic());
+            assert_eq!(data.as_uri_string(), uri);
+            assert_eq!(data.as_topic(), topic);
+
+            // no trailing slash canonicalization
+            let data = Topic::parse_str_with_options("topic://new_kind/a/", &lenient)?.data();
+            assert_eq!(data.as_uri_string(), "topic://new_kind/a/");
+
+            // the known kinds are validated as usual
+            assert_eq!(
+                Topic::parse_str_with_options("topic://state/address", &lenient).unwrap_err(),
+                TopicParseError::InvalidStateTopic
+            );
+
+            let other = Topic::parse_str_with_options(uri, &lenient)?;
+            assert_eq!(topic, other);
+            assert_eq!(HashSet::from([topic, other]).len(), 1);
+            Ok(())
+        }
     }
 
     mod format {
         use crate::State;
         use std::fmt;
 
-        use super::super::{ConfigResource, Topic, TopicData, Transaction, TransactionType};
-        use super::{serde_state, url_escape};
+        use super::super::{
+            ConfigFormat, ConfigResource, Topic, TopicData, Transaction, TransactionType,
+        };
+        use super::{parse::config_query, serde_state, url_escape};
 
         impl fmt::Debug for Topic {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -701,6 +1244,16 @@ mod parse_and_format {
             }
         }
 
+        impl fmt::Display for ConfigFormat {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let s = match self {
+                    Self::Json => "json",
+                    Self::Yaml => "yaml",
+                };
+                write!(f, "{}", s)
+            }
+        }
+
         impl fmt::Display for TransactionType {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 let s = match self {
@@ -735,6 +1288,10 @@ mod parse_and_format {
                     TopicData::Config(ConfigResource { file }) => {
                         result.push_str("config");
                         result.push_str(file.path.as_str());
+                        if let Some(query) = config_query(file.version, file.format) {
+                            result.push('?');
+                            result.push_str(&query);
+                        }
                     }
                     TopicData::State(State::Single(state)) => {
                         let address = url_escape::encode(&state.address);
@@ -751,7 +1308,11 @@ mod parse_and_format {
                         result.push_str(test_res.path.as_str());
                         if let Some(ref query) = test_res.query {
                             result.push_str("?");
-                            result.push_str(query);
+                            if query.is_empty() {
+                                result.push_str(query);
+                            } else {
+                                result.push_str(&crate::canonical_query(query));
+                            }
                         }
                     }
                     TopicData::BlockchainHeight(_) => {
@@ -779,6 +1340,14 @@ mod parse_and_format {
                             pairs.amount_asset, pairs.price_asset
                         ));
                     }
+                    TopicData::Unknown { kind, path, query } => {
+                        result.push_str(kind);
+                        result.push_str(path);
+                        if let Some(query) = query {
+                            result.push('?');
+                            result.push_str(query);
+                        }
+                    }
                 }
                 result
             }
@@ -799,14 +1368,22 @@ mod parse_and_format {
         #[allow(non_snake_case)]
         #[derive(Serialize)]
         struct DataRef<'a> {
-            address__in: &'a [String],
-            key__match_any: &'a [String],
+            address__in: Vec<&'a str>,
+            key__match_any: Vec<&'a str>,
+        }
+
+        /// Sorted and deduplicated, so that equivalent subscriptions have the same topic
+        fn canonical<'a>(items: impl IntoIterator<Item = &'a String>) -> Vec<&'a str> {
+            let mut items = items.into_iter().map(String::as_str).collect::<Vec<_>>();
+            items.sort_unstable();
+            items.dedup();
+            items
         }
 
         pub(super) fn state_query_encode(v: &StateMultiPatterns) -> Result<String, ()> {
             let data = DataRef {
-                address__in: &v.addresses,
-                key__match_any: &v.key_patterns,
+                address__in: canonical(&v.addresses),
+                key__match_any: canonical(&v.key_patterns),
             };
 
             // Interestingly, this URL encoder does not replace '*' with '%2A' as per RFC-3986:
@@ -817,7 +1394,11 @@ mod parse_and_format {
         }
 
         pub(super) fn state_query_decode(s: &str) -> Result<StateMultiPatterns, ()> {
-            let data: Data = serde_qs::from_str(s).map_err(|_| ())?;
+            let mut data: Data = serde_qs::from_str(s).map_err(|_| ())?;
+            for items in [&mut data.address__in, &mut data.key__match_any] {
+                items.sort_unstable();
+                items.dedup();
+            }
             Ok(StateMultiPatterns {
                 addresses: data.address__in,
                 key_patterns: data.key__match_any,
@@ -888,6 +1469,8 @@ mod parse_and_format {
         fn topic_convert_test() -> anyhow::Result<()> {
             let urls = [
                 "topic://config/some/path",
+                "topic://config/some/path?version=42&format=yaml",
+                "topic://config/some/path?format=json",
                 "topic://state/address/key",
                 "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=pattern1&key__match_any[1]=pattern2",
                 "topic://test_resource/some/path?and_query=true",
@@ -940,12 +1523,45 @@ mod parse_and_format {
     }
 }
 
+/// Topics are ordered by their canonical URI string.
+///
+/// This ordering is stable (does not depend on the internal representation),
+/// so it is safe to rely on it in persistent storage.
+impl Ord for Topic {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.topic_url.as_str().cmp(other.topic_url.as_str())
+    }
+}
+
+impl PartialOrd for Topic {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Topic {
     pub fn kind(&self) -> TopicKind {
         // This is checked by `validate()` during parse stage, so `expect()` is safe
         let topic_kind_str = self.topic_url.host_str().expect("invariant broken: host");
-        // Same safety guarantee
-        TopicKind::parse(topic_kind_str).expect("invariant broken: topic_kind")
+        // Same check, an unknown kind could only pass it with `allow_unknown_kinds`
+        TopicKind::parse(topic_kind_str).unwrap_or(TopicKind::Unknown)
+    }
+
+    /// Canonical name of the topic kind, e.g. `"state"` or `"transactions"`.
+    /// Useful as a routing key or for logging.
+    ///
+    /// Same as `kind().as_str()`, so it's `"unknown"` for any `TopicKind::Unknown`.
+    pub fn kind_str(&self) -> &'static str {
+        self.kind().as_str()
+    }
+
+    /// Kind of the topic exactly as it appears in the URI.
+    ///
+    /// Same as `kind_str()` for the known kinds, while for `TopicKind::Unknown`
+    /// it's the actual kind, so the unknown kinds are still told apart.
+    pub fn raw_kind_str(&self) -> &str {
+        // This is checked by `validate()` during parse stage, so `expect()` is safe
+        self.topic_url.host_str().expect("invariant broken: host")
     }
 
     /// Whether this topic can be expanded to a set of other topics.
@@ -959,6 +1575,15 @@ impl Topic {
     pub fn data(&self) -> TopicData {
         TopicData::parse(self)
     }
+
+    /// The canonical form of this topic.
+    ///
+    /// A `Topic` can only be created by `parse_str`, which validates and canonicalizes the URL,
+    /// so every `Topic` is already canonical and this is just a cheap clone (of the `Arc` inside),
+    /// unlike the `topic.data().as_topic()` round-trip, which formats and re-parses the URL.
+    pub fn normalized(&self) -> Topic {
+        self.clone()
+    }
 }
 
 impl TopicData {
@@ -1035,10 +1660,122 @@ impl TopicData {
 
     pub fn as_topic(&self) -> Topic {
         let uri = self.as_uri_string();
-        Topic::parse_str(&uri).expect("internal error: can't parse URI created from TopicData")
+        // The limits only protect from untrusted input, this data is already parsed
+        Topic::parse_str_with_options(&uri, &TopicParseOptions::unlimited())
+            .expect("internal error: can't parse URI created from TopicData")
+    }
+}
+
+impl TestResource {
+    /// Decoded query parameters, in the canonical order (sorted by key, then by value).
+    /// Repeated keys are all preserved.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        match self.query {
+            Some(ref query) => {
+                let mut pairs = url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect::<Vec<_>>();
+                pairs.sort();
+                pairs
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Decoded value of the first query parameter with the given key
+    /// (the smallest one, if the key is repeated).
+    ///
+    /// Returns a `Cow` rather than `&str`: the query is stored form-urlencoded
+    /// (e.g. `a b` as `a+b`, `&` as `%26`), so a value can't always be borrowed as is.
+    /// It is borrowed from the stored query unless it had to be decoded.
+    pub fn query_get(&self, key: &str) -> Option<Cow<'_, str>> {
+        let query = self.query.as_deref()?;
+        url::form_urlencoded::parse(query.as_bytes())
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .min()
+    }
+}
+
+/// Query string with pairs sorted by key, then by value, and consistently re-encoded
+fn canonical_query(query: &str) -> String {
+    let mut pairs = url::form_urlencoded::parse(query.as_bytes()).collect::<Vec<_>>();
+    pairs.sort();
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+impl Transaction {
+    /// Grouping key of this transaction topic, which ignores the subscriber's address.
+    pub fn grouping_key(&self) -> TransactionGroupKey {
+        match self {
+            Transaction::ByAddress(tx) => tx.grouping_key(),
+            Transaction::Exchange(tx) => tx.grouping_key(),
+        }
+    }
+}
+
+impl TransactionByAddress {
+    pub fn grouping_key(&self) -> TransactionGroupKey {
+        TransactionGroupKey::ByType(self.tx_type)
+    }
+}
+
+impl TransactionExchange {
+    pub fn grouping_key(&self) -> TransactionGroupKey {
+        TransactionGroupKey::ExchangePair {
+            amount_asset: self.amount_asset.clone(),
+            price_asset: self.price_asset.clone(),
+        }
     }
 }
 
+#[test]
+fn test_transaction_grouping_key() -> anyhow::Result<()> {
+    use std::collections::HashMap;
+
+    let tx_of = |topic_url: &str| -> anyhow::Result<Transaction> {
+        let data = Topic::parse_str(topic_url)?.data();
+        data.as_transaction()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("bad test case: {}", topic_url))
+    };
+
+    let by_addr1 = tx_of("topic://transactions?type=transfer&address=addr1")?;
+    let by_addr2 = tx_of("topic://transactions?type=transfer&address=addr2")?;
+    let by_addr3 = tx_of("topic://transactions?type=issue&address=addr1")?;
+    let exchange1 = tx_of("topic://transactions?type=exchange&amount_asset=a&price_asset=p")?;
+    let exchange2 = tx_of("topic://transactions?type=exchange&amount_asset=p&price_asset=a")?;
+
+    assert_eq!(
+        by_addr1.grouping_key(),
+        TransactionGroupKey::ByType(TransactionType::Transfer)
+    );
+    assert_eq!(by_addr1.grouping_key(), by_addr2.grouping_key());
+    assert_ne!(by_addr1.grouping_key(), by_addr3.grouping_key());
+    assert_eq!(
+        exchange1.grouping_key(),
+        TransactionGroupKey::ExchangePair {
+            amount_asset: "a".to_string(),
+            price_asset: "p".to_string(),
+        }
+    );
+    assert_ne!(exchange1.grouping_key(), exchange2.grouping_key());
+
+    let mut groups = HashMap::<TransactionGroupKey, Vec<Transaction>>::new();
+    for tx in [by_addr1, by_addr2, by_addr3, exchange1.clone(), exchange1] {
+        groups.entry(tx.grouping_key()).or_default().push(tx);
+    }
+    assert_eq!(groups.len(), 3);
+    assert_eq!(
+        groups[&TransactionGroupKey::ByType(TransactionType::Transfer)].len(),
+        2
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_eq_and_hash() -> anyhow::Result<()> {
     let hash = |topic: &Topic| {
@@ -1075,6 +1812,698 @@ fn test_eq_and_hash() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_trailing_slash() -> anyhow::Result<()> {
+    let hash = |topic: &Topic| {
+        use std::{
+            collections::hash_map::DefaultHasher,
+            hash::{Hash, Hasher},
+        };
+        let mut hasher = DefaultHasher::new();
+        topic.hash(&mut hasher);
+        hasher.finish()
+    };
+    let topic_urls = [
+        "topic://leasing_balance/some_address",
+        "topic://state/address/key",
+        "topic://config/some/path",
+        "topic://config/some/path?version=1",
+        "topic://pairs/amount_asset/price_asset",
+    ];
+    for topic_url in topic_urls {
+        let with_slash = match topic_url.split_once('?') {
+            Some((path, query)) => format!("{}/?{}", path, query),
+            None => format!("{}/", topic_url),
+        };
+        let topic1 = Topic::parse_str(topic_url)?;
+        let topic2 = Topic::parse_str(&with_slash)?;
+
+        assert_eq!(topic1, topic2, "{}", with_slash);
+        assert_eq!(hash(&topic1), hash(&topic2));
+        assert_eq!(topic2.to_string(), topic_url);
+        assert_eq!(topic2.data().as_uri_string(), topic_url);
+        assert_eq!(topic2.data(), topic1.data());
+    }
+
+    // Only a single trailing slash is ignored, extra segments are still rejected
+    let invalid_urls = [
+        "topic://leasing_balance/some_address//",
+        "topic://leasing_balance/some_address/extra/",
+        "topic://state/address/key//",
+        "topic://state/address/key/extra/",
+        "topic://pairs/amount_asset/price_asset//",
+        "topic://pairs/amount_asset/price_asset/extra/",
+    ];
+    for topic_url in invalid_urls {
+        assert!(Topic::parse_str(topic_url).is_err(), "{}", topic_url);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_ord() -> anyhow::Result<()> {
+    use std::collections::BTreeMap;
+
+    let topic_urls = [
+        "topic://transactions?type=all&address=some_address",
+        "topic://state/address/key",
+        "topic://blockchain_height",
+        "topic://config/some/path",
+        "topic://state/address/another_key",
+    ];
+    let mut topics = topic_urls
+        .iter()
+        .map(|s| Topic::parse_str(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    topics.sort();
+
+    let mut expected = topic_urls.to_vec();
+    expected.sort();
+    let actual = topics.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    assert_eq!(actual, expected);
+
+    let mut subscriptions = BTreeMap::new();
+    for (i, topic) in topics.into_iter().enumerate() {
+        subscriptions.insert(topic, i);
+    }
+    let topic = Topic::parse_str("topic://state/address/key")?;
+    assert_eq!(subscriptions.get(&topic), Some(&3));
+    assert_eq!(
+        Topic::parse_str("topic://config/some/path")?
+            .cmp(&Topic::parse_str("topic://config/some/path")?),
+        Ordering::Equal
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_normalized() -> anyhow::Result<()> {
+    let topic_urls = [
+        "topic://state?address__in[]=addr1&address__in[]=addr2&key__match_any[]=*",
+        "topic://test_resource/p?b=2&a=1",
+        "topic://transactions?type=all&address=some_address",
+        "topic://config/some/path",
+    ];
+    for url in topic_urls {
+        let topic = Topic::parse_str(url)?;
+        let normalized = topic.normalized();
+        // No reparse: the very same URL is shared
+        assert!(Arc::ptr_eq(&topic.topic_url, &normalized.topic_url));
+        // ...and it is the same as a full round-trip through `TopicData`
+        assert_eq!(normalized, topic.data().as_topic());
+    }
+
+    Ok(())
+}
+
+/// Compact binary encoding of the topic data, to be used in storage.
+///
+/// Format: a tag byte identifying the topic variant, followed by the variant's fields.
+/// Strings are encoded as a length (unsigned LEB128 varint) followed by UTF-8 bytes,
+/// lists of strings as a number of items (same varint) followed by the items,
+/// optional strings as a presence byte (0 or 1) followed by the string if present,
+/// optional numbers the same way, with the number encoded as a varint.
+///
+/// Tag bytes and transaction type codes are part of the format and must never be changed or reused.
+mod codec {
+    use super::{
+        BlockchainHeight, ConfigFile, ConfigFormat, ConfigResource, ExchangePair, LeasingBalance,
+        State, StateMultiPatterns, StateSingle, TestResource, TopicData, Transaction,
+        TransactionByAddress, TransactionExchange, TransactionType,
+    };
+    use thiserror::Error;
+
+    #[derive(Debug, PartialEq, Eq, Error)]
+    pub enum TopicDecodeError {
+        #[error("Unexpected end of data")]
+        UnexpectedEof,
+
+        #[error("Unknown topic tag: {0}")]
+        UnknownTag(u8),
+
+        #[error("Unknown transaction type code: {0}")]
+        UnknownTransactionType(u8),
+
+        #[error("Unknown config format code: {0}")]
+        UnknownConfigFormat(u8),
+
+        #[error("Invalid length prefix")]
+        InvalidLength,
+
+        #[error("Invalid UTF-8 string")]
+        InvalidUtf8,
+
+        #[error("Malformed data")]
+        MalformedData,
+
+        #[error("Unexpected trailing data")]
+        TrailingData,
+    }
+
+    mod tag {
+        pub(super) const CONFIG: u8 = 1;
+        pub(super) const STATE_SINGLE: u8 = 2;
+        pub(super) const STATE_MULTI_PATTERNS: u8 = 3;
+        pub(super) const TEST_RESOURCE: u8 = 4;
+        pub(super) const BLOCKCHAIN_HEIGHT: u8 = 5;
+        pub(super) const TRANSACTION_BY_ADDRESS: u8 = 6;
+        pub(super) const TRANSACTION_EXCHANGE: u8 = 7;
+        pub(super) const LEASING_BALANCE: u8 = 8;
+        pub(super) const EXCHANGE_PAIR: u8 = 9;
+        /// Config with version and/or format, `CONFIG` is kept for the plain ones
+        pub(super) const CONFIG_WITH_PARAMS: u8 = 10;
+        pub(super) const UNKNOWN: u8 = 11;
+    }
+
+    impl TopicData {
+        /// Encode topic data into a compact binary form, see `from_bytes` for the reverse.
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut w = Writer(Vec::with_capacity(64));
+            match self {
+                TopicData::Config(ConfigResource { file })
+                    if file.version.is_none() && file.format.is_none() =>
+                {
+                    w.u8(tag::CONFIG);
+                    w.str(&file.path);
+                }
+                TopicData::Config(ConfigResource { file }) => {
+                    w.u8(tag::CONFIG_WITH_PARAMS);
+                    w.str(&file.path);
+                    w.opt_u64(file.version);
+                    w.u8(file.format.map_or(0, ConfigFormat::code));
+                }
+                TopicData::State(State::Single(state)) => {
+                    w.u8(tag::STATE_SINGLE);
+                    w.str(&state.address);
+                    w.str(&state.key);
+                }
+                TopicData::State(State::MultiPatterns(state)) => {
+                    w.u8(tag::STATE_MULTI_PATTERNS);
+                    w.strs(&state.addresses);
+                    w.strs(&state.key_patterns);
+                }
+                TopicData::TestResource(test_res) => {
+                    w.u8(tag::TEST_RESOURCE);
+                    w.str(&test_res.path);
+                    w.opt_str(test_res.query.as_deref());
+                }
+                TopicData::BlockchainHeight(_) => {
+                    w.u8(tag::BLOCKCHAIN_HEIGHT);
+                }
+                TopicData::Transaction(Transaction::ByAddress(tx)) => {
+                    w.u8(tag::TRANSACTION_BY_ADDRESS);
+                    w.u8(tx.tx_type.code());
+                    w.str(&tx.address);
+                }
+                TopicData::Transaction(Transaction::Exchange(tx)) => {
+                    w.u8(tag::TRANSACTION_EXCHANGE);
+                    w.str(&tx.amount_asset);
+                    w.str(&tx.price_asset);
+                }
+                TopicData::LeasingBalance(lb) => {
+                    w.u8(tag::LEASING_BALANCE);
+                    w.str(&lb.address);
+                }
+                TopicData::ExchangePair(pair) => {
+                    w.u8(tag::EXCHANGE_PAIR);
+                    w.str(&pair.amount_asset);
+                    w.str(&pair.price_asset);
+                }
+                TopicData::Unknown { kind, path, query } => {
+                    w.u8(tag::UNKNOWN);
+                    w.str(kind);
+                    w.str(path);
+                    w.opt_str(query.as_deref());
+                }
+            }
+            w.0
+        }
+
+        /// Decode topic data previously encoded with `to_bytes`.
+        ///
+        /// Malformed or unknown (e.g. written by a newer version) data results in an error.
+        /// Note that the topic contents are not validated the way `Topic::parse_str` does it,
+        /// so only data produced by `to_bytes` is expected here.
+        pub fn from_bytes(bytes: &[u8]) -> Result<TopicData, TopicDecodeError> {
+            let mut r = Reader(bytes);
+            let data = match r.u8()? {
+                tag::CONFIG => TopicData::Config(ConfigResource {
+                    file: ConfigFile {
+                        path: r.string()?,
+                        version: None,
+                        format: None,
+                    },
+                }),
+                tag::CONFIG_WITH_PARAMS => {
+                    let path = r.string()?;
+                    let version = r.opt_u64()?;
+                    let format = match r.u8()? {
+                        0 => None,
+                        code => Some(
+                            ConfigFormat::from_code(code)
+                                .ok_or(TopicDecodeError::UnknownConfigFormat(code))?,
+                        ),
+                    };
+                    TopicData::Config(ConfigResource {
+                        file: ConfigFile {
+                            path,
+                            version,
+                            format,
+                        },
+                    })
+                }
+                tag::STATE_SINGLE => TopicData::State(State::Single(StateSingle {
+                    address: r.string()?,
+                    key: r.string()?,
+                })),
+                tag::STATE_MULTI_PATTERNS => {
+                    TopicData::State(State::MultiPatterns(StateMultiPatterns {
+                        addresses: r.strings()?,
+                        key_patterns: r.strings()?,
+                    }))
+                }
+                tag::TEST_RESOURCE => TopicData::TestResource(TestResource {
+                    path: r.string()?,
+                    query: r.opt_string()?,
+                }),
+                tag::BLOCKCHAIN_HEIGHT => TopicData::BlockchainHeight(BlockchainHeight),
+                tag::TRANSACTION_BY_ADDRESS => {
+                    let code = r.u8()?;
+                    let tx_type = TransactionType::from_code(code)
+                        .ok_or(TopicDecodeError::UnknownTransactionType(code))?;
+                    TopicData::Transaction(Transaction::ByAddress(TransactionByAddress {
+                        tx_type,
+                        address: r.string()?,
+                    }))
+                }
+                tag::TRANSACTION_EXCHANGE => {
+                    TopicData::Transaction(Transaction::Exchange(TransactionExchange {
+                        amount_asset: r.string()?,
+                        price_asset: r.string()?,
+                    }))
+                }
+                tag::LEASING_BALANCE => TopicData::LeasingBalance(LeasingBalance {
+                    address: r.string()?,
+                }),
+                tag::EXCHANGE_PAIR => TopicData::ExchangePair(ExchangePair {
+                    amount_asset: r.string()?,
+                    price_asset: r.string()?,
+                }),
+                tag::UNKNOWN => TopicData::Unknown {
+                    kind: r.string()?,
+                    path: r.string()?,
+                    query: r.opt_string()?,
+                },
+                unknown => return Err(TopicDecodeError::UnknownTag(unknown)),
+            };
+            if !r.0.is_empty() {
+                return Err(TopicDecodeError::TrailingData);
+            }
+            Ok(data)
+        }
+    }
+
+    impl ConfigFormat {
+        /// Binary code of the config format, 0 is reserved for 'not set'
+        fn code(self) -> u8 {
+            match self {
+                ConfigFormat::Json => 1,
+                ConfigFormat::Yaml => 2,
+            }
+        }
+
+        fn from_code(code: u8) -> Option<Self> {
+            match code {
+                1 => Some(ConfigFormat::Json),
+                2 => Some(ConfigFormat::Yaml),
+                _ => None,
+            }
+        }
+    }
+
+    impl TransactionType {
+        /// Binary code of the transaction type (same as the transaction type number in Waves, 0 for 'all').
+        fn code(self) -> u8 {
+            match self {
+                TransactionType::All => 0,
+                TransactionType::Genesis => 1,
+                TransactionType::Payment => 2,
+                TransactionType::Issue => 3,
+                TransactionType::Transfer => 4,
+                TransactionType::Reissue => 5,
+                TransactionType::Burn => 6,
+                TransactionType::Exchange => 7,
+                TransactionType::Lease => 8,
+                TransactionType::LeaseCancel => 9,
+                TransactionType::Alias => 10,
+                TransactionType::MassTransfer => 11,
+                TransactionType::Data => 12,
+                TransactionType::SetScript => 13,
+                TransactionType::Sponsorship => 14,
+                TransactionType::SetAssetScript => 15,
+                TransactionType::InvokeScript => 16,
+                TransactionType::UpdateAssetInfo => 17,
+                TransactionType::InvokeExpression => 18,
+            }
+        }
+
+        fn from_code(code: u8) -> Option<Self> {
+            let tx_type = match code {
+                0 => TransactionType::All,
+                1 => TransactionType::Genesis,
+                2 => TransactionType::Payment,
+                3 => TransactionType::Issue,
+                4 => TransactionType::Transfer,
+                5 => TransactionType::Reissue,
+                6 => TransactionType::Burn,
+                7 => TransactionType::Exchange,
+                8 => TransactionType::Lease,
+                9 => TransactionType::LeaseCancel,
+                10 => TransactionType::Alias,
+                11 => TransactionType::MassTransfer,
+                12 => TransactionType::Data,
+                13 => TransactionType::SetScript,
+                14 => TransactionType::Sponsorship,
+                15 => TransactionType::SetAssetScript,
+                16 => TransactionType::InvokeScript,
+                17 => TransactionType::UpdateAssetInfo,
+                18 => TransactionType::InvokeExpression,
+                _ => return None,
+            };
+            Some(tx_type)
+        }
+    }
+
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn u8(&mut self, v: u8) {
+            self.0.push(v);
+        }
+
+        fn len(&mut self, len: usize) {
+            self.varint(u32::try_from(len).expect("length too big") as u64);
+        }
+
+        fn varint(&mut self, mut v: u64) {
+            loop {
+                let byte = (v & 0x7F) as u8;
+                v >>= 7;
+                if v == 0 {
+                    self.0.push(byte);
+                    break;
+                }
+                self.0.push(byte | 0x80);
+            }
+        }
+
+        fn str(&mut self, s: &str) {
+            self.len(s.len());
+            self.0.extend_from_slice(s.as_bytes());
+        }
+
+        fn strs(&mut self, v: &[String]) {
+            self.len(v.len());
+            for s in v {
+                self.str(s);
+            }
+        }
+
+        fn opt_str(&mut self, s: Option<&str>) {
+            match s {
+                None => self.u8(0),
+                Some(s) => {
+                    self.u8(1);
+                    self.str(s);
+                }
+            }
+        }
+
+        fn opt_u64(&mut self, v: Option<u64>) {
+            match v {
+                None => self.u8(0),
+                Some(v) => {
+                    self.u8(1);
+                    self.varint(v);
+                }
+            }
+        }
+    }
+
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+        fn u8(&mut self) -> Result<u8, TopicDecodeError> {
+            let (&byte, rest) = self
+                .0
+                .split_first()
+                .ok_or(TopicDecodeError::UnexpectedEof)?;
+            self.0 = rest;
+            Ok(byte)
+        }
+
+        fn len(&mut self) -> Result<usize, TopicDecodeError> {
+            // Lengths are limited to u32, which takes at most 5 bytes
+            let mut result = 0_u64;
+            for i in 0..5 {
+                let byte = self.u8()?;
+                result |= ((byte & 0x7F) as u64) << (7 * i);
+                if byte & 0x80 == 0 {
+                    let len = u32::try_from(result).map_err(|_| TopicDecodeError::InvalidLength)?;
+                    return Ok(len as usize);
+                }
+            }
+            Err(TopicDecodeError::InvalidLength)
+        }
+
+        fn bytes(&mut self, n: usize) -> Result<&'a [u8], TopicDecodeError> {
+            if self.0.len() < n {
+                return Err(TopicDecodeError::UnexpectedEof);
+            }
+            let (head, tail) = self.0.split_at(n);
+            self.0 = tail;
+            Ok(head)
+        }
+
+        fn string(&mut self) -> Result<String, TopicDecodeError> {
+            let len = self.len()?;
+            let bytes = self.bytes(len)?;
+            std::str::from_utf8(bytes)
+                .map(ToOwned::to_owned)
+                .map_err(|_| TopicDecodeError::InvalidUtf8)
+        }
+
+        fn strings(&mut self) -> Result<Vec<String>, TopicDecodeError> {
+            let count = self.len()?;
+            // Every item takes at least one byte, so don't trust the count blindly
+            // to avoid huge allocations on malformed data
+            if count > self.0.len() {
+                return Err(TopicDecodeError::UnexpectedEof);
+            }
+            let mut res = Vec::with_capacity(count);
+            for _ in 0..count {
+                res.push(self.string()?);
+            }
+            Ok(res)
+        }
+
+        fn opt_string(&mut self) -> Result<Option<String>, TopicDecodeError> {
+            match self.u8()? {
+                0 => Ok(None),
+                1 => Ok(Some(self.string()?)),
+                _ => Err(TopicDecodeError::MalformedData),
+            }
+        }
+
+        fn opt_u64(&mut self) -> Result<Option<u64>, TopicDecodeError> {
+            match self.u8()? {
+                0 => Ok(None),
+                1 => {
+                    // u64 takes at most 10 bytes, the last one holding a single bit
+                    let mut result = 0_u64;
+                    for i in 0..10 {
+                        let byte = self.u8()?;
+                        if i == 9 && byte > 1 {
+                            return Err(TopicDecodeError::MalformedData);
+                        }
+                        result |= ((byte & 0x7F) as u64) << (7 * i);
+                        if byte & 0x80 == 0 {
+                            return Ok(Some(result));
+                        }
+                    }
+                    Err(TopicDecodeError::MalformedData)
+                }
+                _ => Err(TopicDecodeError::MalformedData),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::{Topic, TopicData, TopicParseOptions};
+        use super::TopicDecodeError;
+
+        const TOPIC_URLS: &[&str] = &[
+            "topic://config/some/path",
+            "topic://config/some/path?version=42&format=yaml",
+            "topic://config/some/path?version=18446744073709551615",
+            "topic://config/some/path?format=json",
+            "topic://state/address/key",
+            "topic://state?address__in[0]=addr1&address__in[1]=addr2&key__match_any[0]=pattern1&key__match_any[1]=pattern*2",
+            "topic://state?address__in[0]=addr1&key__match_any[0]=pattern1&key__match_any[1]=pattern2&key__match_any[2]=pattern3",
+            "topic://test_resource/some/path?and_query=true",
+            "topic://test_resource/some/path",
+            "topic://blockchain_height",
+            "topic://transactions?type=all&address=some_address",
+            "topic://transactions?type=invoke_expression&address=some_address",
+            "topic://transactions?type=exchange&amount_asset=foo&price_asset=bar",
+            "topic://leasing_balance/some_address",
+            "topic://pairs/amount_asset/price_asset",
+        ];
+
+        #[test]
+        fn round_trip() -> anyhow::Result<()> {
+            for topic_url in TOPIC_URLS {
+                let data = Topic::parse_str(topic_url)?.data();
+                let bytes = data.to_bytes();
+                assert!(bytes.len() < topic_url.len(), "not compact: {}", topic_url);
+                let decoded = TopicData::from_bytes(&bytes)?;
+                assert_eq!(decoded, data, "round trip failed: {}", topic_url);
+                assert_eq!(decoded.as_uri_string(), data.as_uri_string());
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn round_trip_unknown_kind() -> anyhow::Result<()> {
+            let data = TopicData::Unknown {
+                kind: "new_kind".to_owned(),
+                path: "/some/path".to_owned(),
+                query: Some("a=1".to_owned()),
+            };
+            assert_eq!(TopicData::from_bytes(&data.to_bytes())?, data);
+            Ok(())
+        }
+
+        #[test]
+        fn all_transaction_types() -> anyhow::Result<()> {
+            let types = [
+                "all",
+                "genesis",
+                "payment",
+                "issue",
+                "transfer",
+                "reissue",
+                "burn",
+                "exchange",
+                "lease",
+                "lease_cancel",
+                "alias",
+                "mass_transfer",
+                "data",
+                "set_script",
+                "sponsorship",
+                "set_asset_script",
+                "invoke_script",
+                "update_asset_info",
+                "invoke_expression",
+            ];
+            for tx_type in types {
+                let topic_url = format!("topic://transactions?type={}&address=addr", tx_type);
+                let data = Topic::parse_str(&topic_url)?.data();
+                let decoded = TopicData::from_bytes(&data.to_bytes())?;
+                assert_eq!(decoded, data, "round trip failed: {}", topic_url);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn long_strings() -> anyhow::Result<()> {
+            let key = "k".repeat(100_000);
+            let options = TopicParseOptions {
+                max_uri_len: 1024 * 1024,
+                ..TopicParseOptions::default()
+            };
+            let uri = format!("topic://state/address/{}", key);
+            let data = Topic::parse_str_with_options(&uri, &options)?.data();
+            let decoded = TopicData::from_bytes(&data.to_bytes())?;
+            assert_eq!(decoded, data);
+            Ok(())
+        }
+
+        #[test]
+        fn decode_errors() -> anyhow::Result<()> {
+            assert_eq!(
+                TopicData::from_bytes(&[]),
+                Err(TopicDecodeError::UnexpectedEof)
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[0]),
+                Err(TopicDecodeError::UnknownTag(0))
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[200, 1, 2, 3]),
+                Err(TopicDecodeError::UnknownTag(200))
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[6, 99, 1, b'a']),
+                Err(TopicDecodeError::UnknownTransactionType(99))
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[8, 2, 0xFF, 0xFE]),
+                Err(TopicDecodeError::InvalidUtf8)
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+                Err(TopicDecodeError::InvalidLength)
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[4, 1, b'/', 7]),
+                Err(TopicDecodeError::MalformedData)
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[10, 1, b'/', 0, 3]),
+                Err(TopicDecodeError::UnknownConfigFormat(3))
+            );
+            assert_eq!(
+                TopicData::from_bytes(&[
+                    10, 1, b'/', 1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02, 0
+                ]),
+                Err(TopicDecodeError::MalformedData)
+            );
+            // huge list length must not cause a huge allocation
+            assert_eq!(
+                TopicData::from_bytes(&[3, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+                Err(TopicDecodeError::UnexpectedEof)
+            );
+
+            let mut bytes = Topic::parse_str("topic://blockchain_height")?
+                .data()
+                .to_bytes();
+            bytes.push(0);
+            assert_eq!(
+                TopicData::from_bytes(&bytes),
+                Err(TopicDecodeError::TrailingData)
+            );
+
+            // Any truncated data must produce an error, not a panic
+            for topic_url in TOPIC_URLS {
+                let bytes = Topic::parse_str(topic_url)?.data().to_bytes();
+                for len in 0..bytes.len() {
+                    assert!(
+                        TopicData::from_bytes(&bytes[..len]).is_err(),
+                        "truncated data decoded: {}",
+                        topic_url
+                    );
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
 mod convert {
     use super::{
         BlockchainHeight, ConfigFile, ConfigResource, ExchangePair, LeasingBalance, State,
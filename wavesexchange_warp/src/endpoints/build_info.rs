@@ -0,0 +1,177 @@
+use prometheus::{IntGaugeVec, Opts};
+use serde::Serialize;
+use std::collections::HashMap;
+use warp::{Filter, Rejection, Reply};
+
+const VERSION_URL: &str = "version";
+const BUILD_INFO_URL: &str = "build_info";
+
+/// Build metadata of the service, served by the metrics instance at `GET /version` (and `GET /build_info`)
+/// and reported as the `build_info` gauge (value 1, fields as labels).
+///
+/// Usually created with the `build_info!()` macro.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: String,
+    pub commit: Option<String>,
+    pub built_at: Option<String>,
+    /// Any additional info, e.g. `rustc_version`.
+    /// Keys are used as metric label names, so any chars except `[a-zA-Z0-9_]` are replaced with `_`.
+    pub extra: HashMap<String, String>,
+}
+
+/// Create `BuildInfo` of the current crate.
+///
+/// Version is taken from `CARGO_PKG_VERSION`, other fields from the compile-time env variables
+/// (as set by `vergen` in the build script), if present:
+/// * `commit` - `VERGEN_GIT_SHA`;
+/// * `built_at` - `VERGEN_BUILD_TIMESTAMP`;
+/// * `extra["rustc_version"]` - `VERGEN_RUSTC_SEMVER`;
+/// * `extra["git_branch"]` - `VERGEN_GIT_BRANCH`.
+///
+/// ```no_run
+/// # use wavesexchange_warp::{build_info, MetricsWarpBuilder};
+/// # tokio_test::block_on(async {
+/// MetricsWarpBuilder::new().with_build_info(build_info!()).run_async().await;
+/// # })
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {{
+        let mut extra = ::std::collections::HashMap::new();
+        if let Some(rustc_version) = option_env!("VERGEN_RUSTC_SEMVER") {
+            extra.insert("rustc_version".to_string(), rustc_version.to_string());
+        }
+        if let Some(git_branch) = option_env!("VERGEN_GIT_BRANCH") {
+            extra.insert("git_branch".to_string(), git_branch.to_string());
+        }
+        $crate::endpoints::BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: option_env!("VERGEN_GIT_SHA").map(ToString::to_string),
+            built_at: option_env!("VERGEN_BUILD_TIMESTAMP").map(ToString::to_string),
+            extra,
+        }
+    }};
+}
+
+impl BuildInfo {
+    /// `build_info` gauge with all the fields as labels, set to 1
+    pub(crate) fn gauge(&self) -> IntGaugeVec {
+        let mut labels = vec![
+            ("version".to_string(), self.version.clone()),
+            (
+                "commit".to_string(),
+                self.commit.clone().unwrap_or_default(),
+            ),
+            (
+                "built_at".to_string(),
+                self.built_at.clone().unwrap_or_default(),
+            ),
+        ];
+        let mut extra = self
+            .extra
+            .iter()
+            .map(|(k, v)| (sanitize_label_name(k), v.clone()))
+            .filter(|(k, _)| !labels.iter().any(|(name, _)| name == k))
+            .collect::<Vec<_>>();
+        extra.sort();
+        extra.dedup_by(|(a, _), (b, _)| a == b);
+        labels.extend(extra);
+
+        let names = labels.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+        let values = labels.iter().map(|(_, v)| v.as_str()).collect::<Vec<_>>();
+        let gauge = IntGaugeVec::new(Opts::new("build_info", "Build info of the service"), &names)
+            .expect("build_info gauge");
+        gauge.with_label_values(&values).set(1);
+        gauge
+    }
+}
+
+fn sanitize_label_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+pub(crate) fn build_info_filter(
+    build_info: BuildInfo,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path(VERSION_URL)
+        .or(warp::path(BUILD_INFO_URL))
+        .unify()
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(move || warp::reply::json(&build_info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Registry, TextEncoder};
+    use serde_json::{json, Value};
+    use warp::test;
+
+    fn build_info() -> BuildInfo {
+        BuildInfo {
+            version: "1.2.3".to_string(),
+            commit: Some("0123abc".to_string()),
+            built_at: None,
+            extra: HashMap::from([("rustc-version".to_string(), "1.80.0".to_string())]),
+        }
+    }
+
+    #[tokio::test]
+    async fn check_version_endpoint() {
+        let filter = build_info_filter(build_info());
+        for path in ["/version", "/build_info"] {
+            let result = test::request().path(path).reply(&filter).await;
+            assert_eq!(result.status().as_u16(), 200);
+            let result = serde_json::from_slice::<Value>(result.body()).unwrap();
+            assert_eq!(
+                result,
+                json!({
+                    "version": "1.2.3",
+                    "commit": "0123abc",
+                    "built_at": null,
+                    "extra": { "rustc-version": "1.80.0" }
+                })
+            );
+        }
+
+        assert!(!test::request().path("/version/1").matches(&filter).await);
+        assert!(
+            !test::request()
+                .method("POST")
+                .path("/version")
+                .matches(&filter)
+                .await
+        );
+    }
+
+    #[test]
+    fn check_gauge() {
+        let registry = Registry::new();
+        registry.register(Box::new(build_info().gauge())).unwrap();
+        let metrics = TextEncoder::new()
+            .encode_to_string(&registry.gather())
+            .unwrap();
+        assert!(
+            metrics.contains(
+                r#"build_info{built_at="",commit="0123abc",rustc_version="1.80.0",version="1.2.3"} 1"#
+            ),
+            "{metrics}"
+        );
+    }
+
+    #[test]
+    fn check_macro() {
+        let info = crate::build_info!();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -1,5 +1,5 @@
 use serde::Serialize;
-use std::{fmt::Debug, future::Future};
+use std::{collections::BTreeMap, fmt::Debug, future::Future};
 use warp::{
     filters::BoxedFilter,
     http::StatusCode,
@@ -25,26 +25,70 @@ pub enum Readiness {
 pub trait Shared: Send + Sync + 'static {}
 impl<T> Shared for T where T: Send + Sync + 'static {}
 
+/// Result of the liveness checks: the overall status (first error, if any)
+/// and the individual results of the named checkers.
 #[derive(Clone)]
 pub(crate) struct LivenessReply {
     err: Option<String>,
+    checks: BTreeMap<String, String>,
 }
 
 impl LivenessReply {
     pub fn ok() -> Self {
-        Self { err: None }
+        Self {
+            err: None,
+            checks: BTreeMap::new(),
+        }
     }
 
     pub fn err<E: Debug>(msg: E) -> Self {
-        Self {
-            err: Some(format!("{msg:?}")),
+        Self::ok().with_err(msg)
+    }
+
+    /// Set the overall error, unless there already was one
+    fn with_err<E: Debug>(mut self, msg: E) -> Self {
+        if self.err.is_none() {
+            self.err = Some(format!("{msg:?}"));
+        }
+        self
+    }
+
+    fn with_check(mut self, name: String, result: Result<(), String>) -> Self {
+        let check = match result {
+            Ok(()) => "ok".to_owned(),
+            Err(err) => {
+                if self.err.is_none() {
+                    self.err = Some(format!("{name}: {err}"));
+                }
+                format!("fail: {err}")
+            }
+        };
+        self.checks.insert(name, check);
+        self
+    }
+
+    /// Same as `with_check`, but a failed check replaces the overall error, if there already was one
+    pub(crate) fn with_overriding_check(
+        mut self,
+        name: String,
+        result: Result<(), String>,
+    ) -> Self {
+        if let Err(err) = &result {
+            self.err = Some(format!("{name}: {err}"));
         }
+        self.with_check(name, result)
     }
 }
 
+/// Response body, e.g.
+/// `{ "status": "db: \"connection refused\"", "checks": { "db": "fail: \"connection refused\"", "upstream": "ok" } }`.
+///
+/// `checks` is omitted if there are no named checkers.
 #[derive(Serialize)]
 struct StatusResponse {
     status: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    checks: BTreeMap<String, String>,
 }
 
 impl Reply for StatusResponse {
@@ -55,14 +99,16 @@ impl Reply for StatusResponse {
 
 impl Reply for LivenessReply {
     fn into_response(self) -> Response {
+        let checks = self.checks;
         match self.err {
             Some(e) => with_status(
-                StatusResponse { status: e },
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusResponse { status: e, checks },
+                StatusCode::SERVICE_UNAVAILABLE,
             )
             .into_response(),
             None => StatusResponse {
                 status: "ok".to_owned(),
+                checks,
             }
             .into_response(),
         }
@@ -94,14 +140,36 @@ where
         Filter::boxed(self.and_then(move |hc: LivenessReply| {
             let checker = checker.clone();
             async move {
-                Ok::<_, Rejection>(match (hc.err, checker().await) {
-                    (None, Ok(_)) => LivenessReply::ok(),
-                    (Some(err), _) => LivenessReply::err(err),
-                    (_, Err(err)) => LivenessReply::err(err),
+                Ok::<_, Rejection>(match checker().await {
+                    Ok(_) => hc,
+                    Err(err) => hc.with_err(err),
                 })
             }
         }))
     }
+
+    /// Same as `with_checker`, but the result of the check is also reported
+    /// under the given name in the `checks` object of the response.
+    /// Can be called multiple times to register several checkers.
+    fn with_named_checker<F, C>(
+        self,
+        name: impl Into<String>,
+        checker: C,
+    ) -> BoxedFilter<(LivenessReply,)>
+    where
+        F: Future<Output = Result<(), E>> + Send,
+        C: FnOnce() -> F + Clone + Shared,
+    {
+        let name = name.into();
+        Filter::boxed(self.and_then(move |hc: LivenessReply| {
+            let checker = checker.clone();
+            let name = name.clone();
+            async move {
+                let result = checker().await.map_err(|err| format!("{err:?}"));
+                Ok::<_, Rejection>(hc.with_check(name, result))
+            }
+        }))
+    }
 }
 
 impl<F, E: Debug + Shared> Checkz<E> for F where
@@ -136,6 +204,52 @@ mod tests {
         assert_eq!(result["status"], "ok");
     }
 
+    #[tokio::test]
+    async fn check_named_checkers() {
+        let filter = readyz()
+            .with_named_checker("db", || async { Ok::<_, &str>(()) })
+            .with_named_checker("upstream", || async { Err("connection refused") })
+            .with_named_checker("cache", || async { Err("cache is cold") });
+        let result = test::request().path("/readyz").reply(&filter).await;
+        assert_eq!(result.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let result = serde_json::from_slice::<Value>(&result.into_body()).unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "status": "upstream: \"connection refused\"",
+                "checks": {
+                    "db": "ok",
+                    "upstream": "fail: \"connection refused\"",
+                    "cache": "fail: \"cache is cold\""
+                }
+            })
+        );
+
+        let filter = livez()
+            .with_named_checker("db", || async { Ok::<_, &str>(()) })
+            .with_named_checker("upstream", || async { Ok::<_, &str>(()) });
+        let result = test::request().path("/livez").reply(&filter).await;
+        assert_eq!(result.status(), StatusCode::OK);
+        let result = serde_json::from_slice::<Value>(&result.into_body()).unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "status": "ok",
+                "checks": { "db": "ok", "upstream": "ok" }
+            })
+        );
+
+        // unnamed checker fails the endpoint, but keeps the named results
+        let filter = startz()
+            .with_named_checker("db", || async { Ok::<_, &str>(()) })
+            .with_checker(|| async { Err("not enough racoons") });
+        let result = test::request().path("/startz").reply(&filter).await;
+        assert_eq!(result.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let result = serde_json::from_slice::<Value>(&result.into_body()).unwrap();
+        assert_eq!(result["status"], format!("{:?}", "not enough racoons"));
+        assert_eq!(result["checks"], serde_json::json!({ "db": "ok" }));
+    }
+
     #[tokio::test]
     async fn check_send_bounds() {
         #[derive(Clone)]
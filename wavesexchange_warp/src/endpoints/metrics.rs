@@ -1,16 +1,26 @@
+use super::build_info::{build_info_filter, BuildInfo};
 use super::liveness::{
     livez as livez_fn, readyz as readyz_fn, startz as startz_fn, Checkz, LivenessReply, Readiness,
     Shared,
 };
-use futures::future::{join, BoxFuture, FutureExt};
+use crate::extensions::Extensions;
+use crate::log::{log_access, AccessLogRecord};
+use futures::future::{join, BoxFuture, FutureExt, Shared as SharedFuture};
 use lazy_static::lazy_static;
-use prometheus::{core::Collector, HistogramOpts, HistogramVec, IntCounter, Registry, TextEncoder};
+use prometheus::{
+    core::Collector, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
 use std::{
     env,
     fmt::Debug,
     future::Future,
-    sync::{Arc, Mutex},
-    time::Instant,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 use tokio::{
     sync::{mpsc, oneshot},
@@ -20,18 +30,21 @@ use warp::{filters::BoxedFilter, log::Info, Filter, Rejection, Reply};
 use wavesexchange_log::info;
 
 lazy_static! {
-    static ref REQUESTS: IntCounter =
-        IntCounter::new("incoming_requests", "Incoming Requests").unwrap();
-    static ref RESPONSE_DURATION: HistogramVec = HistogramVec::new(
-        HistogramOpts::new("response_duration", "Response duration in secs"),
-        &["code", "method"]
-    )
-    .unwrap();
+    // shared by the builders without a metrics namespace
+    static ref DEFAULT_METRICS: RequestMetrics = RequestMetrics::new(None);
 }
 
 pub const DEFAULT_MAIN_ROUTES_PORT: u16 = 8080;
 pub const DEFAULT_METRICS_PORT_OFFSET: u16 = 1010;
 pub const METRICS_PORT_ENV: &str = "METRICS_PORT";
+pub const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+/// Name of the `/readyz` check registered by `with_init_channel`
+pub const INIT_CHECK_NAME: &str = "init";
+/// Name of the `/readyz` and `/livez` checks registered by `with_readiness_channel`
+pub const READINESS_CHECK_NAME: &str = "readiness";
+/// Name of the `/readyz` check failing during the drain period, see `with_drain_period`
+pub const DRAIN_CHECK_NAME: &str = "drain";
 
 pub trait SharedFilter<R, E: Into<Rejection> = Rejection>:
     Filter<Extract = (R,), Error = E> + Clone + Shared
@@ -45,16 +58,117 @@ where
 {
 }
 
-fn estimate_request(info: Info) {
-    REQUESTS.inc();
-    RESPONSE_DURATION
-        .with_label_values(&[info.status().as_str(), info.method().as_str()])
-        .observe(info.elapsed().as_secs_f64());
+/// Built-in collectors of the main routes requests
+#[derive(Clone)]
+struct RequestMetrics {
+    requests: IntCounter,
+    in_flight: IntGauge,
+    response_duration: HistogramVec,
+    http_responses: IntCounterVec,
+}
+
+impl RequestMetrics {
+    /// With the names prefixed by `<namespace>_`, if any
+    fn new(namespace: Option<&str>) -> Self {
+        let opts = |name: &str, help: &str| match namespace {
+            Some(namespace) => Opts::new(name, help).namespace(namespace),
+            None => Opts::new(name, help),
+        };
+        RequestMetrics {
+            requests: IntCounter::with_opts(opts("incoming_requests", "Incoming Requests"))
+                .unwrap(),
+            in_flight: IntGauge::with_opts(opts("in_flight_requests", "Requests being processed"))
+                .unwrap(),
+            response_duration: HistogramVec::new(
+                HistogramOpts::from(opts("response_duration", "Response duration in secs")),
+                &["code", "method"],
+            )
+            .unwrap(),
+            http_responses: IntCounterVec::new(
+                opts("http_responses_total", "Responses by status class"),
+                &["class"],
+            )
+            .unwrap(),
+        }
+    }
+
+    fn estimate_request(&self, info: &Info) {
+        self.requests.inc();
+        self.response_duration
+            .with_label_values(&[info.status().as_str(), info.method().as_str()])
+            .observe(info.elapsed().as_secs_f64());
+        self.http_responses
+            .with_label_values(&[&status_class(info.status())])
+            .inc();
+    }
+}
+
+/// Status class label, e.g. `2xx` for `200 OK`
+fn status_class(status: warp::http::StatusCode) -> String {
+    format!("{}xx", status.as_u16() / 100)
+}
+
+/// Keeps the `in_flight_requests` gauge incremented while alive,
+/// so the request is counted until it is completed, rejected or cancelled
+struct InFlightRequest(IntGauge);
+
+impl InFlightRequest {
+    fn start(gauge: IntGauge) -> Self {
+        gauge.inc();
+        InFlightRequest(gauge)
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
 }
 
+fn count_in_flight(routes: DeepBoxedFilter, gauge: IntGauge) -> DeepBoxedFilter {
+    warp::any()
+        .map(move || InFlightRequest::start(gauge.clone()))
+        .and(routes)
+        .map(|in_flight: InFlightRequest, reply: Box<dyn Reply>| {
+            drop(in_flight);
+            reply
+        })
+        .boxed()
+}
+
+/// Reset the built-in metrics of the builders without a metrics namespace.
+// Note: `in_flight_requests` is not reset, as it reflects requests being processed right now
 pub fn reset_metrics() {
-    REQUESTS.reset();
-    RESPONSE_DURATION.reset();
+    DEFAULT_METRICS.requests.reset();
+    DEFAULT_METRICS.response_duration.reset();
+    DEFAULT_METRICS.http_responses.reset();
+}
+
+/// Report the `drain` check in `/readyz`, which fails once `draining` is set,
+/// regardless of the other checks
+fn with_drain_check(
+    readyz: DeepBoxedFilter<LivenessReply>,
+    draining: Arc<AtomicBool>,
+) -> DeepBoxedFilter<LivenessReply> {
+    readyz
+        .map(move |reply: LivenessReply| {
+            let result = if draining.load(Ordering::Relaxed) {
+                Err(ServiceStatusError::Draining.to_string())
+            } else {
+                Ok(())
+            };
+            reply.with_overriding_check(DRAIN_CHECK_NAME.to_owned(), result)
+        })
+        .boxed()
+}
+
+/// Wait for the shutdown `signal`, then set `draining` and wait for the drain `period`,
+/// so the servers keep serving the requests meanwhile
+async fn drain(signal: BoxFuture<'static, ()>, period: Duration, draining: Arc<AtomicBool>) {
+    signal.await;
+    info!("draining for {:?} before shutdown", period);
+    draining.store(true, Ordering::Relaxed);
+    tokio::time::sleep(period).await;
 }
 
 async fn metrics_handler(reg: Registry) -> impl Reply {
@@ -62,6 +176,7 @@ async fn metrics_handler(reg: Registry) -> impl Reply {
 }
 
 type DeepBoxedFilter<R = Box<dyn Reply>> = BoxedFilter<(R,)>;
+type ShutdownSignal = SharedFuture<BoxFuture<'static, ()>>;
 
 /// A warp wrapper that provides liveness endpoints (`livez/startz/readyz`)
 /// and extensible metrics collection for gathering requests (or any) statistics.
@@ -91,6 +206,13 @@ type DeepBoxedFilter<R = Box<dyn Reply>> = BoxedFilter<(R,)>;
 ///
 /// // run only metrics instance on port defined in the METRICS_PORT env variable
 /// MetricsWarpBuilder::new().with_metrics_port_from_env().run_async().await;
+///
+/// // serve metrics on the loopback interface only, and main routes on all interfaces
+/// MetricsWarpBuilder::new()
+///     .with_main_routes(routes)
+///     .with_metrics_bind_address([127, 0, 0, 1].into())
+///     .run_async()
+///     .await;
 /// # })
 /// ```
 pub struct MetricsWarpBuilder {
@@ -98,10 +220,22 @@ pub struct MetricsWarpBuilder {
     main_routes: Option<DeepBoxedFilter>,
     main_routes_port: Option<u16>,
     metrics_port: Option<u16>,
+    bind_address: Option<IpAddr>,
+    metrics_bind_address: Option<IpAddr>,
+    #[cfg(feature = "unix-socket")]
+    main_unix_socket: Option<std::path::PathBuf>,
     livez: DeepBoxedFilter<LivenessReply>,
     readyz: DeepBoxedFilter<LivenessReply>,
     startz: DeepBoxedFilter<LivenessReply>,
+    build_info: Option<BuildInfo>,
     graceful_shutdown_signal: Option<BoxFuture<'static, ()>>,
+    metrics_namespace: Option<String>,
+    liveness_routes: bool,
+    access_log: Option<fn(&AccessLogRecord)>,
+    drain_period: Option<Duration>,
+    extensions: Extensions,
+    #[cfg(feature = "otel")]
+    tracing: bool,
 }
 
 impl MetricsWarpBuilder {
@@ -111,11 +245,23 @@ impl MetricsWarpBuilder {
             main_routes: None,
             main_routes_port: None,
             metrics_port: None,
+            bind_address: None,
+            metrics_bind_address: None,
+            #[cfg(feature = "unix-socket")]
+            main_unix_socket: None,
             registry: Registry::new(),
             livez: livez_fn().boxed(),
             readyz: readyz_fn().boxed(),
             startz: startz_fn().boxed(),
+            build_info: None,
             graceful_shutdown_signal: None,
+            metrics_namespace: None,
+            liveness_routes: true,
+            access_log: None,
+            drain_period: None,
+            extensions: Extensions::default(),
+            #[cfg(feature = "otel")]
+            tracing: false,
         }
     }
 
@@ -144,6 +290,29 @@ impl MetricsWarpBuilder {
         self
     }
 
+    /// Define the address to bind both web-server instances to, `0.0.0.0` by default.
+    pub fn with_bind_address(mut self, address: IpAddr) -> Self {
+        self.bind_address = Some(address);
+        self
+    }
+
+    /// Define the address to bind the metrics web-server instance to,
+    /// overriding the one set with `with_bind_address`.
+    pub fn with_metrics_bind_address(mut self, address: IpAddr) -> Self {
+        self.metrics_bind_address = Some(address);
+        self
+    }
+
+    /// Serve main routes on a Unix domain socket at `path` instead of a TCP port.
+    ///
+    /// A stale socket file left at `path` is removed on startup, the socket file gets `0660` permissions
+    /// and is removed after graceful shutdown.
+    #[cfg(feature = "unix-socket")]
+    pub fn with_main_unix_socket(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.main_unix_socket = Some(path.into());
+        self
+    }
+
     /// Use `METRICS_PORT` env variable as the port number of the metrics web-server instance, if set.
     /// If the env variable is not set, use default port number which is the main port number + 1010.
     pub fn with_metrics_port_from_env(mut self) -> Self {
@@ -154,6 +323,7 @@ impl MetricsWarpBuilder {
         self
     }
 
+    /// Set the `/livez` checker, replacing all the previously registered ones (including named).
     pub fn with_livez_checker<F, C, E>(mut self, checker: C) -> Self
     where
         E: Debug + Shared,
@@ -164,6 +334,7 @@ impl MetricsWarpBuilder {
         self
     }
 
+    /// Set the `/readyz` checker, replacing all the previously registered ones (including named).
     pub fn with_readyz_checker<F, C, E>(mut self, checker: C) -> Self
     where
         E: Debug + Shared,
@@ -174,6 +345,7 @@ impl MetricsWarpBuilder {
         self
     }
 
+    /// Set the `/startz` checker, replacing all the previously registered ones (including named).
     pub fn with_startz_checker<F, C, E>(mut self, checker: C) -> Self
     where
         E: Debug + Shared,
@@ -184,9 +356,46 @@ impl MetricsWarpBuilder {
         self
     }
 
+    /// Register a named `/livez` checker. Unlike `with_livez_checker`, multiple named checkers can be registered,
+    /// and the result of each of them is reported in the `checks` object of the response body:
+    /// `{ "status": "...", "checks": { "db": "ok", "upstream": "fail: ..." } }`.
+    pub fn with_named_livez_checker<F, C, E>(mut self, name: impl Into<String>, checker: C) -> Self
+    where
+        E: Debug + Shared,
+        F: Future<Output = Result<(), E>> + Send,
+        C: FnOnce() -> F + Clone + Shared,
+    {
+        self.livez = self.livez.with_named_checker(name, checker);
+        self
+    }
+
+    /// Register a named `/readyz` checker, see `with_named_livez_checker`.
+    pub fn with_named_readyz_checker<F, C, E>(mut self, name: impl Into<String>, checker: C) -> Self
+    where
+        E: Debug + Shared,
+        F: Future<Output = Result<(), E>> + Send,
+        C: FnOnce() -> F + Clone + Shared,
+    {
+        self.readyz = self.readyz.with_named_checker(name, checker);
+        self
+    }
+
+    /// Register a named `/startz` checker, see `with_named_livez_checker`.
+    pub fn with_named_startz_checker<F, C, E>(mut self, name: impl Into<String>, checker: C) -> Self
+    where
+        E: Debug + Shared,
+        F: Future<Output = Result<(), E>> + Send,
+        C: FnOnce() -> F + Clone + Shared,
+    {
+        self.startz = self.startz.with_named_checker(name, checker);
+        self
+    }
+
     /// Provide a oneshot channel for 'initialization finished' signal,
     /// once it is received the service will start to report that it is ready.
     ///
+    /// The check is reported as `init` in the `/readyz` response body.
+    ///
     /// Example:
     /// ```no_run
     /// use tokio::sync::oneshot;
@@ -220,22 +429,25 @@ impl MetricsWarpBuilder {
             }
         });
 
-        self.readyz = readyz_fn()
-            .with_checker(move || async move {
+        self.readyz = self
+            .readyz
+            .with_named_checker(INIT_CHECK_NAME, move || async move {
                 let is_initialized = is_initialized.lock().unwrap();
                 if *is_initialized {
                     Ok(())
                 } else {
                     Err(ServiceStatusError::InitInProgress)
                 }
-            })
-            .boxed();
+            });
 
         self
     }
 
     /// Provide a channel for readiness status changes.
     ///
+    /// The check is reported as `readiness` in the `/readyz` and `/livez` response bodies.
+    /// Use `ReadinessReporter` to debounce the status changes coming from several sources.
+    ///
     /// Example:
     /// ```no_run
     /// use tokio::sync::mpsc;
@@ -274,33 +486,29 @@ impl MetricsWarpBuilder {
             }
         });
 
-        self.readyz = readyz_fn()
-            .with_checker({
-                let readiness = readiness.clone();
-                move || async move {
-                    let readiness = readiness.lock().unwrap();
-                    if *readiness == Readiness::Ready {
-                        Ok(())
-                    } else {
-                        Err(ServiceStatusError::ServiceNotReady)
-                    }
+        self.readyz = self.readyz.with_named_checker(READINESS_CHECK_NAME, {
+            let readiness = readiness.clone();
+            move || async move {
+                let readiness = readiness.lock().unwrap();
+                if *readiness == Readiness::Ready {
+                    Ok(())
+                } else {
+                    Err(ServiceStatusError::ServiceNotReady)
                 }
-            })
-            .boxed();
-
-        self.livez = livez_fn()
-            .with_checker({
-                let readiness = readiness.clone();
-                move || async move {
-                    let readiness = readiness.lock().unwrap();
-                    if *readiness != Readiness::Dead {
-                        Ok(())
-                    } else {
-                        Err(ServiceStatusError::ServiceDead)
-                    }
+            }
+        });
+
+        self.livez = self.livez.with_named_checker(READINESS_CHECK_NAME, {
+            let readiness = readiness.clone();
+            move || async move {
+                let readiness = readiness.lock().unwrap();
+                if *readiness != Readiness::Dead {
+                    Ok(())
+                } else {
+                    Err(ServiceStatusError::ServiceDead)
                 }
-            })
-            .boxed();
+            }
+        });
 
         self
     }
@@ -323,6 +531,89 @@ impl MetricsWarpBuilder {
         self
     }
 
+    /// Don't serve the liveness endpoints (`livez/readyz/startz`) on the metrics instance,
+    /// e.g. when the health checks are handled elsewhere. The `with_*z_checker` methods,
+    /// `with_init_channel` and `with_readiness_channel` have no effect then.
+    pub fn without_liveness_routes(mut self) -> Self {
+        self.liveness_routes = false;
+        self
+    }
+
+    /// Log an `access` record (method, path, status, latency, request id, etc.)
+    /// with info level for every completed request to the main routes,
+    /// in the format configured for `wavesexchange_log`.
+    ///
+    /// The requests to the metrics instance are not logged.
+    pub fn with_access_log(mut self) -> Self {
+        self.access_log = Some(log_access);
+        self
+    }
+
+    /// Prefix the names of the built-in request metrics with `<namespace>_`
+    /// (e.g. `api_incoming_requests`), to tell apart the components running in one process.
+    ///
+    /// The metrics of a namespace are created by this builder, while the builders without
+    /// a namespace share the same metrics (which are reset by `reset_metrics`).
+    pub fn with_metrics_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.metrics_namespace = Some(namespace.into());
+        self
+    }
+
+    /// Record an OpenTelemetry server span of every request to the main routes (not to the metrics instance),
+    /// with the parent context extracted from the request headers (e.g. `traceparent`/`tracestate`).
+    ///
+    /// The spans are created by the global tracer provider after the response,
+    /// and the context is extracted by the global propagator, both have to be set up by the application.
+    #[cfg(feature = "otel")]
+    pub fn with_tracing(mut self) -> Self {
+        self.tracing = true;
+        self
+    }
+
+    /// Register `value` to be extracted by the `extension` filter in the main routes,
+    /// replacing the previously registered value of the same type.
+    pub fn with_extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Filter extracting the extension of type `T` registered with `with_extension`,
+    /// rejecting with `extensions::MissingExtension` (an internal error) if there is none.
+    ///
+    /// Register the extensions before building the routes with this filter,
+    /// the value is taken when the filter is created.
+    pub fn extension<T: Send + Sync + 'static>(
+        &self,
+    ) -> impl Filter<Extract = (Arc<T>,), Error = Rejection> + Clone {
+        self.extensions.extension::<T>()
+    }
+
+    /// Serve the build info at `GET /version` (and `GET /build_info`) of the metrics instance,
+    /// and register the `build_info` gauge with the build info fields as labels.
+    ///
+    /// Use `build_info!()` macro to get the build info of the service:
+    /// ```no_run
+    /// # use wavesexchange_warp::{build_info, MetricsWarpBuilder};
+    /// # let builder = MetricsWarpBuilder::new();
+    /// builder.with_build_info(build_info!());
+    /// ```
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self = self.with_metric(&build_info.gauge());
+        self.build_info = Some(build_info);
+        self
+    }
+
+    /// On the graceful shutdown signal, first fail the `drain` check of `/readyz`
+    /// (overriding the other readiness checks), keep serving the requests for the drain `period`,
+    /// and only then shut down both servers. This gives the load balancer time
+    /// to stop routing new requests to the instance before it stops accepting connections.
+    ///
+    /// Has no effect without `with_graceful_shutdown`.
+    pub fn with_drain_period(mut self, period: Duration) -> Self {
+        self.drain_period = Some(period);
+        self
+    }
+
     pub fn with_graceful_shutdown<F>(mut self, signal: F) -> Self
     where
         F: Future<Output = ()> + Send + 'static,
@@ -337,64 +628,172 @@ impl MetricsWarpBuilder {
     /// and the other on a separate task, to avoid any interference between them
     /// (e.g. programming errors in web handlers in main server will not affect the metrics server).
     pub async fn run_async(mut self) {
+        let metrics = match &self.metrics_namespace {
+            Some(namespace) => RequestMetrics::new(Some(namespace)),
+            None => DEFAULT_METRICS.clone(),
+        };
         self = self
-            .with_metric(&*REQUESTS)
-            .with_metric(&*RESPONSE_DURATION);
+            .with_metric(&metrics.requests)
+            .with_metric(&metrics.in_flight)
+            .with_metric(&metrics.response_duration)
+            .with_metric(&metrics.http_responses);
 
+        let (main_addr, metrics_addr) = self.socket_addrs();
         let Self {
             main_routes,
-            main_routes_port,
-            metrics_port,
             registry,
             livez,
             readyz,
             startz,
+            build_info,
             graceful_shutdown_signal,
+            liveness_routes,
+            access_log,
+            drain_period,
+            #[cfg(feature = "unix-socket")]
+            main_unix_socket,
+            #[cfg(feature = "otel")]
+            tracing,
+            ..
         } = self;
 
-        let host = [0, 0, 0, 0];
-        let main_routes_port = main_routes_port.unwrap_or(DEFAULT_MAIN_ROUTES_PORT);
-        let metrics_port = metrics_port.unwrap_or(main_routes_port + DEFAULT_METRICS_PORT_OFFSET);
         let metrics_filter = warp::path!("metrics")
             .and(warp::get())
             .and(warp::any().map(move || registry.clone()))
             .then(metrics_handler);
 
-        let metrics_web_server = warp::serve(metrics_filter.or(livez).or(readyz).or(startz));
+        let version_filter = match build_info {
+            Some(build_info) => deep_box_filter(build_info_filter(build_info)),
+            None => deep_box_filter(
+                warp::any().and_then(|| async { Err::<String, _>(warp::reject::not_found()) }),
+            ),
+        };
+
+        let draining = Arc::new(AtomicBool::new(false));
+        let readyz = match drain_period {
+            Some(_) => with_drain_check(readyz, draining.clone()),
+            None => readyz,
+        };
+
+        let metrics_routes = if liveness_routes {
+            deep_box_filter(
+                metrics_filter
+                    .or(livez)
+                    .or(readyz)
+                    .or(startz)
+                    .or(version_filter),
+            )
+        } else {
+            deep_box_filter(metrics_filter.or(version_filter))
+        };
+        let signal = graceful_shutdown_signal
+            .map(|signal| match drain_period {
+                Some(period) => drain(signal, period, draining).boxed(),
+                None => signal,
+            })
+            .map(FutureExt::shared);
 
         match main_routes {
             Some(routes) => {
-                let main_web_server = warp::serve(routes.with(warp::log::custom(estimate_request)));
-
-                let (main_server, metrics_server) = match graceful_shutdown_signal {
-                    Some(signal) => {
-                        let signal = signal.shared();
-                        let (_addr, main_server) = main_web_server
-                            .bind_with_graceful_shutdown((host, main_routes_port), signal.clone());
-                        let (_addr, metrics_server) = metrics_web_server
-                            .bind_with_graceful_shutdown((host, metrics_port), signal);
-                        (main_server.boxed(), metrics_server.boxed())
+                let in_flight = metrics.in_flight.clone();
+                let log_request = move |info: Info| {
+                    metrics.estimate_request(&info);
+                    if let Some(log) = access_log {
+                        log(&AccessLogRecord::new(&info));
                     }
-                    None => {
-                        let main_server = main_web_server.run((host, main_routes_port));
-                        let metrics_server = metrics_web_server.run((host, metrics_port));
-                        (main_server.boxed(), metrics_server.boxed())
+                    #[cfg(feature = "otel")]
+                    if tracing {
+                        super::otel::trace_request(&info);
                     }
                 };
+                let routes = deep_box_filter(
+                    count_in_flight(routes, in_flight).with(warp::log::custom(log_request)),
+                );
+
+                #[cfg(feature = "unix-socket")]
+                let main_server = match main_unix_socket {
+                    Some(path) => serve_unix(routes, path, signal.clone()),
+                    None => serve_tcp(routes, main_addr, signal.clone()),
+                };
+                #[cfg(not(feature = "unix-socket"))]
+                let main_server = serve_tcp(routes, main_addr, signal.clone());
+                let metrics_server = serve_tcp(metrics_routes, metrics_addr, signal);
+
                 // Run both web-servers on different Tokio tasks to avoid any unanticipated interference
                 let metrics_server = task::spawn(metrics_server);
                 let ((), task_err) = join(main_server, metrics_server).await;
                 task_err.expect("metrics web-server panicked");
             }
-            None => match graceful_shutdown_signal {
-                Some(signal) => {
-                    let (_addr, metrics_server) = metrics_web_server
-                        .bind_with_graceful_shutdown((host, metrics_port), signal);
-                    metrics_server.await;
-                }
-                None => metrics_web_server.run((host, metrics_port)).await,
-            },
+            None => serve_tcp(metrics_routes, metrics_addr, signal).await,
+        }
+    }
+
+    fn socket_addrs(&self) -> (SocketAddr, SocketAddr) {
+        let main_routes_port = self.main_routes_port.unwrap_or(DEFAULT_MAIN_ROUTES_PORT);
+        let metrics_port = self
+            .metrics_port
+            .unwrap_or(main_routes_port + DEFAULT_METRICS_PORT_OFFSET);
+        let bind_address = self.bind_address.unwrap_or(DEFAULT_BIND_ADDRESS);
+        let metrics_bind_address = self.metrics_bind_address.unwrap_or(bind_address);
+        (
+            SocketAddr::new(bind_address, main_routes_port),
+            SocketAddr::new(metrics_bind_address, metrics_port),
+        )
+    }
+}
+
+fn serve_tcp(
+    routes: DeepBoxedFilter,
+    addr: SocketAddr,
+    signal: Option<ShutdownSignal>,
+) -> BoxFuture<'static, ()> {
+    let server = warp::serve(routes);
+    match signal {
+        Some(signal) => {
+            let (_addr, server) = server.bind_with_graceful_shutdown(addr, signal);
+            server.boxed()
+        }
+        None => server.run(addr).boxed(),
+    }
+}
+
+#[cfg(feature = "unix-socket")]
+fn serve_unix(
+    routes: DeepBoxedFilter,
+    path: std::path::PathBuf,
+    signal: Option<ShutdownSignal>,
+) -> BoxFuture<'static, ()> {
+    use std::{
+        fs,
+        os::unix::fs::{FileTypeExt, PermissionsExt},
+    };
+    use tokio::net::UnixListener;
+
+    match fs::symlink_metadata(&path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            info!("removing stale unix socket {:?}", path);
+            fs::remove_file(&path).expect("can't remove stale unix socket");
         }
+        _ => {}
+    }
+    let listener = UnixListener::bind(&path).expect("can't bind unix socket");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o660))
+        .expect("can't set unix socket permissions");
+
+    let incoming = Box::pin(futures::stream::unfold(listener, |listener| async move {
+        let stream = listener.accept().await.map(|(stream, _addr)| stream);
+        Some((stream, listener))
+    }));
+    let server = warp::serve(routes);
+    match signal {
+        Some(signal) => async move {
+            server
+                .serve_incoming_with_graceful_shutdown(incoming, signal)
+                .await;
+            let _ = fs::remove_file(&path);
+        }
+        .boxed(),
+        None => server.run_incoming(incoming).boxed(),
     }
 }
 
@@ -417,6 +816,9 @@ enum ServiceStatusError {
 
     #[error("service is dead")]
     ServiceDead,
+
+    #[error("service is draining before shutdown")]
+    Draining,
 }
 
 impl Debug for ServiceStatusError {
@@ -424,3 +826,482 @@ impl Debug for ServiceStatusError {
         f.write_str(&self.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use warp::test;
+
+    async fn get_json(filter: &DeepBoxedFilter<LivenessReply>, path: &str) -> (u16, Value) {
+        let result = test::request().path(path).reply(filter).await;
+        let status = result.status().as_u16();
+        (status, serde_json::from_slice(result.body()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn check_in_flight_requests() {
+        let routes = deep_box_filter(warp::path!("slow").and_then(|| async {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            Ok::<_, Rejection>("done")
+        }));
+        let metrics = RequestMetrics::new(None);
+        let routes = count_in_flight(routes, metrics.in_flight.clone());
+
+        assert_eq!(metrics.in_flight.get(), 0);
+        let (response, ()) = tokio::join!(test::request().path("/slow").reply(&routes), async {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            assert_eq!(metrics.in_flight.get(), 1);
+        });
+        assert_eq!(response.body(), "done");
+        assert_eq!(metrics.in_flight.get(), 0);
+
+        // rejected requests are not counted anymore as well
+        let response = test::request().path("/not_found").reply(&routes).await;
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(metrics.in_flight.get(), 0);
+    }
+
+    fn free_port() -> u16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[test]
+    fn check_bind_addresses() {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+
+        let builder = MetricsWarpBuilder::new();
+        assert_eq!(
+            builder.socket_addrs(),
+            (addr("0.0.0.0:8080"), addr("0.0.0.0:9090"))
+        );
+
+        let builder = MetricsWarpBuilder::new()
+            .with_bind_address(localhost)
+            .with_main_routes_port(1000);
+        assert_eq!(
+            builder.socket_addrs(),
+            (addr("127.0.0.1:1000"), addr("127.0.0.1:2010"))
+        );
+
+        let builder = MetricsWarpBuilder::new().with_metrics_bind_address(localhost);
+        assert_eq!(
+            builder.socket_addrs(),
+            (addr("0.0.0.0:8080"), addr("127.0.0.1:9090"))
+        );
+    }
+
+    #[tokio::test]
+    async fn check_bind_to_localhost() {
+        let port = free_port();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            MetricsWarpBuilder::new()
+                .with_bind_address([127, 0, 0, 1].into())
+                .with_metrics_port(port)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .run_async(),
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let response = reqwest::get(format!("http://127.0.0.1:{port}/livez"))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[cfg(feature = "unix-socket")]
+    #[tokio::test]
+    async fn check_main_unix_socket() {
+        use std::{
+            fs,
+            io::{Read, Write},
+            os::unix::{
+                fs::PermissionsExt,
+                net::{UnixListener, UnixStream},
+            },
+        };
+
+        let path = env::temp_dir().join(format!("wx_warp_test_{}.sock", std::process::id()));
+        // stale socket file of a previous run
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            MetricsWarpBuilder::new()
+                .with_main_routes(warp::path!("hello").map(|| "hello"))
+                .with_main_unix_socket(&path)
+                .with_bind_address([127, 0, 0, 1].into())
+                .with_metrics_port(free_port())
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .run_async(),
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+
+        let socket_path = path.clone();
+        let response = tokio::task::spawn_blocking(move || {
+            let mut stream = UnixStream::connect(socket_path).unwrap();
+            stream
+                .write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        })
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("hello"), "{response}");
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn check_handler_timeout() {
+        use crate::timeout::with_timeout;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static SLOW_DONE: AtomicBool = AtomicBool::new(false);
+
+        let slow = warp::path!("slow").and_then(|| {
+            with_timeout(Duration::from_millis(100), 95, async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                SLOW_DONE.store(true, Ordering::SeqCst);
+                Ok::<_, Rejection>("slow")
+            })
+        });
+        let fast = warp::path!("fast").and_then(|| {
+            with_timeout(Duration::from_millis(100), 95, async {
+                Ok::<_, Rejection>("fast")
+            })
+        });
+        let routes = deep_box_filter(slow.or(fast)).with(warp::log::custom(|info| {
+            DEFAULT_METRICS.estimate_request(&info)
+        }));
+        let timed_out = || {
+            DEFAULT_METRICS
+                .response_duration
+                .with_label_values(&["504", "GET"])
+                .get_sample_count()
+        };
+        let timed_out_before = timed_out();
+
+        let started = Instant::now();
+        let response = test::request().path("/slow").reply(&routes).await;
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert_eq!(response.status().as_u16(), 504);
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["errors"][0]["code"], 950600);
+        assert_eq!(timed_out(), timed_out_before + 1);
+
+        // the handler is cancelled
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(!SLOW_DONE.load(Ordering::SeqCst));
+
+        let response = test::request().path("/fast").reply(&routes).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.body(), "fast");
+
+        let response = test::request().path("/not_found").reply(&routes).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    #[tokio::test]
+    async fn check_extensions_of_builder() {
+        struct Name(&'static str);
+
+        let routes = |builder: &MetricsWarpBuilder| {
+            warp::path!("name")
+                .and(builder.extension::<Name>())
+                .map(|name: Arc<Name>| name.0)
+        };
+        let first = MetricsWarpBuilder::new().with_extension(Name("first"));
+        let second = MetricsWarpBuilder::new().with_extension(Name("second"));
+
+        let response = test::request().path("/name").reply(&routes(&first)).await;
+        assert_eq!(response.body(), "first");
+        let response = test::request().path("/name").reply(&routes(&second)).await;
+        assert_eq!(response.body(), "second");
+
+        // not available to the routes of another builder
+        let response = test::request()
+            .path("/name")
+            .reply(&routes(&MetricsWarpBuilder::new()))
+            .await;
+        assert_eq!(response.status().as_u16(), 500);
+    }
+
+    #[tokio::test]
+    async fn check_status_class_counters() {
+        let metrics = RequestMetrics::new(None);
+        let routes = warp::path!("ok").map(|| "ok").with(warp::log::custom({
+            let metrics = metrics.clone();
+            move |info| metrics.estimate_request(&info)
+        }));
+        let count = |class: &str| metrics.http_responses.with_label_values(&[class]).get();
+
+        let response = test::request().path("/ok").reply(&routes).await;
+        assert_eq!(response.status().as_u16(), 200);
+        let response = test::request().path("/not_found").reply(&routes).await;
+        assert_eq!(response.status().as_u16(), 404);
+
+        assert_eq!(count("2xx"), 1);
+        assert_eq!(count("4xx"), 1);
+        assert_eq!(count("5xx"), 0);
+    }
+
+    #[tokio::test]
+    async fn check_metrics_namespace() {
+        let (main_port, metrics_port) = (free_port(), free_port());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            MetricsWarpBuilder::new()
+                .with_main_routes(warp::path!("hello").map(|| "hello"))
+                .with_metrics_namespace("api")
+                .with_bind_address([127, 0, 0, 1].into())
+                .with_main_routes_port(main_port)
+                .with_metrics_port(metrics_port)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .run_async(),
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let response = reqwest::get(format!("http://127.0.0.1:{main_port}/hello"))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let metrics = reqwest::get(format!("http://127.0.0.1:{metrics_port}/metrics"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(metrics.contains("\napi_incoming_requests 1\n"), "{metrics}");
+        assert!(
+            metrics.contains("\napi_in_flight_requests 0\n"),
+            "{metrics}"
+        );
+        assert!(metrics.contains(r#"api_http_responses_total{class="2xx"} 1"#));
+        assert!(metrics.contains(r#"api_response_duration_count{code="200",method="GET"} 1"#));
+        assert!(!metrics.contains("\nincoming_requests"), "{metrics}");
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_without_liveness_routes() {
+        let (main_port, metrics_port) = (free_port(), free_port());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            MetricsWarpBuilder::new()
+                .with_main_routes(warp::path!("hello").map(|| "hello"))
+                .without_liveness_routes()
+                .with_bind_address([127, 0, 0, 1].into())
+                .with_main_routes_port(main_port)
+                .with_metrics_port(metrics_port)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .run_async(),
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let status = |port: u16, path: &'static str| async move {
+            let response = reqwest::get(format!("http://127.0.0.1:{port}{path}"))
+                .await
+                .unwrap();
+            response.status().as_u16()
+        };
+        assert_eq!(status(main_port, "/hello").await, 200);
+        assert_eq!(status(metrics_port, "/metrics").await, 200);
+        for path in ["/livez", "/readyz", "/startz"] {
+            assert_eq!(status(metrics_port, path).await, 404, "{path}");
+        }
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    type CapturedAccess = (String, String, u16, Option<String>);
+
+    static CAPTURED_ACCESS: Mutex<Vec<CapturedAccess>> = Mutex::new(Vec::new());
+
+    fn capture_access(rec: &AccessLogRecord) {
+        CAPTURED_ACCESS.lock().unwrap().push((
+            rec.method.to_owned(),
+            rec.path.to_owned(),
+            rec.status,
+            rec.req_id.map(ToOwned::to_owned),
+        ));
+    }
+
+    #[tokio::test]
+    async fn check_access_log() {
+        let (main_port, metrics_port) = (free_port(), free_port());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let mut builder = MetricsWarpBuilder::new()
+            .with_main_routes(warp::path!("hello").map(|| "hello"))
+            .with_access_log()
+            .with_bind_address([127, 0, 0, 1].into())
+            .with_main_routes_port(main_port)
+            .with_metrics_port(metrics_port)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        assert!(builder.access_log.is_some());
+        builder.access_log = Some(capture_access);
+        let server = tokio::spawn(builder.run_async());
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let client = reqwest::Client::new();
+        let get = |port: u16, path: &'static str| {
+            client
+                .get(format!("http://127.0.0.1:{port}{path}"))
+                .header("x-request-id", "req-42")
+                .send()
+        };
+        assert_eq!(get(main_port, "/hello").await.unwrap().status(), 200);
+        assert_eq!(get(main_port, "/missing").await.unwrap().status(), 404);
+        assert_eq!(get(metrics_port, "/metrics").await.unwrap().status(), 200);
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap();
+
+        let captured = CAPTURED_ACCESS.lock().unwrap().clone();
+        let req_id = Some("req-42".to_string());
+        assert_eq!(
+            captured,
+            vec![
+                ("GET".into(), "/hello".into(), 200, req_id.clone()),
+                ("GET".into(), "/missing".into(), 404, req_id),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn check_drain_period() {
+        let (main_port, metrics_port) = (free_port(), free_port());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let (readiness_tx, readiness_rx) = mpsc::unbounded_channel();
+        let server = tokio::spawn(
+            MetricsWarpBuilder::new()
+                .with_main_routes(warp::path!("hello").map(|| "hello"))
+                .with_readiness_channel(readiness_rx)
+                .with_drain_period(Duration::from_millis(500))
+                .with_bind_address([127, 0, 0, 1].into())
+                .with_main_routes_port(main_port)
+                .with_metrics_port(metrics_port)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .run_async(),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let get = |port: u16, path: &'static str| async move {
+            let response = reqwest::get(format!("http://127.0.0.1:{port}{path}"))
+                .await
+                .unwrap();
+            (response.status().as_u16(), response.text().await.unwrap())
+        };
+        let (status, body) = get(metrics_port, "/readyz").await;
+        assert_eq!(status, 200);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["checks"][DRAIN_CHECK_NAME], "ok");
+
+        shutdown_tx.send(()).unwrap();
+        readiness_tx.send(Readiness::NotReady).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (status, body) = get(metrics_port, "/readyz").await;
+        assert_eq!(status, 503);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["status"], "drain: service is draining before shutdown");
+        assert_eq!(
+            body["checks"][READINESS_CHECK_NAME],
+            "fail: service not ready"
+        );
+        assert_eq!(get(main_port, "/hello").await, (200, "hello".to_owned()));
+        assert!(!server.is_finished());
+
+        tokio::time::timeout(Duration::from_secs(2), server)
+            .await
+            .expect("servers are not shut down after the drain period")
+            .unwrap();
+        readiness_tx.send(Readiness::Ready).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_named_checkers() {
+        let (init_tx, init_rx) = oneshot::channel();
+        let (readiness_tx, readiness_rx) = mpsc::unbounded_channel();
+
+        let builder = MetricsWarpBuilder::new()
+            .with_named_readyz_checker("db", || async { Ok::<_, &str>(()) })
+            .with_init_channel(init_rx)
+            .with_readiness_channel(readiness_rx)
+            .with_named_livez_checker("upstream", || async { Err("connection refused") });
+
+        let (status, body) = get_json(&builder.readyz, "/readyz").await;
+        assert_eq!(status, 503);
+        assert_eq!(
+            body["checks"],
+            json!({
+                "db": "ok",
+                "init": "fail: service initialization in progress",
+                "readiness": "ok"
+            })
+        );
+
+        init_tx.send(()).unwrap();
+        readiness_tx.send(Readiness::NotReady).unwrap();
+        tokio::task::yield_now().await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let (status, body) = get_json(&builder.readyz, "/readyz").await;
+        assert_eq!(status, 503);
+        assert_eq!(body["status"], "readiness: service not ready");
+        assert_eq!(
+            body["checks"],
+            json!({
+                "db": "ok",
+                "init": "ok",
+                "readiness": "fail: service not ready"
+            })
+        );
+
+        let (status, body) = get_json(&builder.livez, "/livez").await;
+        assert_eq!(status, 503);
+        assert_eq!(
+            body["checks"],
+            json!({
+                "readiness": "ok",
+                "upstream": "fail: \"connection refused\""
+            })
+        );
+
+        readiness_tx.send(Readiness::Ready).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let (status, body) = get_json(&builder.readyz, "/readyz").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ok");
+    }
+}
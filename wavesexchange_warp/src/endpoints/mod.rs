@@ -1,5 +1,13 @@
+mod build_info;
 mod liveness;
 pub mod metrics;
+#[cfg(feature = "otel")]
+mod otel;
+mod readiness;
 
+pub use build_info::BuildInfo;
 pub use liveness::Readiness;
-pub use metrics::{MetricsWarpBuilder, DEFAULT_MAIN_ROUTES_PORT, DEFAULT_METRICS_PORT_OFFSET};
+pub use metrics::{
+    MetricsWarpBuilder, DEFAULT_BIND_ADDRESS, DEFAULT_MAIN_ROUTES_PORT, DEFAULT_METRICS_PORT_OFFSET,
+};
+pub use readiness::ReadinessReporter;
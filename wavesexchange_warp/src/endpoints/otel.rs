@@ -0,0 +1,102 @@
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    trace::{Span, SpanKind, Status, Tracer},
+    KeyValue,
+};
+use std::time::SystemTime;
+use warp::{http::HeaderMap, log::Info};
+
+const TRACER_NAME: &str = "wavesexchange_warp";
+
+/// Record a server span of the completed request, see `MetricsWarpBuilder::with_tracing`.
+///
+/// The parent context is extracted from the request headers by the global propagator
+/// (e.g. `traceparent`/`tracestate` with the W3C propagator).
+pub(super) fn trace_request(info: &Info) {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(info.request_headers()))
+    });
+    let tracer = global::tracer(TRACER_NAME);
+    let end = SystemTime::now();
+    let status = info.status();
+    let method = info.method().as_str().to_owned();
+    let mut span = tracer
+        .span_builder(method.clone())
+        .with_kind(SpanKind::Server)
+        .with_start_time(end - info.elapsed())
+        .with_attributes([
+            KeyValue::new("http.request.method", method),
+            KeyValue::new("url.path", info.path().to_owned()),
+            KeyValue::new("http.response.status_code", i64::from(status.as_u16())),
+        ])
+        .start_with_context(&tracer, &parent);
+    if status.is_server_error() {
+        span.set_status(Status::error(status.to_string()));
+    }
+    span.end_with_timestamp(end);
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_sdk::{
+        propagation::TraceContextPropagator, testing::trace::InMemorySpanExporter,
+        trace::TracerProvider,
+    };
+    use warp::{test, Filter};
+
+    #[tokio::test]
+    async fn should_create_span_with_extracted_trace_id() {
+        let exporter = InMemorySpanExporter::default();
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        global::set_tracer_provider(
+            TracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build(),
+        );
+
+        let routes = warp::path!("hello")
+            .map(|| "Hello, world!")
+            .with(warp::log::custom(|info| trace_request(&info)));
+        let result = test::request()
+            .path("/hello")
+            .header(
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .reply(&routes)
+            .await;
+        assert_eq!(result.status().as_u16(), 200);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.span_kind, SpanKind::Server);
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        assert_eq!(
+            span.parent_span_id,
+            SpanId::from_hex("b7ad6b7169203331").unwrap()
+        );
+        assert!(span
+            .attributes
+            .contains(&KeyValue::new("url.path", "/hello")));
+        assert!(span.start_time <= span.end_time);
+    }
+}
@@ -0,0 +1,212 @@
+use super::liveness::Readiness;
+use std::time::Duration;
+use tokio::{
+    sync::mpsc,
+    task,
+    time::{self, Instant},
+};
+use wavesexchange_log::info;
+
+/// Debouncing sender of readiness status changes for `MetricsWarpBuilder::with_readiness_channel`,
+/// to be shared (cloned) by several sources of readiness signals.
+///
+/// A status change is forwarded only after it has been stable for `min_hold`,
+/// except the change to `Dead`, which is forwarded immediately.
+/// Repeated reports of the same status are suppressed.
+///
+/// Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use tokio::sync::mpsc;
+/// use wavesexchange_warp::endpoints::{Readiness, ReadinessReporter};
+/// # use wavesexchange_warp::MetricsWarpBuilder;
+/// # let builder = MetricsWarpBuilder::new();
+/// # tokio_test::block_on(async {
+/// let (tx, rx) = mpsc::unbounded_channel();
+/// let server_future = builder.with_readiness_channel(rx);
+/// let reporter = ReadinessReporter::new(tx, Duration::from_secs(5));
+/// // . . . . .
+/// reporter.report_not_ready_for("consumer lag", Duration::from_secs(30)); // Ready again unless renewed
+/// // . . . . .
+/// reporter.report(Readiness::Dead); // Forwarded immediately
+/// # })
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReadinessReporter {
+    tx: mpsc::UnboundedSender<Report>,
+}
+
+#[derive(Debug)]
+enum Report {
+    Status(Readiness),
+    NotReadyFor(Duration),
+}
+
+impl ReadinessReporter {
+    /// Spawn a task forwarding the debounced status changes to `tx`, must be called within a tokio runtime.
+    ///
+    /// The initial status is `Ready`, same as of the readiness channel.
+    /// When all the reporters are dropped, the last reported status is forwarded without waiting.
+    pub fn new(tx: mpsc::UnboundedSender<Readiness>, min_hold: Duration) -> Self {
+        let (reports_tx, reports_rx) = mpsc::unbounded_channel();
+        task::spawn(debounce(reports_rx, tx, min_hold));
+        ReadinessReporter { tx: reports_tx }
+    }
+
+    pub fn report(&self, status: Readiness) {
+        // the task stops only when all the reporters are dropped
+        let _ = self.tx.send(Report::Status(status));
+    }
+
+    /// Report `NotReady` which reverts to the reported status (`Ready` by default)
+    /// after `ttl`, unless renewed by another call.
+    pub fn report_not_ready_for(&self, reason: impl AsRef<str>, ttl: Duration) {
+        info!("not ready for {:?}: {}", ttl, reason.as_ref());
+        let _ = self.tx.send(Report::NotReadyFor(ttl));
+    }
+}
+
+async fn debounce(
+    mut reports: mpsc::UnboundedReceiver<Report>,
+    tx: mpsc::UnboundedSender<Readiness>,
+    min_hold: Duration,
+) {
+    let mut forwarded = Readiness::Ready;
+    let mut reported = Readiness::Ready;
+    let mut not_ready_until = None::<Instant>;
+    let mut pending = None::<(Readiness, Instant)>;
+
+    loop {
+        let deadline = match (pending, not_ready_until) {
+            (Some((_, a)), Some(b)) => Some(a.min(b)),
+            (Some((_, a)), None) | (None, Some(a)) => Some(a),
+            (None, None) => None,
+        };
+        let report = match deadline {
+            Some(deadline) => time::timeout_at(deadline, reports.recv()).await.ok(),
+            None => Some(reports.recv().await),
+        };
+        match report {
+            Some(None) => break,
+            Some(Some(Report::Status(status))) => reported = status,
+            Some(Some(Report::NotReadyFor(ttl))) => not_ready_until = Some(Instant::now() + ttl),
+            None => {}
+        }
+
+        let now = Instant::now();
+        if not_ready_until.is_some_and(|until| until <= now) {
+            not_ready_until = None;
+        }
+        let status = match reported {
+            Readiness::Ready if not_ready_until.is_some() => Readiness::NotReady,
+            status => status,
+        };
+
+        if status == forwarded {
+            pending = None;
+            continue;
+        }
+        let since = match pending {
+            Some((pending_status, since)) if pending_status == status => since,
+            _ => now,
+        };
+        if status == Readiness::Dead || now >= since + min_hold {
+            if tx.send(status).is_err() {
+                return;
+            }
+            forwarded = status;
+            pending = None;
+        } else {
+            pending = Some((status, since));
+        }
+    }
+
+    if reported != forwarded {
+        let _ = tx.send(reported);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_HOLD: Duration = Duration::from_secs(1);
+
+    async fn advance(d: Duration) {
+        time::sleep(d).await;
+    }
+
+    fn drain(rx: &mut mpsc::UnboundedReceiver<Readiness>) -> Vec<Readiness> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_suppress_flapping() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let reporter = ReadinessReporter::new(tx, MIN_HOLD);
+
+        for _ in 0..5 {
+            reporter.report(Readiness::NotReady);
+            advance(Duration::from_millis(300)).await;
+            reporter.report(Readiness::Ready);
+            advance(Duration::from_millis(300)).await;
+        }
+        advance(MIN_HOLD * 2).await;
+        assert_eq!(drain(&mut rx), []);
+
+        reporter.report(Readiness::NotReady);
+        advance(Duration::from_millis(500)).await;
+        reporter.report(Readiness::NotReady);
+        assert_eq!(drain(&mut rx), []);
+        advance(Duration::from_millis(600)).await;
+        assert_eq!(drain(&mut rx), [Readiness::NotReady]);
+
+        reporter.report(Readiness::NotReady);
+        advance(MIN_HOLD * 2).await;
+        assert_eq!(drain(&mut rx), []);
+
+        reporter.report(Readiness::Ready);
+        advance(MIN_HOLD * 2).await;
+        assert_eq!(drain(&mut rx), [Readiness::Ready]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_forward_dead_immediately() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let reporter = ReadinessReporter::new(tx, MIN_HOLD);
+
+        reporter.report(Readiness::Dead);
+        advance(Duration::from_millis(1)).await;
+        assert_eq!(drain(&mut rx), [Readiness::Dead]);
+
+        reporter.report(Readiness::Dead);
+        advance(MIN_HOLD * 2).await;
+        assert_eq!(drain(&mut rx), []);
+
+        // the last reported status is forwarded when the reporters are dropped
+        reporter.report(Readiness::Ready);
+        drop(reporter);
+        advance(Duration::from_millis(1)).await;
+        assert_eq!(drain(&mut rx), [Readiness::Ready]);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_revert_not_ready_after_ttl() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let reporter = ReadinessReporter::new(tx, MIN_HOLD);
+        let ttl = Duration::from_secs(5);
+
+        reporter.report_not_ready_for("lag", ttl);
+        advance(Duration::from_secs(3)).await;
+        assert_eq!(drain(&mut rx), [Readiness::NotReady]);
+
+        // renewed
+        reporter.report_not_ready_for("lag", ttl);
+        advance(Duration::from_secs(4)).await;
+        assert_eq!(drain(&mut rx), []);
+
+        advance(Duration::from_secs(3)).await;
+        assert_eq!(drain(&mut rx), [Readiness::Ready]);
+    }
+}
@@ -14,38 +14,64 @@ mod offsets {
     pub const LIMITS: u32 = 9;
 }
 
+/// Stable machine-readable error kinds, serialized as `kind` next to the numeric `code`.
+///
+/// Unlike the codes, they don't depend on the service's code prefix,
+/// so clients can match on them without a lookup table.
+pub mod kinds {
+    pub const AUTHENTICATION: &str = "authentication";
+    pub const AUTHORIZATION: &str = "authorization";
+    pub const METHOD_NOT_ALLOWED: &str = "method_not_allowed";
+    pub const UNSUPPORTED_MEDIA_TYPE: &str = "unsupported_media_type";
+    pub const MISSING_PARAMETER: &str = "validation.missing_parameter";
+    pub const INVALID_PARAMETER: &str = "validation.invalid_parameter";
+    pub const MISSING_HEADER: &str = "validation.missing_header";
+    pub const INVALID_HEADER: &str = "validation.invalid_header";
+    pub const BODY_DESERIALIZATION: &str = "validation.body_deserialization";
+    pub const QUERY_DESERIALIZATION: &str = "validation.query_deserialization";
+    pub const NOT_IMPLEMENTED: &str = "not_implemented";
+    pub const REQUESTS_LIMIT_EXCEEDED: &str = "limits.requests_limit_exceeded";
+    pub const NOT_FOUND: &str = "not_found";
+    pub const INTERNAL: &str = "internal";
+    pub const TIMEOUT: &str = "timeout";
+}
+
 pub fn authentication(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::UNAUTHORIZED,
         "Invalid access token.",
         code_prefix as u32 * 10000 + offsets::AUTHENTICATION * 100,
+        Some(kinds::AUTHENTICATION),
         None,
     )
 }
 
 pub fn authorization(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::FORBIDDEN,
         "Permission denied.",
         code_prefix as u32 * 10000 + offsets::AUTHORIZATION * 100,
+        Some(kinds::AUTHORIZATION),
         None,
     )
 }
 
 pub fn method_not_allowed(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::METHOD_NOT_ALLOWED,
         "Method Not Allowed.",
         code_prefix as u32 * 10000 + offsets::METHOD_NOT_ALLOWED * 100,
+        Some(kinds::METHOD_NOT_ALLOWED),
         None,
     )
 }
 
 pub fn unsuported_media_type(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::UNSUPPORTED_MEDIA_TYPE,
         "Unsupported Media Type.",
         code_prefix as u32 * 10000 + offsets::UNSUPPORTED_MEDIA_TYPE * 100,
+        Some(kinds::UNSUPPORTED_MEDIA_TYPE),
         None,
     )
 }
@@ -56,17 +82,18 @@ pub mod validation {
 
     use crate::error::response::ErrorDetails;
 
-    use super::{offsets, Response};
+    use super::{kinds, offsets, Response};
     use warp::http::StatusCode;
 
     pub fn missing_parameter(
         code_prefix: u16,
         details: Option<HashMap<String, String>>,
     ) -> Response {
-        Response::singleton(
+        Response::singleton_with_kind(
             StatusCode::BAD_REQUEST,
             "Missing required parameter.",
             code_prefix as u32 * 10000 + offsets::VALIDATION * 100,
+            Some(kinds::MISSING_PARAMETER),
             details.map(|details| ErrorDetails::from(details)),
         )
     }
@@ -75,28 +102,73 @@ pub mod validation {
         code_prefix: u16,
         details: Option<HashMap<String, String>>,
     ) -> Response {
-        Response::singleton(
+        Response::singleton_with_kind(
             StatusCode::BAD_REQUEST,
             "Invalid parameter value.",
             code_prefix as u32 * 10000 + offsets::VALIDATION * 100 + 1,
+            Some(kinds::INVALID_PARAMETER),
             details.map(|details| ErrorDetails::from(details)),
         )
     }
 
+    /// Domain-level validation error of a request parameter.
+    ///
+    /// Reject with it (`warp::reject::custom(ParameterError::invalid("limit", "too big"))`)
+    /// and the standard error `handler` turns it into `missing_parameter`
+    /// or `invalid_parameter` response with `parameter` and `reason` details.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ParameterError {
+        Missing { name: String },
+        Invalid { name: String, reason: String },
+    }
+
+    impl ParameterError {
+        pub fn missing(name: impl Into<String>) -> Self {
+            ParameterError::Missing { name: name.into() }
+        }
+
+        pub fn invalid(name: impl Into<String>, reason: impl Into<String>) -> Self {
+            ParameterError::Invalid {
+                name: name.into(),
+                reason: reason.into(),
+            }
+        }
+
+        pub fn to_response(&self, code_prefix: u16) -> Response {
+            match self {
+                ParameterError::Missing { name } => missing_parameter(
+                    code_prefix,
+                    ErrorDetails::single_item("parameter", name).into(),
+                ),
+                ParameterError::Invalid { name, reason } => invalid_parameter(
+                    code_prefix,
+                    ErrorDetails::new()
+                        .with("parameter", name)
+                        .with("reason", reason)
+                        .into(),
+                ),
+            }
+        }
+    }
+
+    impl warp::reject::Reject for ParameterError {}
+
     pub fn missing_header(code_prefix: u16, details: Option<HashMap<String, String>>) -> Response {
-        Response::singleton(
+        Response::singleton_with_kind(
             StatusCode::BAD_REQUEST,
             "Missing required header.",
             code_prefix as u32 * 10000 + offsets::VALIDATION * 100 + 2,
+            Some(kinds::MISSING_HEADER),
             details.map(|details| ErrorDetails::from(details)),
         )
     }
 
     pub fn invalid_header(code_prefix: u16, details: Option<HashMap<String, String>>) -> Response {
-        Response::singleton(
+        Response::singleton_with_kind(
             StatusCode::BAD_REQUEST,
             "Invalid header value.",
             code_prefix as u32 * 10000 + offsets::VALIDATION * 100 + 3,
+            Some(kinds::INVALID_HEADER),
             details.map(|details| ErrorDetails::from(details)),
         )
     }
@@ -105,10 +177,11 @@ pub mod validation {
         code_prefix: u16,
         details: Option<HashMap<String, String>>,
     ) -> Response {
-        Response::singleton(
+        Response::singleton_with_kind(
             StatusCode::BAD_REQUEST,
             "Body deserialization error.",
             code_prefix as u32 * 10000 + offsets::VALIDATION * 100 + 4,
+            Some(kinds::BODY_DESERIALIZATION),
             details.map(|details| ErrorDetails::from(details)),
         )
     }
@@ -117,47 +190,52 @@ pub mod validation {
         code_prefix: u16,
         details: Option<HashMap<String, String>>,
     ) -> Response {
-        Response::singleton(
+        Response::singleton_with_kind(
             StatusCode::BAD_REQUEST,
             "Query deserialization error.",
             code_prefix as u32 * 10000 + offsets::VALIDATION * 100 + 5,
+            Some(kinds::QUERY_DESERIALIZATION),
             details.map(|details| ErrorDetails::from(details)),
         )
     }
 }
 
 pub fn not_implemented(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::NOT_IMPLEMENTED,
         "Not implemented.",
         code_prefix as u32 * 10000 + offsets::NOT_IMPLEMENTED * 100,
+        Some(kinds::NOT_IMPLEMENTED),
         None,
     )
 }
 
 pub fn requests_limit_exceeded(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::TOO_MANY_REQUESTS,
         "Requests limit exceeded.",
         code_prefix as u32 * 10000 + offsets::LIMITS * 100,
+        Some(kinds::REQUESTS_LIMIT_EXCEEDED),
         None,
     )
 }
 
 pub fn not_found(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::NOT_FOUND,
         "Not found.",
         code_prefix as u32 * 10000 + offsets::NOT_FOUND * 100,
+        Some(kinds::NOT_FOUND),
         None,
     )
 }
 
 pub fn internal(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::INTERNAL_SERVER_ERROR,
         internal::MESSAGE,
         code_prefix as u32 * 10000 + offsets::INTERNAL * 100,
+        Some(kinds::INTERNAL),
         None,
     )
 }
@@ -189,10 +267,11 @@ pub mod internal {
 }
 
 pub fn timeout(code_prefix: u16) -> Response {
-    Response::singleton(
+    Response::singleton_with_kind(
         StatusCode::GATEWAY_TIMEOUT,
         "Timed out.",
         code_prefix as u32 * 10000 + offsets::TIMEOUT * 100,
+        Some(kinds::TIMEOUT),
         None,
     )
 }
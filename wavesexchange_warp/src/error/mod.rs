@@ -3,23 +3,95 @@ mod response;
 
 // reexport
 pub use constructors::*;
-pub use response::{Error, Response};
+pub use response::{Error, ErrorDetails, Response};
 
 use futures::future::Ready;
 use std::{collections::HashMap, convert::Infallible, sync::Arc};
 use warp::{
+    http::StatusCode,
     reject::{InvalidHeader, MissingHeader, Reject},
     Rejection, Reply,
 };
+use wavesexchange_log::error;
+
+/// Which error responses are logged by the error handler
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorLogging {
+    /// Nothing is logged
+    #[default]
+    Off,
+    /// Only unhandled rejections (the ones turned into a generic internal error) are logged
+    Unhandled,
+    /// Unhandled rejections and every other response with a 5xx status are logged
+    ServerErrors,
+}
 
 pub fn handler<E: Reject>(
     error_code_prefix: u16,
     handle: impl Fn(&E) -> Response,
+) -> impl Fn(Rejection) -> Ready<Result<warp::reply::Response, Infallible>> + Clone {
+    handler_with_options(error_code_prefix, handle, ErrorLogging::Off, |_| None)
+}
+
+/// Same as `handler`, but every 5xx response is logged with error level,
+/// including the rejection and an optional context returned by `log_ctx`
+/// (e.g. request path or request id captured earlier in the filter chain).
+pub fn handler_with_logging<E: Reject>(
+    error_code_prefix: u16,
+    handle: impl Fn(&E) -> Response,
+    log_ctx: impl Fn(&Rejection) -> Option<String>,
+) -> impl Fn(Rejection) -> Ready<Result<warp::reply::Response, Infallible>> + Clone {
+    handler_with_options(
+        error_code_prefix,
+        handle,
+        ErrorLogging::ServerErrors,
+        log_ctx,
+    )
+}
+
+pub fn handler_with_options<E: Reject>(
+    error_code_prefix: u16,
+    handle: impl Fn(&E) -> Response,
+    logging: ErrorLogging,
+    log_ctx: impl Fn(&Rejection) -> Option<String>,
+) -> impl Fn(Rejection) -> Ready<Result<warp::reply::Response, Infallible>> + Clone {
+    handler_impl(error_code_prefix, handle, logging, log_ctx, log_error)
+}
+
+struct ErrorLogRecord<'a> {
+    status: StatusCode,
+    unhandled: bool,
+    rejection: &'a Rejection,
+    context: Option<String>,
+}
+
+fn log_error(rec: &ErrorLogRecord) {
+    let message = if rec.unhandled {
+        "unhandled rejection"
+    } else {
+        "server error"
+    };
+    error!(
+        "{}", message;
+        "status" => rec.status.as_u16(),
+        "rejection" => format!("{:?}", rec.rejection),
+        "context" => rec.context.as_deref()
+    );
+}
+
+fn handler_impl<E: Reject>(
+    error_code_prefix: u16,
+    handle: impl Fn(&E) -> Response,
+    logging: ErrorLogging,
+    log_ctx: impl Fn(&Rejection) -> Option<String>,
+    log: fn(&ErrorLogRecord),
 ) -> impl Fn(Rejection) -> Ready<Result<warp::reply::Response, Infallible>> + Clone {
     let handler = Arc::new(handle);
+    let log_ctx = Arc::new(log_ctx);
 
     move |r: Rejection| {
         let resp: Response;
+        let mut unhandled = false;
 
         if r.is_not_found() {
             resp = not_found(error_code_prefix.clone());
@@ -28,13 +100,17 @@ pub fn handler<E: Reject>(
             details.insert("reason".to_string(), e.to_string());
             resp = validation::body_deserialization(error_code_prefix.clone(), Some(details));
         } else if let Some(e) = r.find::<InvalidHeader>() {
-            let mut details = HashMap::with_capacity(1);
-            details.insert("header_name".to_string(), e.name().to_string());
-            resp = validation::invalid_header(error_code_prefix.clone(), Some(details));
+            let details = ErrorDetails::new()
+                .with("header_name", e.name())
+                .with("reason", e.to_string());
+            resp = validation::invalid_header(error_code_prefix.clone(), details.into());
         } else if let Some(e) = r.find::<MissingHeader>() {
-            let mut details = HashMap::with_capacity(1);
-            details.insert("header_name".to_string(), e.name().to_string());
-            resp = validation::missing_header(error_code_prefix.clone(), Some(details));
+            let details = ErrorDetails::new()
+                .with("header_name", e.name())
+                .with("reason", e.to_string());
+            resp = validation::missing_header(error_code_prefix.clone(), details.into());
+        } else if let Some(e) = r.find::<validation::ParameterError>() {
+            resp = e.to_response(error_code_prefix.clone());
         } else if let Some(crate_error) = r.find::<E>() {
             resp = handler(crate_error);
         } else {
@@ -54,9 +130,24 @@ pub fn handler<E: Reject>(
                 resp = validation::query_deserialization(error_code_prefix.clone(), Some(details));
             } else {
                 resp = internal(error_code_prefix.clone());
+                unhandled = true;
             }
         }
 
+        let should_log = match logging {
+            ErrorLogging::Off => false,
+            ErrorLogging::Unhandled => unhandled,
+            ErrorLogging::ServerErrors => unhandled || resp.status.is_server_error(),
+        };
+        if should_log {
+            log(&ErrorLogRecord {
+                status: resp.status,
+                unhandled,
+                rejection: &r,
+                context: log_ctx(&r),
+            });
+        }
+
         futures::future::ok(resp.into_response())
     }
 }
@@ -81,3 +172,163 @@ pub fn error_handler_with_serde_qs(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Debug)]
+    struct HandledError(StatusCode);
+
+    impl Reject for HandledError {}
+
+    #[derive(Debug)]
+    struct UnknownError;
+
+    impl Reject for UnknownError {}
+
+    thread_local! {
+        static CAPTURED: RefCell<Vec<(u16, bool, String, Option<String>)>> = RefCell::new(Vec::new());
+    }
+
+    fn capture(rec: &ErrorLogRecord) {
+        CAPTURED.with(|c| {
+            c.borrow_mut().push((
+                rec.status.as_u16(),
+                rec.unhandled,
+                format!("{:?}", rec.rejection),
+                rec.context.clone(),
+            ))
+        });
+    }
+
+    fn take_captured() -> Vec<(u16, bool, String, Option<String>)> {
+        CAPTURED.with(|c| c.borrow_mut().drain(..).collect())
+    }
+
+    fn run(
+        logging: ErrorLogging,
+        rejections: Vec<Rejection>,
+    ) -> (Vec<u16>, Vec<(u16, bool, String, Option<String>)>) {
+        let handle = handler_impl(
+            1,
+            |e: &HandledError| Response::singleton_with_kind(e.0, "handled", 1, None, None),
+            logging,
+            |_| Some("req_id=42".to_string()),
+            capture,
+        );
+        let statuses = rejections
+            .into_iter()
+            .map(|r| handle(r).into_inner().unwrap().status().as_u16())
+            .collect();
+        (statuses, take_captured())
+    }
+
+    fn rejections() -> Vec<Rejection> {
+        vec![
+            warp::reject::not_found(),
+            warp::reject::custom(HandledError(StatusCode::BAD_REQUEST)),
+            warp::reject::custom(HandledError(StatusCode::SERVICE_UNAVAILABLE)),
+            warp::reject::custom(UnknownError),
+        ]
+    }
+
+    #[test]
+    fn should_not_log_by_default() {
+        let (statuses, logged) = run(ErrorLogging::default(), rejections());
+        assert_eq!(statuses, vec![404, 400, 503, 500]);
+        assert!(logged.is_empty());
+    }
+
+    #[test]
+    fn should_log_unhandled_rejections() {
+        let (statuses, logged) = run(ErrorLogging::Unhandled, rejections());
+        assert_eq!(statuses, vec![404, 400, 503, 500]);
+        assert_eq!(logged.len(), 1);
+        let (status, unhandled, rejection, context) = &logged[0];
+        assert_eq!(*status, 500);
+        assert!(unhandled);
+        assert!(rejection.contains("UnknownError"), "{}", rejection);
+        assert_eq!(context.as_deref(), Some("req_id=42"));
+    }
+
+    fn body_json(rejection: Rejection) -> (u16, serde_json::Value) {
+        let handle = handler(95, |_: &HandledError| internal(95));
+        let resp = handle(rejection).into_inner().unwrap();
+        let status = resp.status().as_u16();
+        let body = tokio_test::block_on(warp::hyper::body::to_bytes(resp.into_body())).unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn should_respond_with_parameter_error_details() {
+        let (status, body) = body_json(warp::reject::custom(validation::ParameterError::invalid(
+            "limit",
+            "must be positive",
+        )));
+        assert_eq!(status, 400);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "errors": [{
+                    "message": "Invalid parameter value.",
+                    "code": 950201,
+                    "kind": "validation.invalid_parameter",
+                    "details": {
+                        "parameter": "limit",
+                        "reason": "must be positive"
+                    }
+                }]
+            })
+        );
+
+        let (status, body) = body_json(warp::reject::custom(validation::ParameterError::missing(
+            "address",
+        )));
+        assert_eq!(status, 400);
+        assert_eq!(body["errors"][0]["code"], 950200);
+        assert_eq!(
+            body["errors"][0]["details"],
+            serde_json::json!({ "parameter": "address" })
+        );
+    }
+
+    #[test]
+    fn should_respond_with_header_error_details() {
+        use warp::Filter;
+
+        let filter = warp::header::<u32>("x-some-header");
+        let rejection = tokio_test::block_on(warp::test::request().filter(&filter)).unwrap_err();
+        let (status, body) = body_json(rejection);
+        assert_eq!(status, 400);
+        assert_eq!(body["errors"][0]["code"], 950202);
+        assert_eq!(body["errors"][0]["details"]["header_name"], "x-some-header");
+        assert!(body["errors"][0]["details"]["reason"]
+            .as_str()
+            .unwrap()
+            .contains("x-some-header"));
+
+        let rejection = tokio_test::block_on(
+            warp::test::request()
+                .header("x-some-header", "not a number")
+                .filter(&filter),
+        )
+        .unwrap_err();
+        let (status, body) = body_json(rejection);
+        assert_eq!(status, 400);
+        assert_eq!(body["errors"][0]["code"], 950203);
+        assert_eq!(body["errors"][0]["details"]["header_name"], "x-some-header");
+    }
+
+    #[test]
+    fn should_log_server_errors() {
+        let (statuses, logged) = run(ErrorLogging::ServerErrors, rejections());
+        assert_eq!(statuses, vec![404, 400, 503, 500]);
+        let logged = logged
+            .into_iter()
+            .map(|(status, unhandled, _, _)| (status, unhandled))
+            .collect::<Vec<_>>();
+        assert_eq!(logged, vec![(503, false), (500, true)]);
+    }
+}
@@ -6,20 +6,47 @@ use warp::{
     reply::{json, with_status, Reply, Response as WarpResponse},
 };
 
-#[derive(Debug, Clone, Serialize)]
+/// Structured error details, e.g. the name of an invalid parameter and the reason.
+///
+/// Can be passed to any of the `validation` constructors:
+/// ```
+/// # use wavesexchange_warp::error::{validation, ErrorDetails};
+/// let details = ErrorDetails::new()
+///     .with("parameter", "limit")
+///     .with("reason", "must be positive");
+/// let resp = validation::invalid_parameter(95, details.into());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
 pub struct ErrorDetails(HashMap<String, String>);
 
 impl ErrorDetails {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     pub fn single_item(key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
-        let mut hm = HashMap::with_capacity(1);
-        hm.insert(key.as_ref().to_owned(), value.as_ref().to_owned());
-        Self(hm)
+        Self::new().with(key, value)
+    }
+
+    /// Add an item (replacing the previous value with the same key, if any)
+    pub fn with(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.0
+            .insert(key.as_ref().to_owned(), value.as_ref().to_owned());
+        self
     }
 
     pub fn add_item(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
-        let mut hm = HashMap::with_capacity(1);
-        hm.insert(key.as_ref().to_owned(), value.as_ref().to_owned());
-        Self(hm)
+        self.0
+            .insert(key.as_ref().to_owned(), value.as_ref().to_owned());
+        self.clone()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
@@ -29,14 +56,39 @@ impl From<HashMap<String, String>> for ErrorDetails {
     }
 }
 
+impl From<ErrorDetails> for HashMap<String, String> {
+    fn from(details: ErrorDetails) -> Self {
+        details.0
+    }
+}
+
+impl From<ErrorDetails> for Option<HashMap<String, String>> {
+    fn from(details: ErrorDetails) -> Self {
+        Some(details.0)
+    }
+}
+
 #[derive(Serialize, Debug, Clone)]
 pub struct Error {
     pub message: String,
     pub code: u32,
+    /// Stable machine-readable kind of the error, see `kinds`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<&'static str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     details: Option<ErrorDetails>,
 }
 
+impl Error {
+    pub fn kind(&self) -> Option<&'static str> {
+        self.kind
+    }
+
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        self.details.as_ref()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Response {
     pub status: StatusCode,
@@ -44,19 +96,32 @@ pub struct Response {
 }
 
 impl Response {
+    #[deprecated(note = "use `Response::singleton_with_kind`")]
     pub fn singleton(
         status: StatusCode,
         message: impl Into<String>,
         code: u32,
         details: Option<ErrorDetails>,
+    ) -> Self {
+        Self::singleton_with_kind(status, message, code, None, details)
+    }
+
+    /// Response with a single error, `kind` is one of `kinds` or a service-specific one
+    pub fn singleton_with_kind(
+        status: StatusCode,
+        message: impl Into<String>,
+        code: u32,
+        kind: Option<&'static str>,
+        details: Option<ErrorDetails>,
     ) -> Self {
         Self {
             errors: vec![Error {
                 message: message.into(),
-                code: code,
-                details: details,
+                code,
+                kind,
+                details,
             }],
-            status: status,
+            status,
         }
     }
 }
@@ -85,6 +150,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(deprecated)]
     fn should_response_without_details() {
         let res = Response::singleton(StatusCode::NOT_FOUND, "Not Found", 1, None).into_response();
 
@@ -93,6 +159,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn should_response_with_details() {
         let mut details = HashMap::new();
         details.insert("parameter_name".to_string(), "key".to_string());
@@ -108,4 +175,61 @@ mod tests {
         assert_eq!(res.status(), StatusCode::BAD_REQUEST);
         assert_eq!(format!("{:?}", res.body()), "Body(Full(b\"{\\\"errors\\\":[{\\\"message\\\":\\\"Bad Request\\\",\\\"code\\\":1,\\\"details\\\":{\\\"parameter_name\\\":\\\"key\\\"}}]}\"))");
     }
+
+    #[test]
+    fn should_serialize_kind() {
+        use crate::error::{kinds, not_found, validation};
+
+        let body = |res: Response| {
+            let body = res.into_response().into_body();
+            let bytes = tokio_test::block_on(warp::hyper::body::to_bytes(body)).unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let res = not_found(95);
+        assert_eq!(res.errors[0].kind(), Some(kinds::NOT_FOUND));
+        assert_eq!(
+            body(res),
+            r#"{"errors":[{"message":"Not found.","code":950400,"kind":"not_found"}]}"#
+        );
+
+        let res = validation::invalid_parameter(
+            95,
+            ErrorDetails::single_item("parameter", "limit").into(),
+        );
+        assert_eq!(
+            body(res),
+            r#"{"errors":[{"message":"Invalid parameter value.","code":950201,"kind":"validation.invalid_parameter","details":{"parameter":"limit"}}]}"#
+        );
+
+        let res = Response::singleton_with_kind(
+            StatusCode::CONFLICT,
+            "Already exists.",
+            951000,
+            Some("orders.duplicate"),
+            None,
+        );
+        assert_eq!(
+            body(res),
+            r#"{"errors":[{"message":"Already exists.","code":951000,"kind":"orders.duplicate"}]}"#
+        );
+    }
+
+    #[test]
+    fn should_build_details() {
+        let details = ErrorDetails::new()
+            .with("parameter", "limit")
+            .with("reason", "too big")
+            .with("reason", "must be positive");
+        assert_eq!(details.get("parameter"), Some("limit"));
+        assert_eq!(details.get("reason"), Some("must be positive"));
+        assert_eq!(details.get("other"), None);
+
+        let mut details = ErrorDetails::single_item("a", "1");
+        details.add_item("b", "2");
+        assert_eq!(details, ErrorDetails::new().with("a", "1").with("b", "2"));
+
+        let hm = HashMap::<String, String>::from(details.clone());
+        assert_eq!(ErrorDetails::from(hm), details);
+    }
 }
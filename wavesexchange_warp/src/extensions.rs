@@ -0,0 +1,136 @@
+//! Shared application state for the handlers, registered with `MetricsWarpBuilder::with_extension`
+//! and extracted by the `MetricsWarpBuilder::extension` filter,
+//! instead of cloning every handle into the routes by hand.
+//!
+//! The extensions belong to the builder, register them before building the routes.
+//!
+//! Example:
+//! ```no_run
+//! use std::sync::Arc;
+//! use warp::Filter;
+//! use wavesexchange_warp::MetricsWarpBuilder;
+//!
+//! struct Repo;
+//!
+//! # tokio_test::block_on(async {
+//! let builder = MetricsWarpBuilder::new().with_extension(Repo);
+//! let routes = warp::path!("hello")
+//!     .and(builder.extension::<Repo>())
+//!     .map(|_repo: Arc<Repo>| "Hello, world!");
+//! builder.with_main_routes(routes).run_async().await;
+//! # })
+//! ```
+
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+use warp::{reject::Reject, Filter, Rejection};
+
+/// Values by their types, one value per type
+#[derive(Clone, Default, Debug)]
+pub struct Extensions {
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Insert `value`, replacing the previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.insert_arc(Arc::new(value));
+    }
+
+    pub fn insert_arc<T: Send + Sync + 'static>(&mut self, value: Arc<T>) {
+        self.values.insert(TypeId::of::<T>(), value);
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        let value = self.values.get(&TypeId::of::<T>())?.clone();
+        // the value is stored by its own type id
+        Some(value.downcast::<T>().expect("extension type"))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Filter extracting the value of type `T`, rejecting with `MissingExtension` if there is none.
+    ///
+    /// The value is taken when the filter is created, the later inserts are not seen by it.
+    pub fn extension<T: Send + Sync + 'static>(
+        &self,
+    ) -> impl Filter<Extract = (Arc<T>,), Error = Rejection> + Clone {
+        let value = self.get::<T>();
+        warp::any().and_then(move || {
+            let value = value.clone();
+            async move {
+                value.ok_or_else(|| warp::reject::custom(MissingExtension(type_name::<T>())))
+            }
+        })
+    }
+}
+
+/// Rejection of the `extension` filter when no extension of the type is registered (a programming error),
+/// not handled by the error handler explicitly so it results in the standard internal error
+#[derive(Debug)]
+pub struct MissingExtension(pub &'static str);
+
+impl Reject for MissingExtension {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error;
+    use serde_json::Value;
+    use warp::{test, Reply};
+
+    struct Counter(u32);
+    struct Name(&'static str);
+    struct Unregistered;
+
+    #[derive(Debug)]
+    struct NoError;
+
+    impl Reject for NoError {}
+
+    #[tokio::test]
+    async fn should_extract_registered_extensions() {
+        let mut extensions = Extensions::default();
+        extensions.insert(Counter(42));
+        extensions.insert(Name("name"));
+
+        let filter = warp::path!("both")
+            .and(extensions.extension::<Counter>())
+            .and(extensions.extension::<Name>())
+            .map(|counter: Arc<Counter>, name: Arc<Name>| format!("{} {}", name.0, counter.0));
+        let result = test::request().path("/both").reply(&filter).await;
+        assert_eq!(result.status().as_u16(), 200);
+        assert_eq!(result.body(), "name 42");
+    }
+
+    #[tokio::test]
+    async fn should_reject_missing_extension_as_internal_error() {
+        let filter = warp::path!("missing")
+            .and(Extensions::default().extension::<Unregistered>())
+            .map(|_: Arc<Unregistered>| "unreachable")
+            .recover(error::handler(1, |_: &NoError| error::internal(1)));
+        let result = test::request().path("/missing").reply(&filter).await;
+        assert_eq!(result.status().as_u16(), 500);
+        let body = serde_json::from_slice::<Value>(result.body()).unwrap();
+        let expected = warp::hyper::body::to_bytes(error::internal(1).into_response().into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, serde_json::from_slice::<Value>(&expected).unwrap());
+    }
+
+    #[test]
+    fn check_extensions() {
+        let mut extensions = Extensions::default();
+        assert!(extensions.get::<Counter>().is_none());
+        assert!(extensions.is_empty());
+        extensions.insert(Counter(1));
+        extensions.insert(Counter(2));
+        assert_eq!(extensions.get::<Counter>().unwrap().0, 2);
+        assert!(extensions.get::<Name>().is_none());
+    }
+}
@@ -1,7 +1,9 @@
 pub mod endpoints;
 pub mod error;
+pub mod extensions;
 pub mod log;
 pub mod pagination;
+pub mod timeout;
 
 pub use endpoints::MetricsWarpBuilder;
 
@@ -1,24 +1,122 @@
-use wavesexchange_log::info;
-
-pub fn access(info: warp::log::Info) {
-    let headers = info.request_headers();
-
-    let req_id = headers
-        .get("x-request-id")
-        .map(|h| h.to_str().unwrap_or(&""));
-
-    // info.remote_addr stores the proxy ip, not client
-    let ip = headers.get("x-real-ip").map(|h| h.to_str().unwrap_or(&""));
-
-    info!(
-        "access";
-        "path" => info.path(),
-        "method" => info.method().to_string(),
-        "status" => info.status().as_u16(),
-        "ua" => info.user_agent(),
-        "latency" => info.elapsed().as_millis() as u64,
-        "req_id" => req_id,
-        "ip" => ip,
-        "protocol" => format!("{:?}", info.version())
-    );
+use warp::{http::Version, log::Info};
+use wavesexchange_log::{
+    info,
+    slog::{self, Record, Serializer, Value, KV},
+};
+
+/// Fields of the access log record of a completed request
+#[derive(Clone, Copy)]
+pub(crate) struct AccessLogRecord<'a> {
+    pub path: &'a str,
+    pub method: &'a str,
+    pub status: u16,
+    pub ua: Option<&'a str>,
+    pub latency_ms: u64,
+    pub req_id: Option<&'a str>,
+    pub ip: Option<&'a str>,
+    pub protocol: Version,
+}
+
+impl<'a> AccessLogRecord<'a> {
+    pub(crate) fn new(info: &'a Info) -> Self {
+        let headers = info.request_headers();
+
+        let req_id = headers
+            .get("x-request-id")
+            .map(|h| h.to_str().unwrap_or(&""));
+
+        // info.remote_addr stores the proxy ip, not client
+        let ip = headers.get("x-real-ip").map(|h| h.to_str().unwrap_or(&""));
+
+        AccessLogRecord {
+            path: info.path(),
+            method: info.method().as_str(),
+            status: info.status().as_u16(),
+            ua: info.user_agent(),
+            latency_ms: info.elapsed().as_millis() as u64,
+            req_id,
+            ip,
+            protocol: info.version(),
+        }
+    }
+}
+
+impl KV for AccessLogRecord<'_> {
+    fn serialize(&self, record: &Record, serializer: &mut dyn Serializer) -> slog::Result {
+        Value::serialize(&self.path, record, "path", serializer)?;
+        Value::serialize(&self.method, record, "method", serializer)?;
+        Value::serialize(&self.status, record, "status", serializer)?;
+        Value::serialize(&self.ua, record, "ua", serializer)?;
+        Value::serialize(&self.latency_ms, record, "latency", serializer)?;
+        Value::serialize(&self.req_id, record, "req_id", serializer)?;
+        Value::serialize(&self.ip, record, "ip", serializer)?;
+        serializer.emit_arguments("protocol", &format_args!("{:?}", self.protocol))
+    }
+}
+
+pub(crate) fn log_access(rec: &AccessLogRecord) {
+    info!("access"; *rec);
+}
+
+pub fn access(info: Info) {
+    log_access(&AccessLogRecord::new(&info));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    struct KvCollector(Vec<(String, String)>);
+
+    impl Serializer for KvCollector {
+        fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+            self.0.push((key.to_string(), val.to_string()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn access_log_record_kv() {
+        let rec = AccessLogRecord {
+            path: "/hello",
+            method: "GET",
+            status: 200,
+            ua: Some("curl/8.0"),
+            latency_ms: 12,
+            req_id: Some("req-42"),
+            ip: None,
+            protocol: Version::HTTP_11,
+        };
+        let mut kv = KvCollector(Vec::new());
+        rec.serialize(
+            &Record::new(
+                &slog::record_static!(slog::Level::Info, ""),
+                &format_args!("access"),
+                slog::b!(),
+            ),
+            &mut kv,
+        )
+        .unwrap();
+
+        let mut kv = kv.0;
+        kv.sort();
+        let kv = kv
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kv,
+            [
+                ("ip", "None"),
+                ("latency", "12"),
+                ("method", "GET"),
+                ("path", "/hello"),
+                ("protocol", "HTTP/1.1"),
+                ("req_id", "req-42"),
+                ("status", "200"),
+                ("ua", "curl/8.0"),
+            ]
+        );
+    }
 }
@@ -1,9 +1,20 @@
-use serde::{Deserialize, Serialize};
+use crate::error::{validation, Response};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use futures::{stream, Stream};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, future::Future};
+use warp::{http::HeaderValue, Filter, Rejection, Reply};
+
+/// Header with the total number of items, see `List::to_reply`
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct PageInfo {
     pub has_next_page: bool,
     pub last_cursor: Option<String>,
+    /// Total number of the matching items on all the pages, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -23,6 +34,7 @@ impl<T: Serialize> List<T> {
             page_info: PageInfo {
                 has_next_page,
                 last_cursor,
+                total: None,
             },
             items: items.into_iter().collect(),
         }
@@ -31,6 +43,184 @@ impl<T: Serialize> List<T> {
     pub fn from_one_page(items: impl IntoIterator<Item = T>) -> Self {
         Self::new(items, false, None)
     }
+
+    /// Set `last_cursor` to the opaque encoded form of the given cursor, see `encode_cursor`.
+    pub fn with_encoded_cursor<C: Serialize>(mut self, cursor: Option<&C>) -> Self {
+        self.page_info.last_cursor = cursor.map(encode_cursor);
+        self
+    }
+
+    /// Set the total number of the matching items on all the pages
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.page_info.total = Some(total);
+        self
+    }
+
+    /// JSON reply, with the `X-Total-Count` header if the total is known
+    pub fn to_reply(&self) -> warp::reply::Response {
+        let mut response = warp::reply::json(self).into_response();
+        if let Some(total) = self.page_info.total {
+            response
+                .headers_mut()
+                .insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+        }
+        response
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CursorError {
+    #[error("invalid cursor encoding: {0}")]
+    Encoding(#[from] base64::DecodeError),
+
+    #[error("invalid cursor data: {0}")]
+    Data(#[from] serde_json::Error),
+}
+
+impl CursorError {
+    /// Convert to the standard 'invalid parameter' validation error
+    /// with `cursor` as the parameter name.
+    pub fn to_response(&self, code_prefix: u16) -> Response {
+        let mut details = HashMap::with_capacity(2);
+        details.insert("parameter".to_string(), "cursor".to_string());
+        details.insert("reason".to_string(), self.to_string());
+        validation::invalid_parameter(code_prefix, Some(details))
+    }
+}
+
+/// Encode a cursor struct into an opaque string: url-safe base64 (without padding) of its JSON.
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+    let json = serde_json::to_vec(cursor).expect("cursor serialization");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+/// Decode a cursor struct previously encoded with `encode_cursor`.
+///
+/// Cursors come from clients, so any garbage must be handled gracefully,
+/// see `CursorError::to_response`.
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Result<T, CursorError> {
+    let json = URL_SAFE_NO_PAD.decode(cursor.trim_end_matches('='))?;
+    let cursor = serde_json::from_slice(&json)?;
+    Ok(cursor)
+}
+
+/// Page of a list requested by a client, see `pagination_params`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRequest {
+    pub limit: u32,
+    /// Cursor of the last item of the previous page
+    pub after: Option<String>,
+}
+
+/// Extract `limit` and `after` query params of a list endpoint.
+///
+/// `limit` defaults to `default_limit` and must be within `1..=max_limit`,
+/// otherwise the request is rejected with `validation::ParameterError`
+/// (turned into the standard 'invalid parameter' response by the error `handler`).
+/// Other query params are ignored, so they can be extracted by another filter.
+///
+/// ```
+/// # use warp::Filter;
+/// # use wavesexchange_warp::pagination::{pagination_params, PageRequest};
+/// let route = warp::path("items")
+///     .and(pagination_params(1000, 100))
+///     .map(|page: PageRequest| format!("{} items after {:?}", page.limit, page.after));
+/// ```
+pub fn pagination_params(
+    max_limit: u32,
+    default_limit: u32,
+) -> impl Filter<Extract = (PageRequest,), Error = Rejection> + Clone {
+    #[derive(Deserialize)]
+    struct Params {
+        limit: Option<String>,
+        after: Option<String>,
+    }
+
+    warp::query::<Params>().and_then(move |params: Params| async move {
+        let limit = match params.limit {
+            None => default_limit,
+            Some(limit) => parse_limit(&limit, max_limit).map_err(warp::reject::custom)?,
+        };
+        Ok::<_, Rejection>(PageRequest {
+            limit,
+            after: params.after.filter(|after| !after.is_empty()),
+        })
+    })
+}
+
+fn parse_limit(limit: &str, max_limit: u32) -> Result<u32, validation::ParameterError> {
+    match limit.parse::<u32>() {
+        Ok(limit) if (1..=max_limit).contains(&limit) => Ok(limit),
+        _ => Err(validation::ParameterError::invalid(
+            "limit",
+            format!("must be an integer from 1 to {max_limit}"),
+        )),
+    }
+}
+
+/// Turn a paginated endpoint into a stream of individual items.
+///
+/// `fetch` is called with the cursor of the page to load (`None` for the first page),
+/// and pages are fetched lazily: the next page is requested only after all items
+/// of the previous one have been consumed, so memory usage is bounded by the page size.
+///
+/// The stream ends after the last page (`has_next_page == false` or no `last_cursor`),
+/// or right after the first error returned by `fetch`.
+///
+/// ```
+/// # use futures::StreamExt;
+/// # use wavesexchange_warp::pagination::{into_stream, List};
+/// # tokio_test::block_on(async {
+/// let items = into_stream(|cursor: Option<String>| async move {
+///     let page = match cursor.as_deref() {
+///         None => List::new(vec![1, 2], true, Some("2".to_string())),
+///         _ => List::new(vec![3], false, None),
+///     };
+///     Ok::<_, ()>(page)
+/// });
+/// let items = items.collect::<Vec<_>>().await;
+/// assert_eq!(items, vec![Ok(1), Ok(2), Ok(3)]);
+/// # })
+/// ```
+pub fn into_stream<T, E, F, Fut>(fetch: F) -> impl Stream<Item = Result<T, E>>
+where
+    T: Serialize,
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<List<T>, E>>,
+{
+    struct State<T, F> {
+        fetch: F,
+        items: std::vec::IntoIter<T>,
+        // `Some(cursor)` if there is a page to fetch, `None` when done
+        next_page: Option<Option<String>>,
+    }
+
+    let state = State {
+        fetch,
+        items: Vec::new().into_iter(),
+        next_page: Some(None),
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.items.next() {
+                return Some((Ok(item), state));
+            }
+            let cursor = state.next_page.take()?;
+            match (state.fetch)(cursor).await {
+                Ok(list) => {
+                    let PageInfo {
+                        has_next_page,
+                        last_cursor,
+                        ..
+                    } = list.page_info;
+                    state.next_page = last_cursor.filter(|_| has_next_page).map(Some);
+                    state.items = list.items.into_iter();
+                }
+                Err(err) => return Some((Err(err), state)),
+            }
+        }
+    })
 }
 
 #[cfg(test)]
@@ -48,6 +238,7 @@ mod tests {
         let page_info = PageInfo {
             has_next_page: false,
             last_cursor: Some("last_foo".to_owned()),
+            total: None,
         };
 
         let items = vec![Foo { foo: 0 }];
@@ -60,6 +251,38 @@ mod tests {
         assert_eq!(serde_json::to_string(&list).unwrap(), "{\"type\":\"list\",\"page_info\":{\"has_next_page\":false,\"last_cursor\":\"last_foo\"},\"items\":[{\"type\":\"foo\",\"foo\":0}]}");
     }
 
+    #[test]
+    fn total_serialization() {
+        let list = List::new(vec![Foo { foo: 0 }], true, Some("c".to_owned())).with_total(42);
+        assert_eq!(
+            serde_json::to_value(&list).unwrap()["page_info"],
+            serde_json::json!({ "has_next_page": true, "last_cursor": "c", "total": 42 })
+        );
+        let deserialized =
+            serde_json::from_str::<List<Foo>>(&serde_json::to_string(&list).unwrap()).unwrap();
+        assert_eq!(deserialized.page_info.total, Some(42));
+
+        // omitted when unknown
+        let list = List::from_one_page(vec![Foo { foo: 0 }]);
+        let page_info = serde_json::to_value(&list).unwrap()["page_info"].clone();
+        assert!(page_info.get("total").is_none());
+    }
+
+    #[test]
+    fn total_count_header() {
+        let list = List::from_one_page(vec![Foo { foo: 0 }]).with_total(1000);
+        let response = list.to_reply();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["x-total-count"], "1000");
+        assert_eq!(response.headers()["content-type"], "application/json");
+        let body = tokio_test::block_on(warp::hyper::body::to_bytes(response.into_body())).unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(body["page_info"]["total"], 1000);
+
+        let response = List::from_one_page(vec![Foo { foo: 0 }]).to_reply();
+        assert!(response.headers().get("x-total-count").is_none());
+    }
+
     #[test]
     fn data_deserialization() {
         let data = "{\"type\":\"list\",\"page_info\":{\"has_next_page\":false,\"last_cursor\":\"last_foo\"},\"items\":[{\"type\":\"foo\",\"foo\":0}]}";
@@ -88,6 +311,190 @@ mod tests {
         assert_eq!(serde_json::to_string(&list).unwrap(), "{\"type\":\"list\",\"page_info\":{\"has_next_page\":false,\"last_cursor\":null},\"items\":[]}");
     }
 
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Cursor {
+        height: u32,
+        uid: i64,
+        id: String,
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor {
+            height: 3_000_000,
+            uid: -42,
+            id: "some id/with+special?chars".to_owned(),
+        };
+
+        let encoded = encode_cursor(&cursor);
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        let decoded = decode_cursor::<Cursor>(&encoded).unwrap();
+        assert_eq!(decoded, cursor);
+
+        let list = List::from_one_page(vec![Foo { foo: 1 }]).with_encoded_cursor(Some(&cursor));
+        let last_cursor = list.page_info.last_cursor.unwrap();
+        assert_eq!(decode_cursor::<Cursor>(&last_cursor).unwrap(), cursor);
+
+        let list = List::from_one_page(vec![Foo { foo: 1 }]).with_encoded_cursor(None::<&Cursor>);
+        assert_eq!(list.page_info.last_cursor, None);
+    }
+
+    #[test]
+    fn cursor_tampered() {
+        let encoded = encode_cursor(&Cursor {
+            height: 1,
+            uid: 2,
+            id: "3".to_owned(),
+        });
+
+        // not a base64 at all
+        let err = decode_cursor::<Cursor>("%%%garbage%%%").unwrap_err();
+        assert!(matches!(err, CursorError::Encoding(_)));
+
+        // valid base64, but not a json
+        let err = decode_cursor::<Cursor>(&URL_SAFE_NO_PAD.encode("garbage")).unwrap_err();
+        assert!(matches!(err, CursorError::Data(_)));
+
+        // valid json, but of a different shape
+        let err = decode_cursor::<Cursor>(&encode_cursor(&Foo { foo: 1 })).unwrap_err();
+        assert!(matches!(err, CursorError::Data(_)));
+
+        // truncated cursor
+        let err = decode_cursor::<Cursor>(&encoded[..encoded.len() - 3]).unwrap_err();
+        assert!(matches!(
+            err,
+            CursorError::Encoding(_) | CursorError::Data(_)
+        ));
+
+        let resp = err.to_response(95);
+        assert_eq!(resp.status, warp::http::StatusCode::BAD_REQUEST);
+        assert_eq!(resp.errors.len(), 1);
+        assert_eq!(resp.errors[0].code, 950201);
+        let error = serde_json::to_value(&resp.errors[0]).unwrap();
+        assert_eq!(error["details"]["parameter"], "cursor");
+    }
+
+    #[test]
+    fn stream_fetches_pages_lazily() {
+        use futures::StreamExt;
+        use std::sync::{Arc, Mutex};
+
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let items = into_stream({
+            let requested = requested.clone();
+            move |cursor: Option<String>| {
+                requested.lock().unwrap().push(cursor.clone());
+                async move {
+                    match cursor.as_deref() {
+                        None => Ok(List::new(vec![1, 2], true, Some("2".to_owned()))),
+                        Some("2") => Ok(List::new(vec![], true, Some("2a".to_owned()))),
+                        Some("2a") => Ok(List::new(vec![3], true, Some("3".to_owned()))),
+                        Some("3") => Ok(List::new(vec![4], false, Some("4".to_owned()))),
+                        Some(_) => Err("unexpected cursor"),
+                    }
+                }
+            }
+        });
+        let requested = move || requested.lock().unwrap().clone();
+
+        tokio_test::block_on(async {
+            let mut items = Box::pin(items);
+            assert!(requested().is_empty());
+
+            assert_eq!(items.next().await, Some(Ok(1)));
+            assert_eq!(requested(), vec![None]);
+            assert_eq!(items.next().await, Some(Ok(2)));
+            assert_eq!(requested(), vec![None]);
+
+            // empty pages are skipped
+            assert_eq!(items.next().await, Some(Ok(3)));
+            assert_eq!(
+                requested(),
+                vec![None, Some("2".to_owned()), Some("2a".to_owned())]
+            );
+
+            assert_eq!(items.next().await, Some(Ok(4)));
+            assert_eq!(items.next().await, None);
+            assert_eq!(requested().len(), 4);
+        });
+    }
+
+    #[test]
+    fn stream_stops_on_error() {
+        use futures::StreamExt;
+
+        let items = into_stream(|cursor: Option<String>| async move {
+            match cursor {
+                None => Ok(List::new(vec![1], true, Some("1".to_owned()))),
+                Some(_) => Err("fetch failed"),
+            }
+        });
+
+        let items = tokio_test::block_on(items.collect::<Vec<_>>());
+        assert_eq!(items, vec![Ok(1), Err("fetch failed")]);
+    }
+
+    #[test]
+    fn pagination_params_filter() {
+        tokio_test::block_on(async {
+            use crate::error::validation::ParameterError;
+            use warp::test::request;
+
+            let filter = pagination_params(1000, 100);
+            let page = |limit, after: Option<&str>| PageRequest {
+                limit,
+                after: after.map(ToOwned::to_owned),
+            };
+
+            let res = request().path("/items").filter(&filter).await.unwrap();
+            assert_eq!(res, page(100, None));
+            let res = request().path("/items?").filter(&filter).await.unwrap();
+            assert_eq!(res, page(100, None));
+            let res = request()
+                .path("/items?limit=1000&after=abc_-")
+                .filter(&filter)
+                .await
+                .unwrap();
+            assert_eq!(res, page(1000, Some("abc_-")));
+            let res = request()
+                .path("/items?after=&limit=1")
+                .filter(&filter)
+                .await
+                .unwrap();
+            assert_eq!(res, page(1, None));
+
+            for limit in ["1001", "0", "-1", "ten", "", "1.5", "99999999999"] {
+                let rej = request()
+                    .path(&format!("/items?limit={limit}"))
+                    .filter(&filter)
+                    .await
+                    .unwrap_err();
+                let err = rej.find::<ParameterError>().expect("parameter error");
+                assert!(
+                    matches!(err, ParameterError::Invalid { name, .. } if name == "limit"),
+                    "limit={limit}: {err:?}"
+                );
+            }
+
+            // other params are left to other filters
+            #[derive(Debug, PartialEq, Deserialize)]
+            struct Other {
+                sort: String,
+            }
+            let filter = filter.and(warp::query::<Other>());
+            let (page_req, other) = request()
+                .path("/items?sort=desc&limit=5&after=xyz")
+                .filter(&filter)
+                .await
+                .unwrap();
+            assert_eq!(page_req, page(5, Some("xyz")));
+            assert_eq!(other.sort, "desc");
+        });
+    }
+
     #[test]
     fn empty_data_deserialization() {
         let data = "{\"type\":\"list\",\"page_info\":{\"has_next_page\":false,\"last_cursor\":null},\"items\":[]}";
@@ -0,0 +1,39 @@
+//! Bounding the processing time of the handlers with the standard timeout error response.
+
+use crate::error;
+use std::{future::Future, time::Duration};
+use warp::Reply;
+
+/// Respond with the standard timeout error (`504 Gateway Timeout`, see `error::timeout`)
+/// if the `reply` future of a handler is not ready in time. The future is dropped then,
+/// cancelling any work in progress. Errors (e.g. rejections) are passed as is.
+///
+/// Such requests are counted in the `MetricsWarpBuilder` metrics with the `504` status.
+///
+/// Example:
+/// ```no_run
+/// use std::time::Duration;
+/// use warp::{Filter, Rejection};
+/// use wavesexchange_warp::timeout::with_timeout;
+///
+/// async fn handler() -> Result<&'static str, Rejection> {
+///     Ok("Hello, world!")
+/// }
+///
+/// let routes = warp::path!("hello")
+///     .and_then(|| with_timeout(Duration::from_secs(5), 95, handler()));
+/// ```
+pub async fn with_timeout<R, E, F>(
+    timeout: Duration,
+    code_prefix: u16,
+    reply: F,
+) -> Result<Box<dyn Reply>, E>
+where
+    R: Reply + 'static,
+    F: Future<Output = Result<R, E>>,
+{
+    match tokio::time::timeout(timeout, reply).await {
+        Ok(reply) => reply.map(|reply| Box::new(reply) as Box<dyn Reply>),
+        Err(_) => Ok(Box::new(error::timeout(code_prefix))),
+    }
+}